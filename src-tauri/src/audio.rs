@@ -1,80 +1,1187 @@
+use crate::audiofile::FileAudio;
+use crate::backend::TranscriptionError;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use tokio::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{oneshot, Notify};
 
+#[derive(Clone, Serialize)]
+pub struct AudioDeviceInfo {
+    /// Identifier to pass back when selecting this device.
+    pub id: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// How multi-channel input is folded down to mono
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelStrategy {
+    /// Mean of all channels
+    #[default]
+    Average,
+    /// Only the given channel (0-based), for interfaces with a dead input
+    Channel(u16),
+    /// Loudest channel per frame, so a silent channel doesn't halve the level
+    Max,
+}
+
+impl ChannelStrategy {
+    pub fn mix(self, frame: &[f32]) -> f32 {
+        match self {
+            ChannelStrategy::Average => frame.iter().sum::<f32>() / frame.len() as f32,
+            ChannelStrategy::Channel(index) => frame.get(index as usize).copied().unwrap_or(0.0),
+            ChannelStrategy::Max => {
+                frame.iter().copied().fold(
+                    0.0,
+                    |loudest, s| if s.abs() > loudest.abs() { s } else { loudest },
+                )
+            }
+        }
+    }
+}
+
+/// Resampler trade-off between latency/CPU and fidelity
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleQuality {
+    /// Short filter: least delay and CPU, a softer top end
+    Fast,
+    #[default]
+    Balanced,
+    /// Long filter with a steeper cutoff, for accuracy-critical dictation
+    HighQuality,
+}
+
+impl ResampleQuality {
+    /// Filter taps on each side of the output sample
+    fn half_taps(self) -> usize {
+        match self {
+            ResampleQuality::Fast => 4,
+            ResampleQuality::Balanced => 16,
+            ResampleQuality::HighQuality => 48,
+        }
+    }
+
+    /// Cutoff as a fraction of the output Nyquist; longer filters can sit
+    /// closer to it without aliasing
+    fn cutoff_margin(self) -> f64 {
+        match self {
+            ResampleQuality::Fast => 0.85,
+            ResampleQuality::Balanced => 0.94,
+            ResampleQuality::HighQuality => 0.97,
+        }
+    }
+
+    fn window(self, x: f64) -> f64 {
+        match self {
+            ResampleQuality::HighQuality => blackman(x),
+            _ => hann(x),
+        }
+    }
+}
+
+/// Rate audio is delivered at unless configured otherwise
+pub const DEFAULT_SAMPLE_RATE: u32 = 16000;
+
+/// Sample encoding streamed to the transcription service
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+    /// 16-bit signed little-endian PCM
+    #[default]
+    PcmS16le,
+    /// 32-bit float little-endian PCM, skipping 16-bit quantization
+    PcmF32le,
+}
+
+impl AudioFormat {
+    /// Name SONIOX expects in the session config
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AudioFormat::PcmS16le => "pcm_s16le",
+            AudioFormat::PcmF32le => "pcm_f32le",
+        }
+    }
+
+    /// Encoded silence of the given length
+    pub fn silence(self, samples: usize) -> Vec<u8> {
+        let bytes_per_sample = match self {
+            AudioFormat::PcmS16le => 2,
+            AudioFormat::PcmF32le => 4,
+        };
+        vec![0; samples * bytes_per_sample]
+    }
+}
+
+/// Which microphone to record and how to process its signal
+#[derive(Clone, PartialEq)]
+pub struct CaptureConfig {
+    /// Device id from `list_input_devices`; None uses the system default
+    pub input_device: Option<String>,
+    pub channel_strategy: ChannelStrategy,
+    /// Fixed gain in dB applied before anything else
+    pub gain_db: f32,
+    /// Continuously scale the signal toward `AUTO_GAIN_TARGET`
+    pub auto_gain: bool,
+    /// Rate the captured audio is resampled to, in Hz
+    pub sample_rate: u32,
+    /// Float also keeps the unquantized samples in each chunk for streaming
+    pub audio_format: AudioFormat,
+    /// Add TPDF dither before quantizing to 16 bits
+    pub dither: bool,
+    pub resample_quality: ResampleQuality,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        CaptureConfig {
+            input_device: None,
+            channel_strategy: ChannelStrategy::default(),
+            gain_db: 0.0,
+            auto_gain: false,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            audio_format: AudioFormat::default(),
+            dither: false,
+            resample_quality: ResampleQuality::default(),
+        }
+    }
+}
+
+// Peak level auto-gain aims for
+const AUTO_GAIN_TARGET: f32 = 0.5;
+// Limits for auto-gain so background noise isn't blown up
+const AUTO_GAIN_MIN: f32 = 0.25;
+const AUTO_GAIN_MAX: f32 = 10.0;
+// Peaks below this are treated as silence and don't raise the gain further
+const AUTO_GAIN_NOISE_FLOOR: f32 = 0.02;
+// Per-callback decay of the tracked peak (slow release)
+const AUTO_GAIN_PEAK_DECAY: f32 = 0.995;
+// Fraction of the way the gain moves toward its target per callback
+const AUTO_GAIN_SMOOTHING: f32 = 0.05;
+
+/// Convert a gain in decibels to a linear multiplier
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Tracks a running peak and scales quiet input toward a target level
+struct AutoGain {
+    peak: f32,
+    gain: f32,
+}
+
+impl AutoGain {
+    fn new() -> Self {
+        AutoGain {
+            peak: AUTO_GAIN_NOISE_FLOOR,
+            gain: 1.0,
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        let block_peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        self.peak = block_peak.max(self.peak * AUTO_GAIN_PEAK_DECAY);
+
+        let desired = (AUTO_GAIN_TARGET / self.peak.max(AUTO_GAIN_NOISE_FLOOR))
+            .clamp(AUTO_GAIN_MIN, AUTO_GAIN_MAX);
+        self.gain += (desired - self.gain) * AUTO_GAIN_SMOOTHING;
+        // React instantly to a loud block rather than clip it
+        if block_peak * self.gain > 1.0 {
+            self.gain = 1.0 / block_peak;
+        }
+
+        for sample in samples.iter_mut() {
+            *sample *= self.gain;
+        }
+    }
+}
+
+/// Triangular (TPDF) dither: the sum of two independent uniform values of
+/// ±½ LSB, which makes the 16-bit rounding error independent of the signal
+/// instead of a distortion of it. Audible only on very quiet input.
+struct Dither {
+    // xorshift32 state; never zero
+    state: u32,
+}
+
+impl Dither {
+    fn new() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        Dither { state: seed | 1 }
+    }
+
+    // Uniform in [-0.5, 0.5]
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32 - 0.5
+    }
+
+    fn next(&mut self) -> f32 {
+        self.uniform() + self.uniform()
+    }
+}
+
+/// Quantize a float sample to 16 bits, rounding to nearest rather than
+/// truncating toward zero
+fn quantize(sample: f32, dither: Option<&mut Dither>) -> i16 {
+    let mut scaled = sample.clamp(-1.0, 1.0) * 32767.0;
+    if let Some(dither) = dither {
+        scaled += dither.next();
+    }
+    scaled.round().clamp(-32767.0, 32767.0) as i16
+}
+
+// How often to look for a microphone again after the one in use disconnected
+const DEVICE_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// How often the capture thread checks whether to stop, so the longest an
+/// input device stays open after capture is stopped
+pub const CAPTURE_STOP_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+// Payload of `device-changed`, sent when capture moved to another microphone
+#[derive(Clone, Serialize)]
+struct DeviceChangedEvent {
+    device: String,
+}
+
+// Minimum interval between `network-slow` events while audio is being dropped
+const NETWORK_SLOW_NOTICE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+// Payload of `network-slow`, sent when the session can't keep up with the
+// microphone (usually a stalled connection) and audio had to be dropped
+#[derive(Clone, Serialize)]
+struct NetworkSlowEvent {
+    /// Audio lost since the previous notice
+    dropped_ms: u64,
+}
+
+/// Tracks audio dropped because the session's queue was full, reporting it
+/// at most every `NETWORK_SLOW_NOTICE_INTERVAL`
+struct DropReporter {
+    sample_rate: u32,
+    dropped_samples: u64,
+    last_notice: Option<std::time::Instant>,
+}
+
+impl DropReporter {
+    fn new(sample_rate: u32) -> Self {
+        DropReporter {
+            sample_rate,
+            dropped_samples: 0,
+            last_notice: None,
+        }
+    }
+
+    fn dropped(&mut self, app: &AppHandle, samples: usize) {
+        self.dropped_samples += samples as u64;
+        if self
+            .last_notice
+            .is_some_and(|t| t.elapsed() < NETWORK_SLOW_NOTICE_INTERVAL)
+        {
+            return;
+        }
+        self.last_notice = Some(std::time::Instant::now());
+        let dropped_ms = self.dropped_samples * 1000 / self.sample_rate as u64;
+        self.dropped_samples = 0;
+        eprintln!(
+            "Audio queue full; dropped {}ms of audio (network too slow?)",
+            dropped_ms
+        );
+        let _ = app.emit("network-slow", NetworkSlowEvent { dropped_ms });
+    }
+}
+
+// Where the capture thread delivers audio; None discards it
+type ChunkSink = Arc<Mutex<Option<AudioWriter>>>;
+
+// Audio the queue between capture and session holds before dropping any, so
+// a session stalled on the network or the scheduler doesn't lose audio
+const AUDIO_QUEUE_MS: u32 = 3000;
+// Longest chunk handed to the session at once when it's catching up
+const MAX_CHUNK_MS: u32 = 100;
+
+/// Fixed-size ring of samples between the capture callback and the session.
+/// The callback never waits: what doesn't fit is dropped and counted.
+struct AudioQueue {
+    ring: Mutex<AudioRing>,
+    ready: Notify,
+    // Samples lost to overruns over the queue's lifetime
+    dropped: AtomicU64,
+}
+
+struct AudioRing {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    // The writer is gone, so once empty the stream is over
+    closed: bool,
+}
+
+impl AudioQueue {
+    fn new(sample_rate: u32) -> Arc<Self> {
+        let capacity = (sample_rate * AUDIO_QUEUE_MS / 1000) as usize;
+        Arc::new(AudioQueue {
+            ring: Mutex::new(AudioRing {
+                samples: VecDeque::with_capacity(capacity),
+                capacity,
+                closed: false,
+            }),
+            ready: Notify::new(),
+            dropped: AtomicU64::new(0),
+        })
+    }
+}
+
+/// Capture's end of an `AudioQueue`; dropping it ends the stream once the
+/// receiver has drained what's left
+struct AudioWriter(Arc<AudioQueue>);
+
+impl AudioWriter {
+    /// Queue as many samples as fit, returning how many did
+    fn push(&self, samples: &[f32]) -> usize {
+        let Ok(mut ring) = self.0.ring.lock() else {
+            return 0;
+        };
+        let count = samples.len().min(ring.capacity - ring.samples.len());
+        ring.samples.extend(&samples[..count]);
+        drop(ring);
+        if count > 0 {
+            self.0.ready.notify_one();
+        }
+        count
+    }
+
+    /// Queue samples from the capture callback, counting any that don't fit
+    fn push_or_drop(&self, samples: &[f32]) -> usize {
+        let dropped = samples.len() - self.push(samples);
+        if dropped > 0 {
+            self.0.dropped.fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+        dropped
+    }
+}
+
+impl Drop for AudioWriter {
+    fn drop(&mut self) {
+        if let Ok(mut ring) = self.0.ring.lock() {
+            ring.closed = true;
+        }
+        self.0.ready.notify_one();
+    }
+}
+
+// How much microphone audio the mute check looks at after recording starts
+const MUTE_CHECK_MS: u32 = 2000;
+// Loudest sample below which that audio counts as muted (about -80 dBFS).
+// Even a quiet room with no one speaking comes in well above this; a muted
+// input delivers digital silence.
+const MUTE_PEAK_LEVEL: f32 = 0.0001;
+
+/// Watches the start of a microphone session for the near-perfect silence of
+/// a muted input, and emits `possibly-muted` once if that's all it hears.
+/// cpal can't read the OS mute switch, so the audio itself is the only sign.
+struct MuteCheck {
+    app: AppHandle,
+    samples_left: usize,
+    peak: f32,
+}
+
+impl MuteCheck {
+    fn new(app: AppHandle, sample_rate: u32) -> Self {
+        MuteCheck {
+            app,
+            samples_left: (sample_rate * MUTE_CHECK_MS / 1000) as usize,
+            peak: 0.0,
+        }
+    }
+
+    /// Take in the next samples; returns true once the check is over
+    fn observe(&mut self, samples: &[f32]) -> bool {
+        let count = samples.len().min(self.samples_left);
+        self.peak = samples[..count]
+            .iter()
+            .fold(self.peak, |peak, s| peak.max(s.abs()));
+        self.samples_left -= count;
+        if self.samples_left > 0 {
+            return false;
+        }
+        if self.peak < MUTE_PEAK_LEVEL {
+            eprintln!(
+                "Microphone looks muted (peak {:.6} over the first {}ms)",
+                self.peak, MUTE_CHECK_MS
+            );
+            let _ = self.app.emit("possibly-muted", ());
+        }
+        true
+    }
+}
+
+/// The session's end of the capture queue. Quantizes (and dithers) the
+/// float audio into chunks and measures their level.
+pub struct AudioReceiver {
+    queue: Arc<AudioQueue>,
+    sample_rate: u32,
+    max_chunk: usize,
+    keep_float: bool,
+    dither: Option<Dither>,
+    // Only for microphones; a file may well start with silence
+    mute_check: Option<MuteCheck>,
+}
+
+impl AudioReceiver {
+    fn new(queue: Arc<AudioQueue>, config: &CaptureConfig, mute_check: Option<MuteCheck>) -> Self {
+        AudioReceiver {
+            queue,
+            sample_rate: config.sample_rate,
+            max_chunk: (config.sample_rate * MAX_CHUNK_MS / 1000).max(1) as usize,
+            keep_float: config.audio_format == AudioFormat::PcmF32le,
+            dither: config.dither.then(Dither::new),
+            mute_check,
+        }
+    }
+
+    /// Wait for the next chunk; None once capture has stopped and
+    /// everything queued before that was received
+    pub async fn recv(&mut self) -> Option<AudioChunk> {
+        loop {
+            match self.try_recv() {
+                Ok(chunk) => return Some(chunk),
+                Err(TryRecvError::Disconnected) => return None,
+                // A push while not waiting leaves a permit, so none is missed
+                Err(TryRecvError::Empty) => self.queue.ready.notified().await,
+            }
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<AudioChunk, TryRecvError> {
+        let Ok(mut ring) = self.queue.ring.lock() else {
+            return Err(TryRecvError::Disconnected);
+        };
+        if ring.samples.is_empty() {
+            return Err(if ring.closed {
+                TryRecvError::Disconnected
+            } else {
+                TryRecvError::Empty
+            });
+        }
+        let count = ring.samples.len().min(self.max_chunk);
+        let float_samples: Vec<f32> = ring.samples.drain(..count).collect();
+        drop(ring);
+
+        if let Some(check) = self.mute_check.as_mut() {
+            if check.observe(&float_samples) {
+                self.mute_check = None;
+            }
+        }
+
+        Ok(AudioChunk {
+            samples: float_samples
+                .iter()
+                .map(|&sample| quantize(sample, self.dither.as_mut()))
+                .collect(),
+            level: calculate_audio_level(&float_samples),
+            float_samples: self.keep_float.then_some(float_samples),
+        })
+    }
+}
+
+impl Drop for AudioReceiver {
+    fn drop(&mut self) {
+        let dropped = self.queue.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            println!(
+                "Dropped {}ms of audio this session",
+                dropped * 1000 / self.sample_rate as u64
+            );
+        }
+    }
+}
+
+/// A running microphone capture. Capture stops when this is stopped or dropped.
 pub struct AudioCapture {
-    stream: Option<cpal::Stream>,
+    sink: ChunkSink,
+    // None when borrowed from a `WarmMic`, which keeps the device open
+    running: Option<Arc<AtomicBool>>,
 }
 
 impl AudioCapture {
-    pub fn new() -> Self {
-        AudioCapture { stream: None }
+    /// Stop delivering audio and, unless the mic is kept warm, close the
+    /// device; chunks already queued can still be received
+    pub fn stop(&self) {
+        if let Ok(mut sink) = self.sink.lock() {
+            *sink = None;
+        }
+        if let Some(running) = &self.running {
+            running.store(false, Ordering::Relaxed);
+        }
     }
+}
 
-    /// Start capturing audio from the default input device
-    /// Returns a receiver that yields audio samples as i16 PCM data
-    pub fn start(&mut self) -> Result<mpsc::Receiver<Vec<i16>>, String> {
-        let host = cpal::default_host();
+impl Drop for AudioCapture {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
 
-        let device = host
-            .default_input_device()
-            .ok_or("No input device available")?;
+// Audio data with level
+pub struct AudioChunk {
+    pub samples: Vec<i16>,
+    /// The same audio before 16-bit quantization (not clamped), present
+    /// when capturing for `AudioFormat::PcmF32le`
+    pub float_samples: Option<Vec<f32>>,
+    /// Input level of the chunk (0.0 to 1.0)
+    pub level: f32,
+}
+
+/// Calculate RMS audio level from mono samples (returns 0.0 to 1.0)
+fn calculate_audio_level(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    // Calculate RMS
+    let sum_squares: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+    let rms = (sum_squares / samples.len() as f64).sqrt();
+
+    // Apply some scaling to make it more sensitive
+    let normalized = rms * 4.0;
+    normalized.min(1.0) as f32
+}
+
+/// An input stream kept open between dictations so recording starts without
+/// waiting for the device. Audio is discarded while no session is attached.
+pub struct WarmMic {
+    config: CaptureConfig,
+    sink: ChunkSink,
+    running: Arc<AtomicBool>,
+}
 
-        println!("Using input device: {}", device.name().unwrap_or_default());
+impl WarmMic {
+    /// Open the device with `config` and keep it running until dropped
+    pub async fn open(app: AppHandle, config: CaptureConfig) -> Result<Self, TranscriptionError> {
+        let sink: ChunkSink = Arc::new(Mutex::new(None));
+        let running = Arc::new(AtomicBool::new(true));
+        open_input(app, config.clone(), sink.clone(), running.clone()).await?;
+        println!("Microphone kept warm");
+        Ok(WarmMic {
+            config,
+            sink,
+            running,
+        })
+    }
+
+    pub fn config(&self) -> &CaptureConfig {
+        &self.config
+    }
+}
+
+impl Drop for WarmMic {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Where a session's audio comes from
+#[derive(Clone)]
+pub enum AudioSource {
+    /// The configured microphone, using the warm stream if one is open
+    Microphone(Option<Arc<WarmMic>>),
+    /// A decoded file, played in as if it were being spoken
+    File(Arc<FileAudio>),
+}
+
+// Files are streamed this many times faster than real time, in chunks of this length
+const FILE_PLAYBACK_SPEED: u32 = 4;
+const FILE_CHUNK_MS: u32 = 100;
+
+/// Capture mono audio at `config.sample_rate` from the selected (or default) input device on a
+/// dedicated thread, downmixing and resampling whatever the device provides.
+///
+/// Uses the warm mic instead when it is open with the same config, or plays a
+/// file when `source` is one. Otherwise resolves once the input stream is
+/// running, or with an error if no microphone could be opened.
+pub async fn start_capture(
+    app: AppHandle,
+    config: CaptureConfig,
+    source: &AudioSource,
+) -> Result<(AudioCapture, AudioReceiver), TranscriptionError> {
+    let queue = AudioQueue::new(config.sample_rate);
+    let audio_tx = AudioWriter(queue.clone());
+    let mute_check = matches!(source, AudioSource::Microphone(_))
+        .then(|| MuteCheck::new(app.clone(), config.sample_rate));
+    let audio_rx = AudioReceiver::new(queue, &config, mute_check);
+
+    let warm = match source {
+        AudioSource::Microphone(warm) => warm.as_deref(),
+        AudioSource::File(file) => {
+            let sink: ChunkSink = Arc::new(Mutex::new(Some(audio_tx)));
+            let running = Arc::new(AtomicBool::new(true));
+            play_file(file.clone(), &config, sink.clone(), running.clone());
+            return Ok((
+                AudioCapture {
+                    sink,
+                    running: Some(running),
+                },
+                audio_rx,
+            ));
+        }
+    };
+
+    if let Some(warm) = warm {
+        if warm.config == config && warm.running.load(Ordering::Relaxed) {
+            if let Ok(mut sink) = warm.sink.lock() {
+                *sink = Some(audio_tx);
+                return Ok((
+                    AudioCapture {
+                        sink: warm.sink.clone(),
+                        running: None,
+                    },
+                    audio_rx,
+                ));
+            }
+        }
+    }
 
-        // We want 16kHz mono for SONIOX
-        let config = cpal::StreamConfig {
-            channels: 1,
-            sample_rate: cpal::SampleRate(16000),
-            buffer_size: cpal::BufferSize::Default,
+    let sink: ChunkSink = Arc::new(Mutex::new(Some(audio_tx)));
+    let running = Arc::new(AtomicBool::new(true));
+    open_input(app, config, sink.clone(), running.clone()).await?;
+    Ok((
+        AudioCapture {
+            sink,
+            running: Some(running),
+        },
+        audio_rx,
+    ))
+}
+
+/// Feed a decoded file to `sink` on its own thread, resampled and quantized
+/// like microphone audio, and mark it finished once all of it went out
+fn play_file(
+    file: Arc<FileAudio>,
+    config: &CaptureConfig,
+    sink: ChunkSink,
+    running: Arc<AtomicBool>,
+) {
+    let target_rate = config.sample_rate;
+    let quality = config.resample_quality;
+
+    std::thread::spawn(move || {
+        let samples =
+            Resampler::new(file.sample_rate(), target_rate, quality).process(file.samples());
+        let chunk_len = (target_rate * FILE_CHUNK_MS / 1000) as usize;
+        let pause = std::time::Duration::from_millis((FILE_CHUNK_MS / FILE_PLAYBACK_SPEED) as u64);
+
+        for mut chunk in samples.chunks(chunk_len.max(1)) {
+            // Unlike a microphone, a file can wait for the session to catch up
+            loop {
+                if !running.load(Ordering::Relaxed) {
+                    return;
+                }
+                let written = match sink.lock() {
+                    Ok(sink) => match sink.as_ref() {
+                        Some(writer) => writer.push(chunk),
+                        None => return,
+                    },
+                    Err(_) => return,
+                };
+                chunk = &chunk[written..];
+                std::thread::sleep(pause);
+                if chunk.is_empty() {
+                    break;
+                }
+            }
+        }
+        println!("Finished playing audio file");
+        file.mark_finished();
+    });
+}
+
+/// Run the input stream on its own thread until `running` clears, feeding `sink`.
+///
+/// If the device goes away mid-capture (a USB mic unplugged, a laptop
+/// undocked), the stream is rebuilt on the selected device once it's back or
+/// on the new default, and `device-changed` is emitted. The receiver just sees
+/// a gap in the audio, so the transcription session carries on.
+async fn open_input(
+    app: AppHandle,
+    config: CaptureConfig,
+    sink: ChunkSink,
+    running: Arc<AtomicBool>,
+) -> Result<(), TranscriptionError> {
+    // The thread reports whether the stream actually started
+    let (ready_tx, ready_rx) = oneshot::channel::<Result<(), TranscriptionError>>();
+
+    // Start audio capture in a blocking thread
+    std::thread::spawn(move || {
+        // Set from the stream's error callback when the device disappears
+        let device_lost = Arc::new(AtomicBool::new(false));
+
+        // Held only to keep the stream running
+        let mut _stream = match build_stream(&app, &config, &sink, &device_lost) {
+            Ok((stream, _)) => Some(stream),
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+                return;
+            }
+        };
+        println!("Audio capture started");
+        let _ = ready_tx.send(Ok(()));
+
+        // Keep the stream alive while recording
+        let mut last_attempt = std::time::Instant::now();
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(CAPTURE_STOP_POLL);
+            if !device_lost.load(Ordering::Relaxed)
+                || last_attempt.elapsed() < DEVICE_RETRY_INTERVAL
+            {
+                continue;
+            }
+            last_attempt = std::time::Instant::now();
+
+            // Release the dead stream before opening a new one
+            _stream = None;
+            device_lost.store(false, Ordering::Relaxed);
+            match build_stream(&app, &config, &sink, &device_lost) {
+                Ok((new_stream, device)) => {
+                    println!("Audio capture moved to {}", device);
+                    let _ = app.emit("device-changed", DeviceChangedEvent { device });
+                    _stream = Some(new_stream);
+                }
+                Err(e) => {
+                    // Nothing to switch to yet; try again shortly
+                    eprintln!("Could not reopen the microphone: {}", e);
+                    device_lost.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        println!("Audio capture stopped");
+    });
+
+    match ready_rx.await {
+        Ok(result) => result,
+        Err(_) => Err(TranscriptionError::Audio(
+            "Audio capture thread exited unexpectedly".to_string(),
+        )),
+    }
+}
+
+/// Open and start an input stream for `config` that delivers to `sink`,
+/// returning it with the name of the device used. The stream flags
+/// `device_lost` if the device disconnects.
+fn build_stream(
+    app: &AppHandle,
+    config: &CaptureConfig,
+    sink: &ChunkSink,
+    device_lost: &Arc<AtomicBool>,
+) -> Result<(cpal::Stream, String), TranscriptionError> {
+    let (device, warning) =
+        choose_input_device(&cpal::default_host(), config.input_device.as_deref())?;
+    if let Some(warning) = warning {
+        eprintln!("{}", warning);
+        let _ = app.emit("input-device-warning", warning);
+    }
+    let device_name = device.name().unwrap_or_default();
+    println!("Using input device: {}", device_name);
+
+    // Get the default input config (usually 44100 or 48000 Hz)
+    let supported_config = device.default_input_config().map_err(|e| {
+        TranscriptionError::Audio(format!("Failed to get default input config: {}", e))
+    })?;
+
+    let sample_rate = supported_config.sample_rate().0;
+    let channels = supported_config.channels();
+    let sample_format = supported_config.sample_format();
+    println!(
+        "Using audio config: {} Hz, {} channels, {:?}",
+        sample_rate, channels, sample_format
+    );
+
+    // A channel the device doesn't have would only ever yield silence
+    let mut strategy = config.channel_strategy;
+    if let ChannelStrategy::Channel(index) = strategy {
+        if index >= channels {
+            let warning = format!(
+                "Input channel {} not available ({} channels); averaging instead",
+                index + 1,
+                channels
+            );
+            eprintln!("{}", warning);
+            let _ = app.emit("input-device-warning", warning);
+            strategy = ChannelStrategy::Average;
+        }
+    }
+
+    let stream_config = cpal::StreamConfig {
+        channels,
+        sample_rate: cpal::SampleRate(sample_rate),
+        buffer_size: cpal::BufferSize::Default,
+    };
+
+    let mut resampler = Resampler::new(sample_rate, config.sample_rate, config.resample_quality);
+    let gain = db_to_linear(config.gain_db);
+    let mut auto_gain = config.auto_gain.then(AutoGain::new);
+    let sink = sink.clone();
+    let mut drops = DropReporter::new(config.sample_rate);
+    let drop_app = app.clone();
+
+    // Everything below works on f32; integer devices are converted first
+    let mut process = move |data: &[f32]| {
+        // Convert to mono if stereo
+        let mut mono_data: Vec<f32> = if channels > 1 {
+            data.chunks(channels as usize)
+                .map(|frame| strategy.mix(frame))
+                .collect()
+        } else {
+            data.to_vec()
+        };
+
+        if gain != 1.0 {
+            for sample in mono_data.iter_mut() {
+                *sample *= gain;
+            }
+        }
+        if let Some(auto_gain) = auto_gain.as_mut() {
+            auto_gain.process(&mut mono_data);
+        }
+
+        // Band-limited resampling to the target rate, continuous across callbacks
+        let resampled = resampler.process(&mono_data);
+
+        if !resampled.is_empty() {
+            // No session attached (warm mic idling): drop the audio
+            if let Ok(sink) = sink.lock() {
+                if let Some(writer) = sink.as_ref() {
+                    // The callback can't wait, so a session that's seconds
+                    // behind loses what doesn't fit; say so instead of
+                    // degrading silently
+                    let dropped = writer.push_or_drop(&resampled);
+                    if dropped > 0 {
+                        drops.dropped(&drop_app, dropped);
+                    }
+                }
+            }
+        }
+    };
+
+    let lost = device_lost.clone();
+    let error_app = app.clone();
+    let on_error = move |err: cpal::StreamError| match err {
+        cpal::StreamError::DeviceNotAvailable => {
+            if !lost.swap(true, Ordering::Relaxed) {
+                let warning = "Microphone disconnected; switching to another input device";
+                eprintln!("{}", warning);
+                let _ = error_app.emit("input-device-warning", warning);
+            }
+        }
+        other => eprintln!("Audio stream error: {}", other),
+    };
+
+    // Open the stream in the device's native format; asking for f32 on
+    // hardware that only does integer samples fails to build
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &stream_config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| process(data),
+            on_error,
+            None,
+        ),
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &stream_config,
+            move |data: &[i16], _: &cpal::InputCallbackInfo| process(&i16_to_f32(data)),
+            on_error,
+            None,
+        ),
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &stream_config,
+            move |data: &[u16], _: &cpal::InputCallbackInfo| process(&u16_to_f32(data)),
+            on_error,
+            None,
+        ),
+        other => {
+            return Err(TranscriptionError::Audio(format!(
+                "Unsupported input sample format: {:?}",
+                other
+            )))
+        }
+    }
+    .map_err(|e| TranscriptionError::Audio(format!("Failed to create audio stream: {}", e)))?;
+
+    stream
+        .play()
+        .map_err(|e| TranscriptionError::Audio(format!("Failed to start audio stream: {}", e)))?;
+    Ok((stream, device_name))
+}
+
+/// Enumerate input devices on the default host.
+/// Devices that fail to report a name are skipped.
+pub fn list_input_devices() -> Result<Vec<AudioDeviceInfo>, String> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+
+    let infos = enumerate_input_devices(&host)?
+        .into_iter()
+        .map(|(id, name, _)| AudioDeviceInfo {
+            // Only the first device with the default's name is the default.
+            is_default: default_name.as_deref() == Some(id.as_str()),
+            id,
+            name,
+        })
+        .collect();
+
+    Ok(infos)
+}
+
+/// List speakers and headphones, with ids that are stable across calls
+pub fn list_output_devices() -> Result<Vec<AudioDeviceInfo>, String> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+    let devices = host
+        .output_devices()
+        .map_err(|e| format!("Failed to enumerate output devices: {}", e))?;
+
+    let infos = with_unique_ids(devices, "output")
+        .into_iter()
+        .map(|(id, name, _)| AudioDeviceInfo {
+            is_default: default_name.as_deref() == Some(id.as_str()),
+            id,
+            name,
+        })
+        .collect();
+
+    Ok(infos)
+}
+
+/// Find an output device by the id reported from `list_output_devices`.
+pub fn find_output_device(host: &cpal::Host, id: &str) -> Option<cpal::Device> {
+    let devices = host.output_devices().ok()?;
+    with_unique_ids(devices, "output")
+        .into_iter()
+        .find(|(device_id, _, _)| device_id == id)
+        .map(|(_, _, device)| device)
+}
+
+/// Find an input device by the id reported from `list_input_devices`.
+pub fn find_input_device(host: &cpal::Host, id: &str) -> Option<cpal::Device> {
+    enumerate_input_devices(host)
+        .ok()?
+        .into_iter()
+        .find(|(device_id, _, _)| device_id == id)
+        .map(|(_, _, device)| device)
+}
+
+fn enumerate_input_devices(
+    host: &cpal::Host,
+) -> Result<Vec<(String, String, cpal::Device)>, String> {
+    let devices = host
+        .input_devices()
+        .map_err(|e| format!("Failed to enumerate input devices: {}", e))?;
+    Ok(with_unique_ids(devices, "input"))
+}
+
+/// The part of an audio host that picking a microphone needs, so the choice
+/// (and what happens when there's no microphone) doesn't depend on hardware
+pub trait InputHost {
+    type Device;
+    /// The input device with an id from `list_input_devices`
+    fn find_device(&self, id: &str) -> Option<Self::Device>;
+    /// The system's default input, if there is one
+    fn default_device(&self) -> Option<Self::Device>;
+}
+
+impl InputHost for cpal::Host {
+    type Device = cpal::Device;
+
+    fn find_device(&self, id: &str) -> Option<cpal::Device> {
+        find_input_device(self, id)
+    }
+
+    fn default_device(&self) -> Option<cpal::Device> {
+        self.default_input_device()
+    }
+}
+
+/// Prefer the user's chosen mic, falling back to the default if it's gone,
+/// with a warning to show in that case. `NoInputDevice` when there's neither.
+fn choose_input_device<H: InputHost>(
+    host: &H,
+    selected: Option<&str>,
+) -> Result<(H::Device, Option<String>), TranscriptionError> {
+    if let Some(device) = selected.and_then(|id| host.find_device(id)) {
+        return Ok((device, None));
+    }
+    let device = host
+        .default_device()
+        .ok_or(TranscriptionError::NoInputDevice)?;
+    let warning = selected.map(|id| {
+        format!(
+            "Selected microphone \"{}\" not found; using the default input device",
+            id
+        )
+    });
+    Ok((device, warning))
+}
+
+/// Pair each device with an id and name, skipping devices without a name
+fn with_unique_ids(
+    devices: impl Iterator<Item = cpal::Device>,
+    kind: &str,
+) -> Vec<(String, String, cpal::Device)> {
+    let mut entries: Vec<(String, String, cpal::Device)> = Vec::new();
+    for device in devices {
+        let name = match device.name() {
+            Ok(name) => name,
+            Err(e) => {
+                eprintln!("Skipping {} device without a name: {}", kind, e);
+                continue;
+            }
         };
 
-        let (tx, rx) = mpsc::channel::<Vec<i16>>(100);
+        // Identical models (e.g. two of the same USB mic) share a name, so
+        // later duplicates get an ordinal suffix to keep ids unique.
+        let duplicates = entries.iter().filter(|(_, n, _)| *n == name).count();
+        let id = if duplicates == 0 {
+            name.clone()
+        } else {
+            format!("{} #{}", name, duplicates + 1)
+        };
 
-        let err_fn = |err| eprintln!("Audio stream error: {}", err);
+        entries.push((id, name, device));
+    }
 
+    entries
+}
+
+/// Whether the app may record from the microphone
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+// Only macOS reports a refusal; elsewhere access is always granted
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub enum PermissionStatus {
+    Granted,
+    /// The user turned access off; only System Settings can undo it
+    Denied,
+    /// Blocked by parental controls or device management
+    Restricted,
+    /// Never asked; the first capture shows the OS prompt
+    NotDetermined,
+}
+
+/// Microphone access as the OS reports it. Only macOS gates this per app;
+/// elsewhere it's always `Granted`.
+pub fn microphone_permission() -> PermissionStatus {
+    #[cfg(target_os = "macos")]
+    {
+        match macos_microphone::authorization_status() {
+            0 => PermissionStatus::NotDetermined,
+            1 => PermissionStatus::Restricted,
+            2 => PermissionStatus::Denied,
+            _ => PermissionStatus::Granted,
+        }
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        PermissionStatus::Granted
+    }
+}
+
+/// Ask for microphone access by briefly opening the default input, which is
+/// what makes macOS show its prompt. The answer comes later, so check
+/// `microphone_permission` again afterwards. Does nothing elsewhere.
+pub fn request_microphone_permission() -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or("No microphone found")?;
+        let config = device
+            .default_input_config()
+            .map_err(|e| format!("Failed to get input config: {}", e))?;
         let stream = device
-            .build_input_stream(
-                &config,
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    // Convert f32 samples to i16
-                    let samples: Vec<i16> = data
-                        .iter()
-                        .map(|&sample| {
-                            // Clamp and convert to i16
-                            let clamped = sample.clamp(-1.0, 1.0);
-                            (clamped * 32767.0) as i16
-                        })
-                        .collect();
-
-                    // Send samples through channel
-                    let _ = tx.try_send(samples);
-                },
-                err_fn,
+            .build_input_stream_raw(
+                &config.config(),
+                config.sample_format(),
+                |_: &cpal::Data, _: &cpal::InputCallbackInfo| {},
+                |err| eprintln!("Audio stream error: {}", err),
                 None,
             )
-            .map_err(|e| format!("Failed to build input stream: {}", e))?;
-
+            .map_err(|e| format!("Failed to open the microphone: {}", e))?;
         stream
             .play()
-            .map_err(|e| format!("Failed to start stream: {}", e))?;
+            .map_err(|e| format!("Failed to start the microphone: {}", e))?;
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    Ok(())
+}
 
-        self.stream = Some(stream);
+/// Open the Microphone pane of System Settings (macOS only)
+pub fn open_microphone_settings() -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg("x-apple.systempreferences:com.apple.preference.security?Privacy_Microphone")
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to open System Settings: {}", e))
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        Err("Microphone settings can only be opened on macOS".to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos_microphone {
+    use std::ffi::{c_char, c_void};
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    extern "C" {
+        static AVMediaTypeAudio: *const c_void;
+    }
 
-        Ok(rx)
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> *const c_void;
+        fn sel_registerName(name: *const c_char) -> *const c_void;
+        fn objc_msgSend();
     }
 
-    /// Stop capturing audio
-    pub fn stop(&mut self) {
-        if let Some(stream) = self.stream.take() {
-            drop(stream);
+    /// `[AVCaptureDevice authorizationStatusForMediaType:AVMediaTypeAudio]`
+    pub fn authorization_status() -> isize {
+        // SAFETY: the class and selector exist on every supported macOS, and
+        // objc_msgSend is cast to the method's exact signature before calling
+        unsafe {
+            let class = objc_getClass(c"AVCaptureDevice".as_ptr());
+            if class.is_null() {
+                // Nothing to ask; let capture report any problem itself
+                return 3;
+            }
+            let selector = sel_registerName(c"authorizationStatusForMediaType:".as_ptr());
+            let send: unsafe extern "C" fn(*const c_void, *const c_void, *const c_void) -> isize =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            send(class, selector, AVMediaTypeAudio)
         }
     }
 }
 
-impl Drop for AudioCapture {
-    fn drop(&mut self) {
-        self.stop();
-    }
+/// Scale signed 16-bit input samples to -1.0..1.0
+fn i16_to_f32(data: &[i16]) -> Vec<f32> {
+    data.iter().map(|&s| s as f32 / 32768.0).collect()
+}
+
+/// Scale unsigned 16-bit input samples (silence at 32768) to -1.0..1.0
+fn u16_to_f32(data: &[u16]) -> Vec<f32> {
+    data.iter()
+        .map(|&s| (s as f32 - 32768.0) / 32768.0)
+        .collect()
 }
 
 /// Helper function to convert i16 samples to bytes for WebSocket transmission
@@ -84,3 +1191,256 @@ pub fn samples_to_bytes(samples: &[i16]) -> Vec<u8> {
         .flat_map(|&sample| sample.to_le_bytes())
         .collect()
 }
+
+/// f32 counterpart of `samples_to_bytes`, for `AudioFormat::PcmF32le`
+pub fn float_samples_to_bytes(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|&sample| sample.to_le_bytes())
+        .collect()
+}
+
+impl AudioChunk {
+    /// Encode the chunk in the format it was captured for
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match &self.float_samples {
+            Some(samples) => float_samples_to_bytes(samples),
+            None => samples_to_bytes(&self.samples),
+        }
+    }
+}
+
+/// Streaming windowed-sinc resampler.
+///
+/// Keeps a tail of previous input between calls so interpolation stays
+/// continuous across callback buffers instead of restarting at every buffer.
+pub struct Resampler {
+    /// Input samples consumed per output sample.
+    step: f64,
+    /// Low-pass cutoff relative to the input Nyquist frequency.
+    cutoff: f64,
+    half_taps: usize,
+    quality: ResampleQuality,
+    history: Vec<f32>,
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32, quality: ResampleQuality) -> Self {
+        let half_taps = quality.half_taps();
+        // Slightly below the output Nyquist so the transition band doesn't alias.
+        let cutoff = (output_rate as f64 / input_rate as f64).min(1.0) * quality.cutoff_margin();
+        Resampler {
+            step: input_rate as f64 / output_rate as f64,
+            cutoff,
+            half_taps,
+            quality,
+            // Zero left context so the first output sample has a full window.
+            history: vec![0.0; half_taps],
+            position: half_taps as f64,
+        }
+    }
+
+    /// Feed mono samples at the input rate, returns samples at the output rate.
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        self.history.extend_from_slice(input);
+
+        let half = self.half_taps as isize;
+        let mut output = Vec::with_capacity((input.len() as f64 / self.step) as usize + 1);
+
+        while (self.position.floor() as usize) + self.half_taps < self.history.len() {
+            let center = self.position.floor() as isize;
+            let frac = self.position - center as f64;
+
+            let mut acc = 0.0f64;
+            let mut weight_sum = 0.0f64;
+            for j in (1 - half)..=half {
+                let distance = j as f64 - frac;
+                let weight = self.cutoff
+                    * sinc(self.cutoff * distance)
+                    * self.quality.window(distance / self.half_taps as f64);
+                acc += self.history[(center + j) as usize] as f64 * weight;
+                weight_sum += weight;
+            }
+
+            output.push(if weight_sum.abs() > f64::EPSILON {
+                (acc / weight_sum) as f32
+            } else {
+                0.0
+            });
+            self.position += self.step;
+        }
+
+        // Drop input that no future output window can reach.
+        let consumed = (self.position.floor() as isize - (half - 1)).max(0) as usize;
+        let consumed = consumed.min(self.history.len());
+        self.history.drain(..consumed);
+        self.position -= consumed as f64;
+
+        output
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let px = std::f64::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+fn hann(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        0.0
+    } else {
+        0.5 * (1.0 + (std::f64::consts::PI * x).cos())
+    }
+}
+
+// Lower sidelobes than Hann, for the long high-quality filter
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        0.0
+    } else {
+        let px = std::f64::consts::PI * x;
+        0.42 + 0.5 * px.cos() + 0.08 * (2.0 * px).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Microphones by id, with no hardware behind them
+    struct MockHost {
+        devices: Vec<&'static str>,
+        default: Option<&'static str>,
+    }
+
+    impl InputHost for MockHost {
+        type Device = &'static str;
+
+        fn find_device(&self, id: &str) -> Option<&'static str> {
+            self.devices.iter().copied().find(|device| *device == id)
+        }
+
+        fn default_device(&self) -> Option<&'static str> {
+            self.default
+        }
+    }
+
+    #[test]
+    fn no_microphone_is_an_error() {
+        let host = MockHost {
+            devices: Vec::new(),
+            default: None,
+        };
+        assert!(matches!(
+            choose_input_device(&host, None),
+            Err(TranscriptionError::NoInputDevice)
+        ));
+        assert!(matches!(
+            choose_input_device(&host, Some("USB Mic")),
+            Err(TranscriptionError::NoInputDevice)
+        ));
+    }
+
+    #[test]
+    fn missing_microphone_falls_back_to_default_with_warning() {
+        let host = MockHost {
+            devices: vec!["Built-in"],
+            default: Some("Built-in"),
+        };
+        let (device, warning) = choose_input_device(&host, Some("USB Mic")).unwrap();
+        assert_eq!(device, "Built-in");
+        assert!(warning.is_some_and(|w| w.contains("USB Mic")));
+    }
+
+    #[test]
+    fn selected_microphone_is_used() {
+        let host = MockHost {
+            devices: vec!["Built-in", "USB Mic"],
+            default: Some("Built-in"),
+        };
+        let (device, warning) = choose_input_device(&host, Some("USB Mic")).unwrap();
+        assert_eq!(device, "USB Mic");
+        assert!(warning.is_none());
+
+        let (device, warning) = choose_input_device(&host, None).unwrap();
+        assert_eq!(device, "Built-in");
+        assert!(warning.is_none());
+    }
+
+    fn sine(freq: f64, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// Share of the signal's energy that isn't a `freq` tone
+    fn off_tone_energy(samples: &[f32], freq: f64, rate: u32) -> f64 {
+        let w = 2.0 * std::f64::consts::PI * freq / rate as f64;
+        let n = samples.len() as f64;
+        let (mut sin_sum, mut cos_sum, mut total) = (0.0, 0.0, 0.0);
+        for (i, &s) in samples.iter().enumerate() {
+            let s = s as f64;
+            sin_sum += s * (w * i as f64).sin();
+            cos_sum += s * (w * i as f64).cos();
+            total += s * s;
+        }
+        let tone = 2.0 * (sin_sum * sin_sum + cos_sum * cos_sum) / n;
+        (total - tone).max(0.0) / total
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        (samples.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn downsampled_tone_has_no_aliasing() {
+        for quality in [
+            ResampleQuality::Fast,
+            ResampleQuality::Balanced,
+            ResampleQuality::HighQuality,
+        ] {
+            let input = sine(1000.0, 48000, 48000);
+            let output = Resampler::new(48000, 16000, quality).process(&input);
+            // Skip the filter's start-up, where the left context is silence,
+            // and measure whole cycles (16 samples each)
+            let steady = &output[192..192 + 14_400];
+            assert!(off_tone_energy(steady, 1000.0, 16000) < 1e-4);
+            assert!((rms(steady) - std::f64::consts::FRAC_1_SQRT_2).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn tone_above_output_nyquist_is_filtered_out() {
+        // 10 kHz can't be represented at 16 kHz; it must not fold back to 6 kHz
+        let input = sine(10_000.0, 48000, 48000);
+        let output = Resampler::new(48000, 16000, ResampleQuality::HighQuality).process(&input);
+        let steady = &output[200..output.len() - 200];
+        assert!(rms(steady) < 0.01);
+    }
+
+    #[test]
+    fn chunks_resample_like_one_buffer() {
+        let input = sine(440.0, 44100, 44100);
+        let whole = Resampler::new(44100, 16000, ResampleQuality::Balanced).process(&input);
+
+        let mut resampler = Resampler::new(44100, 16000, ResampleQuality::Balanced);
+        let mut chunked = Vec::new();
+        // Uneven sizes, like audio callbacks, including an empty one
+        for chunk in input.chunks(441).flat_map(|c| {
+            let (a, b) = c.split_at(c.len() / 3);
+            [a, &[][..], b]
+        }) {
+            chunked.extend(resampler.process(chunk));
+        }
+
+        assert_eq!(whole.len(), chunked.len());
+        for (a, b) in whole.iter().zip(&chunked) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+}
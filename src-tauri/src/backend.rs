@@ -0,0 +1,269 @@
+use futures_util::future::BoxFuture;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tauri::AppHandle;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::audio::AudioSource;
+use crate::openai;
+use crate::soniox::{self, SessionConfig};
+
+/// Errors from a transcription session.
+///
+/// Serialized as `{ "kind": ..., "message": ... }` for the `transcription-error`
+/// event so the frontend can react to specific failures.
+#[derive(Debug, Clone)]
+pub enum TranscriptionError {
+    /// Could not reach the transcription service
+    Connect(String),
+    /// API key rejected
+    Auth(String),
+    /// No microphone to capture from
+    NoInputDevice,
+    /// The microphone couldn't be opened or started
+    Audio(String),
+    /// The service reported an error or sent something we couldn't handle
+    Protocol(String),
+    /// The WebSocket failed after connecting
+    WebSocket(String),
+    /// A backend is unavailable or misconfigured (e.g. missing model file)
+    Backend(String),
+    /// The user aborted the dictation; nothing should be typed or reported
+    Cancelled,
+}
+
+impl TranscriptionError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TranscriptionError::Connect(_) => "connect",
+            TranscriptionError::Auth(_) => "auth",
+            TranscriptionError::NoInputDevice => "no_input_device",
+            TranscriptionError::Audio(_) => "audio",
+            TranscriptionError::Protocol(_) => "protocol",
+            TranscriptionError::WebSocket(_) => "websocket",
+            TranscriptionError::Backend(_) => "backend",
+            TranscriptionError::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl fmt::Display for TranscriptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptionError::Connect(msg) => write!(f, "Failed to connect: {}", msg),
+            TranscriptionError::Auth(msg) => write!(f, "API key rejected: {}", msg),
+            TranscriptionError::NoInputDevice => write!(f, "No input device available"),
+            TranscriptionError::Audio(msg) => write!(f, "Microphone error: {}", msg),
+            TranscriptionError::Protocol(msg) => write!(f, "{}", msg),
+            TranscriptionError::WebSocket(msg) => write!(f, "WebSocket error: {}", msg),
+            TranscriptionError::Backend(msg) => write!(f, "{}", msg),
+            TranscriptionError::Cancelled => write!(f, "Transcription cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptionError {}
+
+impl Serialize for TranscriptionError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut payload = serializer.serialize_struct("TranscriptionError", 2)?;
+        payload.serialize_field("kind", self.kind())?;
+        payload.serialize_field("message", &self.to_string())?;
+        payload.end()
+    }
+}
+
+/// Which engine turns speech into text
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// SONIOX real-time cloud streaming
+    #[default]
+    Soniox,
+    /// Offline whisper.cpp (requires the `whisper` cargo feature)
+    WhisperLocal,
+    /// OpenAI's hosted Whisper API; uploads the recording on stop
+    OpenAi,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 3] = [
+        BackendKind::Soniox,
+        BackendKind::WhisperLocal,
+        BackendKind::OpenAi,
+    ];
+
+    /// Whether this build can use the backend at all
+    pub fn is_available(self) -> bool {
+        match self {
+            BackendKind::Soniox => true,
+            BackendKind::WhisperLocal => cfg!(feature = "whisper"),
+            BackendKind::OpenAi => true,
+        }
+    }
+
+    /// Whether partial results arrive while recording. Non-streaming backends
+    /// do all their work after stop, so finishing takes longer.
+    pub fn is_streaming(self) -> bool {
+        !matches!(self, BackendKind::OpenAi)
+    }
+}
+
+/// A finalized word with its position in the recording, for subtitle export
+#[derive(Clone, Serialize)]
+pub struct TimedSegment {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// 0-1, lowest confidence among the word's tokens
+    pub confidence: f32,
+}
+
+/// Seconds of audio sent for transcription, reported by `get_usage_stats`
+#[derive(Clone, Copy, Serialize)]
+pub struct UsageStats {
+    /// Since the app started
+    pub session_seconds: f64,
+    /// Across restarts (persisted in settings)
+    pub lifetime_seconds: f64,
+}
+
+/// Running totals of audio sent to a cloud service, for cost tracking.
+/// Kept in microseconds so short chunks don't round away.
+#[derive(Default)]
+pub struct UsageMeter {
+    session_us: AtomicU64,
+    lifetime_us: AtomicU64,
+}
+
+impl UsageMeter {
+    /// Resume the lifetime total saved by a previous run
+    pub fn restore_lifetime(&self, seconds: f64) {
+        let us = (seconds.max(0.0) * 1_000_000.0) as u64;
+        self.lifetime_us.store(us, Ordering::Relaxed);
+    }
+
+    /// Count `samples` of mono audio at `sample_rate` as sent
+    pub fn record(&self, samples: usize, sample_rate: u32) {
+        if sample_rate == 0 {
+            return;
+        }
+        let us = samples as u64 * 1_000_000 / sample_rate as u64;
+        self.session_us.fetch_add(us, Ordering::Relaxed);
+        self.lifetime_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> UsageStats {
+        UsageStats {
+            session_seconds: self.session_us.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+            lifetime_seconds: self.lifetime_us.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        }
+    }
+
+    /// Zero both totals
+    pub fn reset(&self) {
+        self.session_us.store(0, Ordering::Relaxed);
+        self.lifetime_us.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Identifies one recording session. Starting a new session supersedes the
+/// older ones, which must stop touching the mic and shared state.
+#[derive(Clone)]
+pub struct SessionGeneration {
+    id: u64,
+    current: Arc<AtomicU64>,
+}
+
+impl SessionGeneration {
+    /// Claim the next generation from the shared counter
+    pub fn next(current: Arc<AtomicU64>) -> Self {
+        let id = current.fetch_add(1, Ordering::SeqCst) + 1;
+        SessionGeneration { id, current }
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.current.load(Ordering::SeqCst) == self.id
+    }
+}
+
+/// Shared state a running session reports into
+pub struct SessionContext {
+    pub app: AppHandle,
+    pub is_recording: Arc<AtomicBool>,
+    pub latest_transcription: Arc<Mutex<String>>,
+    /// Set when the user cancels; the session should stop without a result
+    pub cancelled: Arc<AtomicBool>,
+    /// Word timings of the finished transcript, if the backend provides them
+    pub segments: Arc<Mutex<Vec<TimedSegment>>>,
+    pub generation: SessionGeneration,
+    /// The microphone (already open if kept warm) or a file to transcribe
+    pub source: AudioSource,
+    /// Cloud backends count the audio they send here
+    pub usage: Arc<UsageMeter>,
+    /// Receives finalized text as it arrives, for typing while recording
+    /// (SONIOX only; None when live typing is off)
+    pub live_text: Option<mpsc::UnboundedSender<LiveText>>,
+}
+
+/// A change to the finalized text, sent for live typing in the order made
+#[derive(Debug, PartialEq)]
+pub enum LiveText {
+    /// Text added to the end
+    Append(String),
+    /// The last this many bytes sent were taken back, as when a sentence
+    /// break turns a trailing comma into a full stop
+    Rewind(usize),
+}
+
+/// A speech-to-text engine fed from the microphone.
+///
+/// Implementations capture audio until `is_recording` clears, emit the usual
+/// `transcription` / `transcription-complete` events, and return the final text.
+/// If `cancelled` is set or a newer session has started they return
+/// `TranscriptionError::Cancelled` instead, without emitting
+/// `transcription-complete`.
+pub trait TranscriptionBackend: Send {
+    fn transcribe_stream(
+        self: Box<Self>,
+        ctx: SessionContext,
+    ) -> BoxFuture<'static, Result<String, TranscriptionError>>;
+}
+
+/// Build the backend for a new session
+pub fn create_backend(
+    kind: BackendKind,
+    session: SessionConfig,
+    whisper_model_path: Option<String>,
+) -> Result<Box<dyn TranscriptionBackend>, TranscriptionError> {
+    match kind {
+        BackendKind::Soniox => Ok(Box::new(soniox::SonioxBackend::new(session))),
+        #[cfg(feature = "whisper")]
+        BackendKind::WhisperLocal => {
+            let model_path = whisper_model_path.ok_or_else(|| {
+                TranscriptionError::Backend("No Whisper model file selected".to_string())
+            })?;
+            Ok(Box::new(crate::whisper::WhisperLocal::new(
+                session, model_path,
+            )))
+        }
+        #[cfg(not(feature = "whisper"))]
+        BackendKind::WhisperLocal => {
+            let _ = whisper_model_path;
+            Err(TranscriptionError::Backend(
+                "Local Whisper support is not included in this build".to_string(),
+            ))
+        }
+        BackendKind::OpenAi => {
+            if session.api_key.is_empty() {
+                return Err(TranscriptionError::Backend(
+                    "No OpenAI API key set".to_string(),
+                ));
+            }
+            Ok(Box::new(openai::OpenAiBackend::new(session)))
+        }
+    }
+}
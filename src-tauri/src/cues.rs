@@ -0,0 +1,142 @@
+use crate::audio;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::Duration;
+
+const CUE_DURATION_MS: u64 = 120;
+const CUE_VOLUME: f32 = 0.15;
+// Fade in/out to avoid clicks at the edges of the tone
+const CUE_FADE_MS: f32 = 10.0;
+
+/// Where cues play and how loud
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CueOutput {
+    /// Output device id from `list_output_devices` (None = system default)
+    pub device: Option<String>,
+    /// 0.0 - 1.0, scaling the built-in cue level
+    pub volume: f32,
+}
+
+impl Default for CueOutput {
+    fn default() -> Self {
+        CueOutput {
+            device: None,
+            volume: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Cue {
+    /// Rising tone when recording starts
+    Start,
+    /// Falling tone when recording stops
+    Stop,
+    /// Low falling tone when a dictation came back empty
+    NoSpeech,
+}
+
+impl Cue {
+    fn sweep(self) -> (f32, f32) {
+        match self {
+            Cue::Start => (660.0, 990.0),
+            Cue::Stop => (990.0, 660.0),
+            Cue::NoSpeech => (440.0, 330.0),
+        }
+    }
+}
+
+/// Play a cue on the chosen output device, blocking until it has finished.
+/// Falls back to the default device if the chosen one is gone or fails.
+pub fn play(cue: Cue, output: &CueOutput) -> Result<(), String> {
+    let host = cpal::default_host();
+    if let Some(id) = output.device.as_deref() {
+        match audio::find_output_device(&host, id) {
+            Some(device) => match play_on(&device, cue, output.volume) {
+                Ok(()) => return Ok(()),
+                Err(e) => eprintln!("Cue device \"{}\" failed ({}); using the default", id, e),
+            },
+            None => eprintln!("Cue device \"{}\" not found; using the default", id),
+        }
+    }
+
+    let device = host
+        .default_output_device()
+        .ok_or("No output device available")?;
+    play_on(&device, cue, output.volume)
+}
+
+fn play_on(device: &cpal::Device, cue: Cue, volume: f32) -> Result<(), String> {
+    let supported = device
+        .default_output_config()
+        .map_err(|e| format!("Failed to get output config: {}", e))?;
+
+    let sample_rate = supported.sample_rate().0;
+    let config: cpal::StreamConfig = supported.config();
+    let samples = render(cue, sample_rate, volume);
+
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_stream::<f32>(device, &config, samples),
+        cpal::SampleFormat::I16 => build_stream::<i16>(device, &config, samples),
+        cpal::SampleFormat::U16 => build_stream::<u16>(device, &config, samples),
+        format => return Err(format!("Unsupported output sample format: {}", format)),
+    }?;
+
+    stream
+        .play()
+        .map_err(|e| format!("Failed to play cue: {}", e))?;
+    // Let the device drain before the stream is dropped.
+    thread::sleep(Duration::from_millis(CUE_DURATION_MS + 40));
+    Ok(())
+}
+
+/// Synthesize the cue as mono f32 samples
+fn render(cue: Cue, sample_rate: u32, volume: f32) -> Vec<f32> {
+    let (from_hz, to_hz) = cue.sweep();
+    let total = (sample_rate as u64 * CUE_DURATION_MS / 1000) as usize;
+    let fade = (sample_rate as f32 * CUE_FADE_MS / 1000.0).max(1.0);
+
+    let mut phase = 0.0f32;
+    (0..total)
+        .map(|i| {
+            let progress = i as f32 / total as f32;
+            let freq = from_hz + (to_hz - from_hz) * progress;
+            phase += 2.0 * std::f32::consts::PI * freq / sample_rate as f32;
+
+            let envelope = (i as f32 / fade).min((total - i) as f32 / fade).min(1.0);
+            phase.sin() * envelope * CUE_VOLUME * volume
+        })
+        .collect()
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Vec<f32>,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let mut position = 0usize;
+
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                for frame in data.chunks_mut(channels) {
+                    let value = samples.get(position).copied().unwrap_or(0.0);
+                    position += 1;
+                    for out in frame.iter_mut() {
+                        *out = T::from_sample(value);
+                    }
+                }
+            },
+            |err| eprintln!("Audio cue stream error: {}", err),
+            None,
+        )
+        .map_err(|e| format!("Failed to build output stream: {}", e))
+}
@@ -0,0 +1,116 @@
+use crate::backend::TimedSegment;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// A subtitle cue ends after this long, or this many characters, whichever comes first
+const MAX_CUE_MS: u64 = 6000;
+const MAX_CUE_CHARS: usize = 84;
+// A pause this long between words starts a new cue
+const CUE_GAP_MS: u64 = 1000;
+
+/// File format for `export_transcription`
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Text,
+    Srt,
+    Vtt,
+}
+
+impl ExportFormat {
+    /// Whether the format needs word timings
+    pub fn is_timed(self) -> bool {
+        !matches!(self, ExportFormat::Text)
+    }
+}
+
+/// Write a transcription to `path`. Subtitle formats use the word timings;
+/// plain text uses `text` as it was typed.
+pub fn write(
+    path: &Path,
+    format: ExportFormat,
+    text: &str,
+    segments: &[TimedSegment],
+) -> Result<(), String> {
+    let contents = match format {
+        ExportFormat::Text => format!("{}\n", text.trim()),
+        ExportFormat::Srt => srt(&cues(segments)),
+        ExportFormat::Vtt => vtt(&cues(segments)),
+    };
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// A line of subtitles and when it's on screen
+struct Cue {
+    text: String,
+    start_ms: u64,
+    end_ms: u64,
+}
+
+/// Group words into cues, breaking at pauses, sentence ends and length limits
+fn cues(segments: &[TimedSegment]) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = Vec::new();
+    let mut sentence_ended = false;
+    for segment in segments {
+        let fits = cues.last().is_some_and(|cue| {
+            !sentence_ended
+                && segment.start_ms.saturating_sub(cue.end_ms) < CUE_GAP_MS
+                && segment.end_ms.saturating_sub(cue.start_ms) <= MAX_CUE_MS
+                && cue.text.len() + 1 + segment.text.len() <= MAX_CUE_CHARS
+        });
+        match cues.last_mut() {
+            Some(cue) if fits => {
+                cue.text.push(' ');
+                cue.text.push_str(&segment.text);
+                cue.end_ms = segment.end_ms;
+            }
+            _ => cues.push(Cue {
+                text: segment.text.clone(),
+                start_ms: segment.start_ms,
+                end_ms: segment.end_ms,
+            }),
+        }
+        sentence_ended = segment.text.ends_with(['.', '!', '?', '؟', '…']);
+    }
+    cues
+}
+
+fn srt(cues: &[Cue]) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                timestamp(cue.start_ms, ','),
+                timestamp(cue.end_ms, ','),
+                cue.text
+            )
+        })
+        .collect()
+}
+
+fn vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            timestamp(cue.start_ms, '.'),
+            timestamp(cue.end_ms, '.'),
+            cue.text
+        ));
+    }
+    out
+}
+
+/// `HH:MM:SS,mmm` (SRT) or `HH:MM:SS.mmm` (VTT)
+fn timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+#[derive(Clone, Serialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub text: String,
+    /// Unix time in milliseconds when the transcription completed
+    pub timestamp_ms: u64,
+}
+
+/// Most recent completed transcriptions, oldest first
+pub struct TranscriptionHistory {
+    entries: VecDeque<HistoryEntry>,
+    next_id: u64,
+    limit: usize,
+}
+
+impl TranscriptionHistory {
+    pub fn new(limit: usize) -> Self {
+        TranscriptionHistory {
+            entries: VecDeque::new(),
+            next_id: 1,
+            limit,
+        }
+    }
+
+    /// Record a transcription, returning its id (empty text is ignored)
+    pub fn push(&mut self, text: &str, timestamp_ms: u64) -> Option<u64> {
+        let text = text.trim();
+        if text.is_empty() || self.limit == 0 {
+            return None;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push_back(HistoryEntry {
+            id,
+            text: text.to_string(),
+            timestamp_ms,
+        });
+        self.trim_to_limit();
+        Some(id)
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// The most recent transcription
+    pub fn last(&self) -> Option<&HistoryEntry> {
+        self.entries.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim_to_limit();
+    }
+
+    fn trim_to_limit(&mut self) {
+        while self.entries.len() > self.limit {
+            self.entries.pop_front();
+        }
+    }
+}
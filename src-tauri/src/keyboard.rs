@@ -1,49 +1,923 @@
-use arboard::Clipboard;
+use arboard::{Clipboard, ImageData};
 use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use serde::{Deserialize, Serialize};
 use std::thread;
 use std::time::Duration;
 
-pub fn type_text(text: &str) -> Result<(), String> {
-    if text.trim().is_empty() {
-        return Ok(());
+/// How transcribed text is injected into the focused app
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeMode {
+    /// Send one Unicode key event per character
+    Keystrokes,
+    /// Put the text on the clipboard and send the paste shortcut.
+    /// Faster for long text and keeps RTL text in logical order.
+    #[default]
+    Paste,
+    /// Insert the whole string through the platform's Unicode input (one
+    /// SendInput batch on Windows, Unicode key events on macOS; a paste
+    /// elsewhere), so the target app lays out RTL text itself rather than
+    /// seeing it one keystroke at a time
+    Unicode,
+}
+
+/// How typing handles right-to-left text (Arabic, Hebrew, ...)
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextDirection {
+    /// Type every dictation with the chosen mode
+    Off,
+    /// Paste mostly right-to-left dictations instead of typing them key by
+    /// key, which some apps reorder or shape wrongly
+    #[default]
+    Auto,
+    /// Like `Auto`, and start each line of such dictations with a
+    /// right-to-left mark so it's laid out right to left even when it opens
+    /// with a Latin word or a number
+    AutoWithMarks,
+}
+
+// Right-to-left mark (U+200F): invisible, but a strong right-to-left character
+const RLM: char = '\u{200F}';
+
+/// Whether a character belongs to a right-to-left script
+fn is_rtl_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        // Hebrew through Arabic Extended-A
+        0x0590..=0x08FF
+            // Hebrew and Arabic presentation forms
+            | 0xFB1D..=0xFDFF
+            | 0xFE70..=0xFEFF
+            // Historic right-to-left scripts
+            | 0x10800..=0x10FFF
+            | 0x1E800..=0x1EFFF
+    )
+}
+
+/// Whether most of the letters in `text` are right-to-left. Digits,
+/// punctuation and spaces don't count either way.
+pub fn is_mostly_rtl(text: &str) -> bool {
+    let (mut rtl, mut ltr) = (0usize, 0usize);
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        if is_rtl_char(c) {
+            rtl += 1;
+        } else {
+            ltr += 1;
+        }
     }
+    rtl > ltr
+}
 
-    // Fast path: paste through clipboard (much faster for long dictation).
-    if let Err(e) = paste_via_clipboard(text) {
-        eprintln!("clipboard paste failed, falling back to key typing: {}", e);
+/// The mode to type `text` with: keystrokes become a paste for mostly
+/// right-to-left text unless `direction` is `Off`
+pub fn mode_for_text(text: &str, mode: TypeMode, direction: TextDirection) -> TypeMode {
+    if direction != TextDirection::Off && mode == TypeMode::Keystrokes && is_mostly_rtl(text) {
+        TypeMode::Paste
     } else {
+        mode
+    }
+}
+
+/// Start each line of a mostly right-to-left `text` with a right-to-left
+/// mark when `direction` asks for it; other text is returned unchanged.
+/// `starts_line` is false for a piece that continues a line already typed,
+/// whose first line then doesn't get a mark.
+pub fn add_direction_marks(text: &str, direction: TextDirection, starts_line: bool) -> String {
+    if direction != TextDirection::AutoWithMarks || !is_mostly_rtl(text) {
+        return text.to_string();
+    }
+    text.split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if line.trim().is_empty() || line.starts_with(RLM) || (i == 0 && !starts_line) {
+                line.to_string()
+            } else {
+                format!("{}{}", RLM, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Post-processing applied to a dictation before it's typed
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InsertStyle {
+    /// Type the transcription as-is
+    #[default]
+    Raw,
+    /// Add a space when continuing right after the previous dictation
+    AutoSpace,
+    /// Auto-space, and capitalize the first letter when starting a sentence
+    AutoSpaceCapitalize,
+}
+
+/// Adjust `text` to follow `previous`, the dictation typed just before it.
+///
+/// The app can't see what's left of the cursor, so `previous` is the only
+/// context; pass `None` when this dictation doesn't continue an earlier one.
+pub fn apply_insert_style(text: &str, style: InsertStyle, previous: Option<&str>) -> String {
+    if style == InsertStyle::Raw {
+        return text.to_string();
+    }
+
+    let prev_char = previous.and_then(|p| p.chars().last());
+    let mut out = String::with_capacity(text.len() + 1);
+
+    // Don't glue words together, but keep punctuation attached
+    let needs_space = prev_char.is_some_and(|c| !c.is_whitespace())
+        && text
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_whitespace() && !is_trailing_punctuation(c));
+    if needs_space {
+        out.push(' ');
+    }
+
+    // Look past a trailing space, e.g. one added by `TextNormalization`
+    let starts_sentence = previous
+        .and_then(|p| p.trim_end_matches([' ', '\t']).chars().last())
+        .is_none_or(|c| matches!(c, '.' | '!' | '?' | '\n'));
+    if style == InsertStyle::AutoSpaceCapitalize && starts_sentence {
+        let mut chars = text.chars();
+        if let Some(first) = chars.next() {
+            out.extend(first.to_uppercase());
+            out.push_str(chars.as_str());
+            return out;
+        }
+    }
+
+    out.push_str(text);
+    out
+}
+
+/// Cleanup applied to every dictation before it's typed; all off by default
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextNormalization {
+    /// Turn runs of spaces and tabs into a single space
+    pub collapse_spaces: bool,
+    /// Strip spaces from both ends of every line
+    pub trim_lines: bool,
+    /// Capitalize the first letter of each sentence
+    pub capitalize_sentences: bool,
+    /// End with a space so the next dictation doesn't run into this one
+    pub trailing_space: bool,
+}
+
+/// Apply `options` to a trimmed dictation
+pub fn normalize_text(text: &str, options: TextNormalization) -> String {
+    normalize_piece(text, options, "", true)
+}
+
+/// Apply `options` to a piece of a dictation typed as it's spoken, after
+/// `previous`, what its earlier pieces were typed as. A piece that continues
+/// a sentence doesn't get a capital, and only the `last` one gets the
+/// trailing space.
+pub fn normalize_piece(
+    text: &str,
+    options: TextNormalization,
+    previous: &str,
+    last: bool,
+) -> String {
+    let mut text = text.trim().to_string();
+
+    if options.collapse_spaces {
+        let mut collapsed = String::with_capacity(text.len());
+        let mut in_space = false;
+        for c in text.chars() {
+            if c == ' ' || c == '\t' {
+                if !in_space {
+                    collapsed.push(' ');
+                }
+                in_space = true;
+            } else {
+                collapsed.push(c);
+                in_space = false;
+            }
+        }
+        text = collapsed;
+    }
+
+    if options.trim_lines {
+        text = text.lines().map(str::trim).collect::<Vec<_>>().join("\n");
+    }
+
+    if options.capitalize_sentences {
+        let mut capitalized = String::with_capacity(text.len());
+        let mut sentence_start = previous
+            .trim_end_matches([' ', '\t'])
+            .chars()
+            .last()
+            .is_none_or(|c| matches!(c, '.' | '!' | '?' | '\n'));
+        for c in text.chars() {
+            if sentence_start && c.is_alphabetic() {
+                capitalized.extend(c.to_uppercase());
+                sentence_start = false;
+                continue;
+            }
+            if matches!(c, '.' | '!' | '?' | '\n') {
+                sentence_start = true;
+            } else if !c.is_whitespace() {
+                sentence_start = false;
+            }
+            capitalized.push(c);
+        }
+        text = capitalized;
+    }
+
+    if options.trailing_space && last && !text.is_empty() {
+        text.push(' ');
+    }
+    text
+}
+
+fn is_trailing_punctuation(c: char) -> bool {
+    matches!(c, '.' | ',' | '!' | '?' | ';' | ':' | ')' | '،' | '؛' | '؟')
+}
+
+/// Pacing for keystroke typing
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypingConfig {
+    /// Wait before the first keystroke so the target window has focus
+    pub pre_delay_ms: u64,
+    /// Pause between characters; 0 sends the whole text at once.
+    /// Some apps (Electron, heavy web forms) drop characters that arrive too fast.
+    pub char_delay_ms: u64,
+}
+
+impl Default for TypingConfig {
+    fn default() -> Self {
+        TypingConfig {
+            pre_delay_ms: 50,
+            char_delay_ms: 0,
+        }
+    }
+}
+
+/// Where simulated input goes. Typing, pasting and erasing only talk to
+/// this, so their pacing and key sequences can run against something other
+/// than the real keyboard (a recorder, in a session with no desktop).
+pub trait TextInjector {
+    /// Send `text` as typed characters
+    fn text(&mut self, text: &str) -> Result<(), String>;
+    /// Press, release or click a single key
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), String>;
+    /// Send `text` as one run of Unicode input, or Err where the platform has
+    /// no way to do that
+    fn unicode(&mut self, text: &str) -> Result<(), String>;
+    /// Put `text` on the clipboard to be pasted. With `restore`, what was
+    /// there before goes back once the paste has had time to land.
+    fn set_clipboard(&mut self, text: &str, restore: bool) -> Result<(), String>;
+    /// Wait between inputs; a stand-in can skip it
+    fn pause(&mut self, ms: u64) {
+        thread::sleep(Duration::from_millis(ms));
+    }
+}
+
+/// The real keyboard, through enigo
+pub struct EnigoInjector(Enigo);
+
+impl EnigoInjector {
+    pub fn new() -> Result<Self, String> {
+        Enigo::new(&Settings::default())
+            .map(EnigoInjector)
+            .map_err(|e| format!("Failed to create Enigo instance: {}", e))
+    }
+}
+
+impl TextInjector for EnigoInjector {
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        self.0
+            .text(text)
+            .map_err(|e| format!("Failed to type text: {}", e))
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), String> {
+        self.0
+            .key(key, direction)
+            .map_err(|e| format!("Failed to send {:?}: {}", key, e))
+    }
+
+    fn unicode(&mut self, text: &str) -> Result<(), String> {
+        insert_unicode(text)
+    }
+
+    fn set_clipboard(&mut self, text: &str, restore: bool) -> Result<(), String> {
+        let mut clipboard =
+            Clipboard::new().map_err(|e| format!("Failed to access clipboard: {}", e))?;
+
+        let previous = if restore {
+            SavedClipboard::capture(&mut clipboard)
+        } else {
+            None
+        };
+        clipboard
+            .set_text(text.to_string())
+            .map_err(|e| format!("Failed to write clipboard text: {}", e))?;
+
+        // Restore the previous clipboard in background well after the paste,
+        // to avoid racing the target app's paste handling.
+        if let Some(previous) = previous {
+            let pasted = text.to_string();
+            std::thread::spawn(move || {
+                thread::sleep(Duration::from_millis(
+                    CLIPBOARD_SETTLE_MS + PASTE_SETTLE_MS + 1200,
+                ));
+                if let Ok(mut cb) = Clipboard::new() {
+                    // Don't clobber something the user copied in the meantime.
+                    if cb.get_text().ok().as_deref() != Some(pasted.as_str()) {
+                        return;
+                    }
+                    if let Err(e) = previous.restore(&mut cb) {
+                        eprintln!("Failed to restore clipboard: {}", e);
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+// Returned by typing functions when macOS hasn't granted Accessibility access
+pub const ACCESSIBILITY_DENIED: &str =
+    "LocalWispr needs Accessibility permission to type. Enable it in System Settings > \
+     Privacy & Security > Accessibility, then try again.";
+
+#[cfg(target_os = "macos")]
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+}
+
+/// Whether the app may send synthetic input. Only macOS gates this, behind
+/// the Accessibility permission; without it enigo's events are dropped.
+pub fn has_input_permission() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        // SAFETY: takes no arguments and only reads the process's trust state
+        unsafe { AXIsProcessTrusted() }
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        true
+    }
+}
+
+/// Open the Accessibility pane of System Settings (macOS only)
+pub fn open_accessibility_settings() -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg("x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility")
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to open System Settings: {}", e))
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        Err("Accessibility settings only apply to macOS".to_string())
+    }
+}
+
+/// Type `text` into the focused application.
+///
+/// Input is simulated with enigo. That works on Windows, macOS and Linux/X11.
+/// Wayland compositors only let it reach XWayland windows, and native Wayland
+/// apps may silently ignore it, so there the text is also left on the
+/// clipboard for a manual paste.
+pub fn type_text(text: &str, mode: TypeMode, config: TypingConfig) -> Result<(), String> {
+    if text.trim().is_empty() {
         return Ok(());
     }
+    if !has_input_permission() {
+        return Err(ACCESSIBILITY_DENIED.to_string());
+    }
 
-    // Fallback path: direct key simulation.
-    thread::sleep(Duration::from_millis(50));
+    #[cfg(target_os = "linux")]
+    if is_wayland_session() {
+        return type_text_wayland(text, mode, config);
+    }
 
-    let mut enigo = Enigo::new(&Settings::default())
-        .map_err(|e| format!("Failed to create Enigo instance: {}", e))?;
+    simulate_typing(&mut EnigoInjector::new()?, text, mode, config, true)
+}
 
-    enigo
-        .text(text)
-        .map_err(|e| format!("Failed to type text: {}", e))?;
+/// Whether this is a Linux Wayland session, where synthetic input is restricted
+#[cfg(target_os = "linux")]
+fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE").is_ok_and(|t| t.eq_ignore_ascii_case("wayland"))
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+#[cfg(target_os = "linux")]
+fn type_text_wayland(text: &str, mode: TypeMode, config: TypingConfig) -> Result<(), String> {
+    // Keep the text on the clipboard afterwards: if the compositor dropped the
+    // simulated input, the user can still paste it.
+    let result = EnigoInjector::new()
+        .and_then(|mut injector| simulate_typing(&mut injector, text, mode, config, false));
+    let copied = Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text.to_string()))
+        .is_ok();
+
+    result.map_err(|e| {
+        eprintln!("Typing failed on Wayland: {}", e);
+        if copied {
+            "Simulated typing is blocked in this Wayland session. The text was copied to \
+             the clipboard; press Ctrl+V to paste it."
+                .to_string()
+        } else {
+            format!(
+                "Simulated typing is blocked in this Wayland session ({}). Try an X11 session.",
+                e
+            )
+        }
+    })
+}
+
+fn simulate_typing(
+    injector: &mut dyn TextInjector,
+    text: &str,
+    mode: TypeMode,
+    config: TypingConfig,
+    restore_clipboard: bool,
+) -> Result<(), String> {
+    if mode == TypeMode::Unicode {
+        injector.pause(config.pre_delay_ms);
+        match injector.unicode(text) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                eprintln!("Unicode insertion unavailable, pasting instead: {}", e);
+                return paste_via_clipboard(injector, text, restore_clipboard);
+            }
+        }
+    }
+
+    if mode == TypeMode::Paste {
+        // Fast path: paste through clipboard (much faster for long dictation).
+        if let Err(e) = paste_via_clipboard(injector, text, restore_clipboard) {
+            eprintln!("clipboard paste failed, falling back to key typing: {}", e);
+        } else {
+            return Ok(());
+        }
+    }
+
+    // Direct key simulation (also the fallback when pasting fails).
+    injector.pause(config.pre_delay_ms);
+    type_keystrokes(injector, text, config)
+}
+
+/// Type `text` as keystrokes, one character at a time when `char_delay_ms`
+/// asks for a pause between them. `pre_delay_ms` is left to the caller.
+pub fn type_keystrokes(
+    injector: &mut dyn TextInjector,
+    text: &str,
+    config: TypingConfig,
+) -> Result<(), String> {
+    if config.char_delay_ms == 0 {
+        return injector.text(text);
+    }
+
+    let mut buf = [0u8; 4];
+    for (i, c) in text.chars().enumerate() {
+        if i > 0 {
+            injector.pause(config.char_delay_ms);
+        }
+        injector.text(c.encode_utf8(&mut buf))?;
+    }
 
     Ok(())
 }
 
-fn paste_via_clipboard(text: &str) -> Result<(), String> {
-    let mut clipboard =
-        Clipboard::new().map_err(|e| format!("Failed to access clipboard: {}", e))?;
+/// Send `text` as a single run of Unicode input, or Err where the platform has
+/// no way to do that
+fn insert_unicode(text: &str) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        windows_input::send_unicode(text)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos_input::post_unicode(text)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let _ = text;
+        Err("no Unicode input API on this platform".to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows_input {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE,
+        VIRTUAL_KEY,
+    };
+
+    pub fn send_unicode(text: &str) -> Result<(), String> {
+        let mut inputs = Vec::with_capacity(text.len() * 2);
+        // Carriage return is what apps treat as Enter in Unicode input
+        for unit in text.replace('\n', "\r").encode_utf16() {
+            for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+                inputs.push(INPUT {
+                    r#type: INPUT_KEYBOARD,
+                    Anonymous: INPUT_0 {
+                        ki: KEYBDINPUT {
+                            wVk: VIRTUAL_KEY(0),
+                            wScan: unit,
+                            dwFlags: flags,
+                            time: 0,
+                            dwExtraInfo: 0,
+                        },
+                    },
+                });
+            }
+        }
+
+        // SAFETY: every INPUT is fully initialized and the size matches the struct
+        let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+        if sent as usize != inputs.len() {
+            return Err(format!(
+                "only {} of {} input events were accepted",
+                sent,
+                inputs.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos_input {
+    use std::ffi::c_void;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn CGEventCreateKeyboardEvent(
+            source: *const c_void,
+            keycode: u16,
+            key_down: bool,
+        ) -> *mut c_void;
+        fn CGEventKeyboardSetUnicodeString(event: *mut c_void, length: usize, string: *const u16);
+        fn CGEventPost(tap: u32, event: *mut c_void);
+        fn CFRelease(cf: *const c_void);
+    }
+
+    // kCGHIDEventTap
+    const HID_EVENT_TAP: u32 = 0;
+    // Longer strings can get truncated by the receiving app
+    const MAX_UNITS_PER_EVENT: usize = 20;
+
+    pub fn post_unicode(text: &str) -> Result<(), String> {
+        // Split on character boundaries so surrogate pairs stay together
+        let mut chunk: Vec<u16> = Vec::with_capacity(MAX_UNITS_PER_EVENT);
+        let mut buf = [0u16; 2];
+        for c in text.chars() {
+            let units = c.encode_utf16(&mut buf);
+            if chunk.len() + units.len() > MAX_UNITS_PER_EVENT {
+                post_chunk(&chunk)?;
+                chunk.clear();
+            }
+            chunk.extend_from_slice(units);
+        }
+        if !chunk.is_empty() {
+            post_chunk(&chunk)?;
+        }
+        Ok(())
+    }
+
+    fn post_chunk(units: &[u16]) -> Result<(), String> {
+        for key_down in [true, false] {
+            // SAFETY: the event is checked for null, only used while alive,
+            // and released exactly once
+            unsafe {
+                let event = CGEventCreateKeyboardEvent(std::ptr::null(), 0, key_down);
+                if event.is_null() {
+                    return Err("failed to create a keyboard event".to_string());
+                }
+                CGEventKeyboardSetUnicodeString(event, units.len(), units.as_ptr());
+                CGEventPost(HID_EVENT_TAP, event);
+                CFRelease(event);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the focused control looks like it takes text input.
+///
+/// Only implemented on Windows (via UI Automation) for now. `None` means it
+/// couldn't be determined, in which case callers should type as usual.
+pub fn focused_element_accepts_text() -> Option<bool> {
+    #[cfg(target_os = "windows")]
+    {
+        windows_focus::accepts_text()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows_focus {
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER,
+        COINIT_MULTITHREADED,
+    };
+    use windows::Win32::UI::Accessibility::{
+        CUIAutomation, IUIAutomation, UIA_ComboBoxControlTypeId, UIA_DocumentControlTypeId,
+        UIA_EditControlTypeId,
+    };
+
+    pub fn accepts_text() -> Option<bool> {
+        // SAFETY: COM is initialized for this thread before use and released
+        // after every interface pointer has been dropped
+        unsafe {
+            let initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+            let result = query_focused();
+            if initialized {
+                CoUninitialize();
+            }
+            result
+        }
+    }
+
+    unsafe fn query_focused() -> Option<bool> {
+        let automation: IUIAutomation =
+            CoCreateInstance(&CUIAutomation, None, CLSCTX_INPROC_SERVER).ok()?;
+        let element = automation.GetFocusedElement().ok()?;
+        let control_type = element.CurrentControlType().ok()?;
+        // Documents cover rich editors and browser content-editable regions
+        Some(
+            [
+                UIA_EditControlTypeId,
+                UIA_DocumentControlTypeId,
+                UIA_ComboBoxControlTypeId,
+            ]
+            .contains(&control_type),
+        )
+    }
+}
+
+/// A top-level window of another app, remembered so focus can be handed back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowHandle(isize);
+
+/// The window the user is working in, if it belongs to another app.
+///
+/// Only implemented on Windows; elsewhere the OS is trusted to return focus
+/// once the popup hides.
+pub fn foreground_window() -> Option<WindowHandle> {
+    #[cfg(target_os = "windows")]
+    {
+        windows_window::foreground().map(WindowHandle)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+/// A window dictation should always go to, picked by title and/or process
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TargetWindow {
+    /// Part of the window title, matched case-insensitively
+    pub title: Option<String>,
+    /// Executable name, e.g. `notepad.exe` (the `.exe` may be left off)
+    pub process: Option<String>,
+}
+
+impl TargetWindow {
+    /// Trim both fields, dropping blanks; Err if nothing is left to match on
+    pub fn validated(self) -> Result<Self, String> {
+        let clean = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let target = TargetWindow {
+            title: clean(self.title),
+            process: clean(self.process),
+        };
+        if target.title.is_none() && target.process.is_none() {
+            return Err("Give a window title or a process name to lock onto".to_string());
+        }
+        if !cfg!(target_os = "windows") {
+            return Err("Locking dictation to a window is only supported on Windows".to_string());
+        }
+        Ok(target)
+    }
+}
+
+/// Find an open window matching `target` (Windows only)
+pub fn find_window(target: &TargetWindow) -> Option<WindowHandle> {
+    #[cfg(target_os = "windows")]
+    {
+        windows_window::find(target).map(WindowHandle)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = target;
+        None
+    }
+}
+
+/// Bring `window` back to the foreground. Returns whether it now has focus.
+pub fn restore_focus(window: WindowHandle) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        windows_window::activate(window.0)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = window;
+        false
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows_window {
+    use super::TargetWindow;
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::{CloseHandle, BOOL, HWND, LPARAM};
+    use windows::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId, IsIconic,
+        IsWindow, IsWindowVisible, SetForegroundWindow, ShowWindow, SW_RESTORE,
+    };
+
+    struct Search<'a> {
+        target: &'a TargetWindow,
+        found: Option<isize>,
+    }
 
-    // Best-effort restore for previous text clipboard content.
-    let previous_text = clipboard.get_text().ok();
-    clipboard
-        .set_text(text.to_string())
-        .map_err(|e| format!("Failed to write clipboard text: {}", e))?;
+    pub fn find(target: &TargetWindow) -> Option<isize> {
+        let mut search = Search {
+            target,
+            found: None,
+        };
+        // SAFETY: the callback only runs during this call, while `search` is alive.
+        // EnumWindows reports an error when the callback stops it early; that's expected.
+        unsafe {
+            let _ = EnumWindows(
+                Some(visit_window),
+                LPARAM(&mut search as *mut Search as isize),
+            );
+        }
+        search.found
+    }
 
-    // Let the OS commit clipboard content before paste.
-    thread::sleep(Duration::from_millis(35));
+    unsafe extern "system" fn visit_window(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam.0 as *mut Search);
+        if IsWindowVisible(hwnd).as_bool() && matches(hwnd, search.target) {
+            search.found = Some(hwnd.0 as isize);
+            return BOOL(0);
+        }
+        BOOL(1)
+    }
 
-    let mut enigo = Enigo::new(&Settings::default())
-        .map_err(|e| format!("Failed to create Enigo instance: {}", e))?;
+    unsafe fn matches(hwnd: HWND, target: &TargetWindow) -> bool {
+        if let Some(title) = &target.title {
+            let mut buf = [0u16; 512];
+            let len = GetWindowTextW(hwnd, &mut buf).max(0) as usize;
+            let text = String::from_utf16_lossy(&buf[..len]).to_lowercase();
+            if !text.contains(&title.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(process) = &target.process {
+            let Some(name) = process_name(hwnd) else {
+                return false;
+            };
+            let wanted = process.to_lowercase();
+            let wanted = wanted.strip_suffix(".exe").unwrap_or(&wanted);
+            let name = name.to_lowercase();
+            if name.strip_suffix(".exe").unwrap_or(&name) != wanted {
+                return false;
+            }
+        }
+        true
+    }
 
+    // File name of the executable that owns the window
+    unsafe fn process_name(hwnd: HWND) -> Option<String> {
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut buf = [0u16; 1024];
+        let mut len = buf.len() as u32;
+        let queried = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buf.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(process);
+        queried.ok()?;
+        let path = String::from_utf16_lossy(&buf[..len as usize]);
+        path.rsplit(['\\', '/']).next().map(str::to_string)
+    }
+
+    pub fn foreground() -> Option<isize> {
+        // SAFETY: these calls only read window state and tolerate any handle
+        unsafe {
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_invalid() {
+                return None;
+            }
+            let mut pid = 0;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            // Our own popup or settings window is never a typing target
+            if pid == std::process::id() {
+                return None;
+            }
+            Some(hwnd.0 as isize)
+        }
+    }
+
+    pub fn activate(handle: isize) -> bool {
+        let hwnd = HWND(handle as *mut _);
+        // SAFETY: IsWindow guards against the window having been closed since
+        unsafe {
+            if !IsWindow(hwnd).as_bool() {
+                return false;
+            }
+            if GetForegroundWindow() == hwnd {
+                return true;
+            }
+            if IsIconic(hwnd).as_bool() {
+                let _ = ShowWindow(hwnd, SW_RESTORE);
+            }
+            let _ = SetForegroundWindow(hwnd);
+            GetForegroundWindow() == hwnd
+        }
+    }
+}
+
+/// Put `text` on the clipboard so the user can paste it themselves
+pub fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text.to_string()))
+        .map_err(|e| format!("Failed to copy to clipboard: {}", e))
+}
+
+/// Delete `count` characters before the cursor with Backspace.
+///
+/// Only removes the right text if nothing was typed and the cursor hasn't
+/// moved since; editors that delete whole grapheme clusters per Backspace
+/// may also remove a little more than intended.
+pub fn erase_chars(count: usize, config: TypingConfig) -> Result<(), String> {
+    if !has_input_permission() {
+        return Err(ACCESSIBILITY_DENIED.to_string());
+    }
+    let mut injector = EnigoInjector::new()?;
+    injector.pause(config.pre_delay_ms);
+    send_backspaces(&mut injector, count, config)
+}
+
+/// Press Backspace `count` times, paced like typing
+pub fn send_backspaces(
+    injector: &mut dyn TextInjector,
+    count: usize,
+    config: TypingConfig,
+) -> Result<(), String> {
+    for i in 0..count {
+        if i > 0 && config.char_delay_ms > 0 {
+            injector.pause(config.char_delay_ms);
+        }
+        injector.key(Key::Backspace, Direction::Click)?;
+    }
+    Ok(())
+}
+
+/// Clipboard contents captured before pasting, put back afterwards
+enum SavedClipboard {
+    Text(String),
+    Image(ImageData<'static>),
+}
+
+impl SavedClipboard {
+    /// Snapshot the clipboard. Anything other than text or an image (files,
+    /// rich formats only) can't be read back through arboard and is skipped.
+    fn capture(clipboard: &mut Clipboard) -> Option<Self> {
+        if let Ok(text) = clipboard.get_text() {
+            return Some(SavedClipboard::Text(text));
+        }
+        clipboard.get_image().ok().map(SavedClipboard::Image)
+    }
+
+    fn restore(self, clipboard: &mut Clipboard) -> Result<(), arboard::Error> {
+        match self {
+            SavedClipboard::Text(text) => clipboard.set_text(text),
+            SavedClipboard::Image(image) => clipboard.set_image(image),
+        }
+    }
+}
+
+/// Press the platform's paste shortcut (Cmd+V on macOS, Ctrl+V elsewhere)
+pub fn send_paste_shortcut(injector: &mut dyn TextInjector) -> Result<(), String> {
     #[cfg(target_os = "macos")]
     let modifier = Key::Meta;
     #[cfg(not(target_os = "macos"))]
@@ -54,29 +928,225 @@ fn paste_via_clipboard(text: &str) -> Result<(), String> {
     #[cfg(not(target_os = "windows"))]
     let paste_key = Key::Unicode('v');
 
-    enigo
-        .key(modifier, Direction::Press)
-        .map_err(|e| format!("Failed to press modifier key: {}", e))?;
-    enigo
-        .key(paste_key, Direction::Click)
-        .map_err(|e| format!("Failed to trigger paste shortcut: {}", e))?;
-    enigo
-        .key(modifier, Direction::Release)
-        .map_err(|e| format!("Failed to release modifier key: {}", e))?;
+    injector.key(modifier, Direction::Press)?;
+    injector.key(paste_key, Direction::Click)?;
+    injector.key(modifier, Direction::Release)
+}
+
+// Let the OS commit clipboard content before the paste
+const CLIPBOARD_SETTLE_MS: u64 = 35;
+// Give the target app a moment to consume the paste
+const PASTE_SETTLE_MS: u64 = 120;
 
-    // Give target app a moment to consume paste.
-    thread::sleep(Duration::from_millis(120));
+fn paste_via_clipboard(
+    injector: &mut dyn TextInjector,
+    text: &str,
+    restore: bool,
+) -> Result<(), String> {
+    injector.set_clipboard(text, restore)?;
+    injector.pause(CLIPBOARD_SETTLE_MS);
+    send_paste_shortcut(injector)?;
+    injector.pause(PASTE_SETTLE_MS);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Input {
+        Text(String),
+        Key(Key, Direction),
+        Unicode(String),
+        Clipboard(String, bool),
+        Pause(u64),
+    }
+
+    // Records what would have been sent, without touching the real keyboard
+    #[derive(Default)]
+    struct MockInjector {
+        inputs: Vec<Input>,
+        no_unicode: bool,
+        no_clipboard: bool,
+    }
 
-    // Restore clipboard text in background after a longer delay to avoid
-    // racing the target app's paste handling.
-    if let Some(old) = previous_text {
-        std::thread::spawn(move || {
-            thread::sleep(Duration::from_millis(1200));
-            if let Ok(mut cb) = Clipboard::new() {
-                let _ = cb.set_text(old);
+    impl TextInjector for MockInjector {
+        fn text(&mut self, text: &str) -> Result<(), String> {
+            self.inputs.push(Input::Text(text.to_string()));
+            Ok(())
+        }
+
+        fn key(&mut self, key: Key, direction: Direction) -> Result<(), String> {
+            self.inputs.push(Input::Key(key, direction));
+            Ok(())
+        }
+
+        fn unicode(&mut self, text: &str) -> Result<(), String> {
+            if self.no_unicode {
+                return Err("no Unicode input".to_string());
+            }
+            self.inputs.push(Input::Unicode(text.to_string()));
+            Ok(())
+        }
+
+        fn set_clipboard(&mut self, text: &str, restore: bool) -> Result<(), String> {
+            if self.no_clipboard {
+                return Err("no clipboard".to_string());
             }
-        });
+            self.inputs
+                .push(Input::Clipboard(text.to_string(), restore));
+            Ok(())
+        }
+
+        fn pause(&mut self, ms: u64) {
+            self.inputs.push(Input::Pause(ms));
+        }
     }
 
-    Ok(())
+    fn config(pre_delay_ms: u64, char_delay_ms: u64) -> TypingConfig {
+        TypingConfig {
+            pre_delay_ms,
+            char_delay_ms,
+        }
+    }
+
+    fn paste_shortcut() -> Vec<Input> {
+        let mut injector = MockInjector::default();
+        send_paste_shortcut(&mut injector).unwrap();
+        injector.inputs
+    }
+
+    fn typed(mode: TypeMode, config: TypingConfig, injector: &mut MockInjector) -> Vec<Input> {
+        simulate_typing(injector, "héllo", mode, config, true).unwrap();
+        std::mem::take(&mut injector.inputs)
+    }
+
+    #[test]
+    fn keystrokes_send_the_text_at_once() {
+        let inputs = typed(
+            TypeMode::Keystrokes,
+            config(50, 0),
+            &mut MockInjector::default(),
+        );
+        assert_eq!(inputs, [Input::Pause(50), Input::Text("héllo".to_string())]);
+    }
+
+    #[test]
+    fn char_delay_paces_each_character() {
+        let mut injector = MockInjector::default();
+        simulate_typing(
+            &mut injector,
+            "hé",
+            TypeMode::Keystrokes,
+            config(0, 20),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            injector.inputs,
+            [
+                Input::Pause(0),
+                Input::Text("h".to_string()),
+                Input::Pause(20),
+                Input::Text("é".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn paste_goes_through_the_clipboard() {
+        let inputs = typed(TypeMode::Paste, config(50, 0), &mut MockInjector::default());
+        let mut expected = vec![
+            Input::Clipboard("héllo".to_string(), true),
+            Input::Pause(CLIPBOARD_SETTLE_MS),
+        ];
+        expected.extend(paste_shortcut());
+        expected.push(Input::Pause(PASTE_SETTLE_MS));
+        assert_eq!(inputs, expected);
+    }
+
+    #[test]
+    fn paste_falls_back_to_keystrokes() {
+        let mut injector = MockInjector {
+            no_clipboard: true,
+            ..Default::default()
+        };
+        let inputs = typed(TypeMode::Paste, config(50, 0), &mut injector);
+        assert_eq!(inputs, [Input::Pause(50), Input::Text("héllo".to_string())]);
+    }
+
+    #[test]
+    fn unicode_sends_one_run() {
+        let inputs = typed(
+            TypeMode::Unicode,
+            config(50, 5),
+            &mut MockInjector::default(),
+        );
+        assert_eq!(
+            inputs,
+            [Input::Pause(50), Input::Unicode("héllo".to_string())]
+        );
+    }
+
+    #[test]
+    fn unicode_falls_back_to_paste() {
+        let mut injector = MockInjector {
+            no_unicode: true,
+            ..Default::default()
+        };
+        let inputs = typed(TypeMode::Unicode, config(50, 0), &mut injector);
+        assert_eq!(inputs[0], Input::Pause(50));
+        assert_eq!(inputs[1], Input::Clipboard("héllo".to_string(), true));
+    }
+
+    #[test]
+    fn backspaces_are_paced_like_typing() {
+        let mut injector = MockInjector::default();
+        send_backspaces(&mut injector, 2, config(50, 10)).unwrap();
+        assert_eq!(
+            injector.inputs,
+            [
+                Input::Key(Key::Backspace, Direction::Click),
+                Input::Pause(10),
+                Input::Key(Key::Backspace, Direction::Click),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalization_cleans_up_a_dictation() {
+        let options = TextNormalization {
+            collapse_spaces: true,
+            trim_lines: true,
+            capitalize_sentences: true,
+            trailing_space: true,
+        };
+        assert_eq!(
+            normalize_text("  hello   world. again \n next ", options),
+            "Hello world. Again\nNext "
+        );
+        assert_eq!(
+            normalize_text("hello  world", TextNormalization::default()),
+            "hello  world"
+        );
+    }
+
+    #[test]
+    fn pieces_continue_the_sentence() {
+        let options = TextNormalization {
+            capitalize_sentences: true,
+            trailing_space: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_piece("more words", options, "Some", false),
+            "more words"
+        );
+        assert_eq!(
+            normalize_piece("next one", options, "Done. ", false),
+            "Next one"
+        );
+        assert_eq!(normalize_piece("end", options, "The", true), "end ");
+    }
 }
@@ -2,218 +2,4141 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod audio;
+mod audiofile;
+mod backend;
+mod cues;
+mod export;
+mod history;
 mod keyboard;
+mod openai;
+mod recordings;
+mod replacements;
+mod settings;
 mod soniox;
+#[cfg(feature = "whisper")]
+mod whisper;
 
-use serde::Serialize;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use futures_util::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Emitter, Manager, State,
+    AppHandle, Emitter, Listener, Manager, PhysicalPosition, RunEvent, State, WebviewUrl,
+    WebviewWindow, WebviewWindowBuilder,
+};
+use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
+use tauri_plugin_global_shortcut::{
+    Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutEvent, ShortcutState,
 };
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+// App state to track recording status
+#[derive(Clone)]
+pub struct AppState {
+    // Atomic so the shortcut handler and session loops can check it without awaiting a lock
+    pub is_recording: Arc<AtomicBool>,
+    pub soniox_api_key: Arc<Mutex<String>>,
+    // Set when SONIOX rejected the stored key, until a new one is entered
+    pub api_key_rejected: Arc<AtomicBool>,
+    pub openai_api_key: Arc<Mutex<String>>,
+    pub usage: Arc<backend::UsageMeter>,
+    pub endpoint_url: Arc<Mutex<Option<String>>>,
+    pub last_start_ms: Arc<AtomicU64>,
+    pub latest_transcription: Arc<Mutex<String>>,
+    // Append mode: each dictation continues `accumulated_text` instead of starting over
+    pub append_mode: Arc<Mutex<bool>>,
+    pub accumulated_text: Arc<Mutex<String>>,
+    // Text in the editor window; dictations finished while it's open are
+    // added here instead of being typed
+    pub editor_text: Arc<Mutex<String>>,
+    // Word timings of the last completed transcription
+    pub latest_segments: Arc<Mutex<Vec<backend::TimedSegment>>>,
+    pub selected_input_device: Arc<Mutex<Option<String>>>,
+    pub channel_strategy: Arc<Mutex<audio::ChannelStrategy>>,
+    pub input_gain_db: Arc<Mutex<f32>>,
+    pub auto_gain: Arc<Mutex<bool>>,
+    // TPDF dither when quantizing captured audio to 16 bits
+    pub dither: Arc<Mutex<bool>>,
+    pub resample_quality: Arc<Mutex<audio::ResampleQuality>>,
+    // Rate audio is resampled to and declared to SONIOX
+    pub sample_rate: Arc<Mutex<u32>>,
+    // Silence sent to SONIOX ahead of the audio (0 = none)
+    pub priming_ms: Arc<Mutex<u64>>,
+    // Drop stray tokens transcribed from the priming silence
+    pub trim_priming_artifacts: Arc<Mutex<bool>>,
+    // Speech without new tokens for this long shows "still listening" (0 = off)
+    pub stall_notice_ms: Arc<Mutex<u64>>,
+    // Revised live words wait this long to settle before the popup shows them (0 = off)
+    pub display_smoothing_ms: Arc<Mutex<u64>>,
+    // Keepalive sent to SONIOX after this long without sending anything (0 = off)
+    pub keepalive_secs: Arc<Mutex<u64>>,
+    pub audio_format: Arc<Mutex<audio::AudioFormat>>,
+    // Keep the input stream open between dictations for a faster start
+    pub warm_mic_enabled: Arc<Mutex<bool>>,
+    pub warm_mic: Arc<Mutex<Option<Arc<audio::WarmMic>>>>,
+    pub recording_mode: Arc<Mutex<RecordingMode>>,
+    pub finish_mode: Arc<Mutex<FinishMode>>,
+    pub no_speech_notice: Arc<Mutex<NoSpeechNotice>>,
+    pub push_to_talk_min_ms: Arc<Mutex<u64>>,
+    pub hold_cancel_modifier: Arc<Mutex<HoldCancelModifier>>,
+    // Shortcut plus cancel modifier, registered only while push-to-talk is held
+    pub hold_cancel_shortcut: Arc<Mutex<Option<Shortcut>>>,
+    pub hold_cancel_shortcut_id: Arc<AtomicU32>,
+    pub type_mode: Arc<Mutex<keyboard::TypeMode>>,
+    // How right-to-left dictations are typed
+    pub text_direction: Arc<Mutex<keyboard::TextDirection>>,
+    pub typing_config: Arc<Mutex<keyboard::TypingConfig>>,
+    pub type_delay_ms: Arc<Mutex<u64>>,
+    // Countdown shown in the popup before typing, so manual keystrokes don't interleave
+    pub typing_countdown_ms: Arc<Mutex<u64>>,
+    pub insert_style: Arc<Mutex<keyboard::InsertStyle>>,
+    // Only type when the focused control takes text (Windows only for now)
+    pub require_text_field: Arc<Mutex<bool>>,
+    // Window that was in front when recording started, to hand focus back to (Windows only)
+    pub target_window: Arc<Mutex<Option<keyboard::WindowHandle>>>,
+    // Window every dictation goes to, whatever had focus (Windows only)
+    pub target_lock: Arc<Mutex<Option<keyboard::TargetWindow>>>,
+    pub replacements: Arc<Mutex<replacements::ReplacementConfig>>,
+    // Words masked or removed from the final text
+    pub redaction: Arc<Mutex<replacements::RedactionConfig>>,
+    pub text_normalization: Arc<Mutex<keyboard::TextNormalization>>,
+    // Last dictation typed and when, used as context for insert styles
+    pub last_typed: Arc<Mutex<Option<(String, u64)>>>,
+    pub transcription_options: Arc<Mutex<soniox::TranscriptionOptions>>,
+    pub history: Arc<Mutex<history::TranscriptionHistory>>,
+    pub auto_stop: Arc<Mutex<soniox::AutoStopConfig>>,
+    pub pause_breaks: Arc<Mutex<soniox::PauseBreakConfig>>,
+    pub max_duration_secs: Arc<Mutex<u64>>,
+    pub backend: Arc<Mutex<backend::BackendKind>>,
+    pub whisper_model_path: Arc<Mutex<Option<String>>>,
+    pub popup_near_cursor: Arc<Mutex<bool>>,
+    // Keep the popup above other windows (or let them cover it)
+    pub popup_always_on_top: Arc<Mutex<bool>>,
+    pub audio_cues: Arc<Mutex<bool>>,
+    pub cue_output: Arc<Mutex<cues::CueOutput>>,
+    // Save each session's audio as WAV, to `recordings_dir` or the default folder
+    pub save_recordings: Arc<Mutex<bool>>,
+    pub recordings_dir: Arc<Mutex<Option<String>>>,
+    pub shortcut: Arc<Mutex<Shortcut>>,
+    pub cancel_shortcut: Arc<Mutex<Option<Shortcut>>>,
+    // Extra shortcuts that dictate with their own recognition options
+    pub profiles: Arc<Mutex<Vec<(Shortcut, settings::ShortcutProfile)>>>,
+    // Id of the cancel shortcut (0 = none), readable from the sync shortcut handler
+    pub cancel_shortcut_id: Arc<AtomicU32>,
+    pub undo_shortcut: Arc<Mutex<Option<Shortcut>>>,
+    pub undo_shortcut_id: Arc<AtomicU32>,
+    // Separate stop-and-type shortcut; while set, the recording shortcut only starts
+    pub stop_shortcut: Arc<Mutex<Option<Shortcut>>>,
+    pub stop_shortcut_id: Arc<AtomicU32>,
+    // Cycles through recent transcriptions to type one again
+    pub history_shortcut: Arc<Mutex<Option<Shortcut>>>,
+    pub history_shortcut_id: Arc<AtomicU32>,
+    // Transcription the history shortcut is showing (0 = newest); each press
+    // bumps `history_pick_seq` so only the last one's timer types it
+    pub history_pick: Arc<Mutex<Option<usize>>>,
+    pub history_pick_seq: Arc<AtomicU64>,
+    // Switches to the next connected microphone; None when unset
+    pub device_shortcut: Arc<Mutex<Option<Shortcut>>>,
+    pub device_shortcut_id: Arc<AtomicU32>,
+    // Bumped by each switch so only the last one hides the popup again
+    pub device_notice_seq: Arc<AtomicU64>,
+    // Cleanup before exit has begun / is done, so a second quit doesn't run it
+    // again and the exit it triggers goes through
+    pub shutdown_started: Arc<AtomicBool>,
+    pub shutdown_done: Arc<AtomicBool>,
+    // Bumped on every start so a superseded session knows to stop
+    pub session_generation: Arc<AtomicU64>,
+    // Cancel flag of the most recent transcription session
+    pub session_cancelled: Arc<Mutex<Arc<AtomicBool>>>,
+    // The running session; resolves to its final text once it has wrapped up
+    pub session_task: Arc<Mutex<Option<JoinHandle<Option<String>>>>>,
+    // Type finalized words while still recording instead of all at once on stop
+    pub live_typing: Arc<Mutex<bool>>,
+    // What live typing has typed during the current session, and its task
+    pub live_typed: Arc<Mutex<LiveTyped>>,
+    pub live_typer: Arc<Mutex<Option<JoinHandle<()>>>>,
+    // Debounce state for the global shortcut
+    pub shortcut_is_down: Arc<AtomicBool>,
+    pub last_shortcut_ms: Arc<AtomicU64>,
+    // Held by `settings::update` and `save_settings` from reading the file (or
+    // the state) to writing it back, so two updates can't each save over the
+    // other's change
+    pub settings_lock: Arc<Mutex<()>>,
+}
+
+// How the global shortcut controls recording
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingMode {
+    // Press once to start, press again to stop and type
+    #[default]
+    Toggle,
+    // Record while the shortcut is held, stop and type on release
+    PushToTalk,
+}
+
+// What happens to the transcription once recording stops
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishMode {
+    // Type it into the focused app right away
+    #[default]
+    TypeImmediately,
+    // Keep the popup open to review and edit it; type on `confirm_and_type`
+    ConfirmBeforeType,
+}
+
+// How to tell the user a dictation came back empty (nothing said, or a muted mic)
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoSpeechNotice {
+    // Finish quietly, as if something had been typed
+    Off,
+    // Emit `no-speech-detected` and show the popup with a notice briefly
+    #[default]
+    Popup,
+    // The popup notice plus a tone, even with audio cues off
+    PopupAndSound,
+}
+
+// Modifier that, pressed while holding push-to-talk, discards the dictation
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldCancelModifier {
+    #[default]
+    None,
+    Shift,
+    Alt,
+    Control,
+}
+
+impl HoldCancelModifier {
+    fn modifiers(self) -> Option<Modifiers> {
+        match self {
+            HoldCancelModifier::None => None,
+            HoldCancelModifier::Shift => Some(Modifiers::SHIFT),
+            HoldCancelModifier::Alt => Some(Modifiers::ALT),
+            HoldCancelModifier::Control => Some(Modifiers::CONTROL),
+        }
+    }
+}
+
+// Push-to-talk holds shorter than this are treated as accidental taps, unless changed
+const DEFAULT_PUSH_TO_TALK_MIN_MS: u64 = 300;
+// Longest accepted tap threshold, so real dictations aren't thrown away
+const MAX_PUSH_TO_TALK_MIN_MS: u64 = 2000;
+
+// Recordings stop on their own after this long unless changed (0 = unlimited)
+const DEFAULT_MAX_DURATION_SECS: u64 = 120;
+
+// How long stopping waits for the session's final text before typing
+const SESSION_FINISH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Non-streaming backends only start transcribing on stop, so allow for the upload
+const UPLOAD_FINISH_TIMEOUT: std::time::Duration =
+    openai::UPLOAD_TIMEOUT.saturating_add(std::time::Duration::from_secs(5));
+
+// Longest quitting waits for a dictation to close its connection and microphone
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+// How long stopping waits for live typing to catch up with the final words
+const LIVE_TYPING_FINISH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// A dictation typed within this long of the previous one is treated as its
+// continuation for spacing and capitalization
+const INSERT_CONTINUATION_MS: u64 = 30_000;
+
+// Accepted range for the manual input gain
+const MIN_INPUT_GAIN_DB: f32 = -20.0;
+const MAX_INPUT_GAIN_DB: f32 = 30.0;
+
+// Phrase typed by `test_typing`, and the time given to focus the target app
+const TEST_TYPING_SAMPLE: &str = "Typing test — اختبار الكتابة";
+const TEST_TYPING_COUNTDOWN: std::time::Duration = std::time::Duration::from_secs(3);
+
+// Pause between hiding the popup and typing, unless changed
+const DEFAULT_TYPE_DELAY_MS: u64 = 280;
+
+// Warning shown before typing starts unless changed (0 = none), and its upper bound
+const DEFAULT_TYPING_COUNTDOWN_MS: u64 = 0;
+const MAX_TYPING_COUNTDOWN_MS: u64 = 5000;
+// How often `typing-countdown` updates the remaining time
+const TYPING_COUNTDOWN_TICK: std::time::Duration = std::time::Duration::from_millis(100);
+// How long the popup stays up to say no speech was heard
+const NO_SPEECH_NOTICE_DURATION: std::time::Duration = std::time::Duration::from_millis(1500);
+// The history shortcut steps through this many recent transcriptions, and
+// types the one shown once it hasn't been pressed for a moment
+const HISTORY_PICK_SIZE: usize = 5;
+const HISTORY_PICK_CONFIRM_DELAY: std::time::Duration = std::time::Duration::from_millis(1500);
+
+// How long the popup shows the microphone the device shortcut switched to
+const DEVICE_NOTICE_DURATION: std::time::Duration = std::time::Duration::from_millis(1500);
+
+// Label of the window for long-form dictation, opened by `open_editor`
+const EDITOR_WINDOW: &str = "editor";
+
+// Longest to wait for the popup to disappear before typing
+const POPUP_HIDE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(1000);
+
+// Upper bound for typing delays so a typo can't stall typing for minutes
+const MAX_TYPING_DELAY_MS: u64 = 1000;
+
+// Always-on emergency stop; it can't be changed or turned off
+const PANIC_SHORTCUT: &str = "Ctrl+Alt+Shift+Escape";
+
+fn panic_shortcut() -> Shortcut {
+    Shortcut::new(
+        Some(Modifiers::CONTROL | Modifiers::ALT | Modifiers::SHIFT),
+        Code::Escape,
+    )
+}
+
+// A shortcut can be a single key with no modifiers, which is how foot pedals
+// and programmable mouse buttons work: they send a key the user picks in the
+// device's software, usually F13-F24, which no keyboard has. USB pedals that
+// act as a keyboard (most Infinity, Olympus and generic ones) and mice whose
+// software maps a button to a key all work this way; devices that only show
+// up as raw HID input aren't supported. Holding the pedal works with
+// push-to-talk like holding a shortcut.
+//
+// A bare key that's used for typing would stop it from being typed anywhere,
+// so only keys outside normal typing may go without modifiers.
+fn check_bare_key(shortcut: Shortcut) -> Result<(), String> {
+    if !shortcut.mods.is_empty() {
+        return Ok(());
+    }
+    let typing_key = matches!(
+        shortcut.key,
+        Code::KeyA
+            | Code::KeyB
+            | Code::KeyC
+            | Code::KeyD
+            | Code::KeyE
+            | Code::KeyF
+            | Code::KeyG
+            | Code::KeyH
+            | Code::KeyI
+            | Code::KeyJ
+            | Code::KeyK
+            | Code::KeyL
+            | Code::KeyM
+            | Code::KeyN
+            | Code::KeyO
+            | Code::KeyP
+            | Code::KeyQ
+            | Code::KeyR
+            | Code::KeyS
+            | Code::KeyT
+            | Code::KeyU
+            | Code::KeyV
+            | Code::KeyW
+            | Code::KeyX
+            | Code::KeyY
+            | Code::KeyZ
+            | Code::Digit0
+            | Code::Digit1
+            | Code::Digit2
+            | Code::Digit3
+            | Code::Digit4
+            | Code::Digit5
+            | Code::Digit6
+            | Code::Digit7
+            | Code::Digit8
+            | Code::Digit9
+            | Code::Space
+            | Code::Enter
+            | Code::Tab
+            | Code::Backspace
+            | Code::Delete
+            | Code::Minus
+            | Code::Equal
+            | Code::BracketLeft
+            | Code::BracketRight
+            | Code::Backslash
+            | Code::Semicolon
+            | Code::Quote
+            | Code::Backquote
+            | Code::Comma
+            | Code::Period
+            | Code::Slash
+            | Code::ArrowUp
+            | Code::ArrowDown
+            | Code::ArrowLeft
+            | Code::ArrowRight
+    );
+    if typing_key {
+        return Err(
+            "A shortcut without modifiers must use a key that isn't used for typing, \
+             such as F13-F24 (what most foot pedals send)"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+// Refuse the emergency stop shortcut for anything else
+fn check_not_panic_shortcut(shortcut: Shortcut) -> Result<(), String> {
+    if shortcut == panic_shortcut() {
+        return Err(format!(
+            "{} is reserved for the emergency stop",
+            PANIC_SHORTCUT
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Serialize)]
+struct RecordingStateEvent {
+    is_recording: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct RecordingElapsedEvent {
+    seconds: u64,
+}
+
+#[derive(Clone, Serialize)]
+struct HistoryCandidateEvent {
+    text: String,
+    // 1 = the newest transcription
+    position: usize,
+    count: usize,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// Gap between the mouse cursor and the popup's corner
+const POPUP_CURSOR_OFFSET: i32 = 16;
+
+// Move the popup next to the mouse cursor, kept fully on the cursor's monitor
+fn position_near_cursor(window: &WebviewWindow) -> Result<(), String> {
+    let cursor = window.cursor_position().map_err(|e| e.to_string())?;
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    let (cursor_x, cursor_y) = (cursor.x as i32, cursor.y as i32);
+
+    let monitors = window.available_monitors().map_err(|e| e.to_string())?;
+    let monitor = monitors
+        .into_iter()
+        .find(|m| {
+            let (pos, dim) = (m.position(), m.size());
+            cursor_x >= pos.x
+                && cursor_x < pos.x + dim.width as i32
+                && cursor_y >= pos.y
+                && cursor_y < pos.y + dim.height as i32
+        })
+        .or(window.primary_monitor().map_err(|e| e.to_string())?)
+        .ok_or("No monitor found")?;
+
+    let (min_x, min_y) = (monitor.position().x, monitor.position().y);
+    let max_x = min_x + monitor.size().width as i32 - size.width as i32;
+    let max_y = min_y + monitor.size().height as i32 - size.height as i32;
+
+    // Prefer below-right of the cursor, flipping to the other side near edges.
+    let mut x = cursor_x + POPUP_CURSOR_OFFSET;
+    if x > max_x {
+        x = cursor_x - POPUP_CURSOR_OFFSET - size.width as i32;
+    }
+    let mut y = cursor_y + POPUP_CURSOR_OFFSET;
+    if y > max_y {
+        y = cursor_y - POPUP_CURSOR_OFFSET - size.height as i32;
+    }
+    let x = x.clamp(min_x, max_x.max(min_x));
+    let y = y.clamp(min_y, max_y.max(min_y));
+
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| e.to_string())
+}
+
+// Play a start/stop cue if enabled, returning once it has finished
+async fn play_cue(state: &AppState, cue: cues::Cue) {
+    if !*state.audio_cues.lock().await {
+        return;
+    }
+    play_cue_now(state, cue).await;
+}
+
+// Play a cue regardless of the audio cues setting
+async fn play_cue_now(state: &AppState, cue: cues::Cue) {
+    let output = state.cue_output.lock().await.clone();
+    match tokio::task::spawn_blocking(move || cues::play(cue, &output)).await {
+        Ok(Err(e)) => eprintln!("Failed to play audio cue: {}", e),
+        Err(e) => eprintln!("Audio cue task failed: {}", e),
+        Ok(Ok(())) => {}
+    }
+}
+
+// Emit `recording-elapsed` every second until this recording ends.
+// A new recording changes `last_start_ms`, which also ends the loop.
+fn spawn_elapsed_timer(app: AppHandle, state: AppState) {
+    let started_ms = state.last_start_ms.load(Ordering::Relaxed);
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+        loop {
+            ticker.tick().await;
+            if !state.is_recording.load(Ordering::SeqCst)
+                || state.last_start_ms.load(Ordering::Relaxed) != started_ms
+            {
+                break;
+            }
+            let seconds = now_millis().saturating_sub(started_ms) / 1000;
+            let _ = app.emit("recording-elapsed", RecordingElapsedEvent { seconds });
+        }
+    });
+}
+
+// Microphone settings for the next capture
+async fn capture_config(state: &AppState) -> audio::CaptureConfig {
+    audio::CaptureConfig {
+        input_device: state.selected_input_device.lock().await.clone(),
+        channel_strategy: *state.channel_strategy.lock().await,
+        gain_db: *state.input_gain_db.lock().await,
+        auto_gain: *state.auto_gain.lock().await,
+        sample_rate: *state.sample_rate.lock().await,
+        audio_format: *state.audio_format.lock().await,
+        dither: *state.dither.lock().await,
+        resample_quality: *state.resample_quality.lock().await,
+    }
+}
+
+// Open, reopen or close the warm mic so it matches the current settings
+async fn refresh_warm_mic(
+    app: &AppHandle,
+    state: &AppState,
+) -> Result<Option<Arc<audio::WarmMic>>, backend::TranscriptionError> {
+    let mut warm_mic = state.warm_mic.lock().await;
+    if !*state.warm_mic_enabled.lock().await {
+        // Dropping the last handle releases the device
+        *warm_mic = None;
+        return Ok(None);
+    }
+
+    let config = capture_config(state).await;
+    if let Some(mic) = warm_mic.as_ref().filter(|mic| *mic.config() == config) {
+        return Ok(Some(mic.clone()));
+    }
+    // Settings changed; release the old stream before opening the new one
+    *warm_mic = None;
+    let mic = Arc::new(audio::WarmMic::open(app.clone(), config).await?);
+    *warm_mic = Some(mic.clone());
+    Ok(Some(mic))
+}
+
+// Recording flag transitions. Starting and stopping swap the flag, so of two
+// racing starts (or stops and cancels) only one goes ahead.
+
+// Enter recording; false if a dictation was already running
+fn try_start_recording(is_recording: &AtomicBool) -> bool {
+    !is_recording.swap(true, Ordering::SeqCst)
+}
+
+// Leave recording, on stop or cancel; false if it had already stopped
+fn try_stop_recording(is_recording: &AtomicBool) -> bool {
+    is_recording.swap(false, Ordering::SeqCst)
+}
+
+// Leave recording after a session failed, keeping the popup up to show the
+// error but letting it be dismissed
+async fn end_failed_session(app: &AppHandle, state: &AppState) {
+    try_stop_recording(&state.is_recording);
+    let _ = app.emit(
+        "recording-state",
+        RecordingStateEvent {
+            is_recording: false,
+        },
+    );
+    make_popup_interactive(app);
+}
+
+// Run a transcription session in the background, resetting state if it fails.
+// `options` overrides the global recognition options (from a shortcut profile).
+async fn spawn_transcription(
+    app: AppHandle,
+    state: AppState,
+    api_key: String,
+    options: Option<soniox::TranscriptionOptions>,
+    file: Option<Arc<audiofile::FileAudio>>,
+) {
+    let endpoint_url = state
+        .endpoint_url
+        .lock()
+        .await
+        .clone()
+        .unwrap_or_else(|| soniox::SONIOX_WS_URL.to_string());
+    // A file is already saved, so it's never recorded again
+    let recording_dir = if file.is_none() && *state.save_recordings.lock().await {
+        match state.recordings_dir.lock().await.clone() {
+            Some(dir) => Some(std::path::PathBuf::from(dir)),
+            None => recordings::default_dir(&app)
+                .map_err(|e| eprintln!("Not saving this recording: {}", e))
+                .ok(),
+        }
+    } else {
+        None
+    };
+    let session = soniox::SessionConfig {
+        api_key,
+        endpoint_url,
+        capture: capture_config(&state).await,
+        options: match options {
+            Some(options) => options,
+            None => state.transcription_options.lock().await.clone(),
+        },
+        // Stopping early would only cut a file short (and type it)
+        auto_stop: if file.is_some() {
+            soniox::AutoStopConfig {
+                enabled: false,
+                ..*state.auto_stop.lock().await
+            }
+        } else {
+            *state.auto_stop.lock().await
+        },
+        pause_breaks: *state.pause_breaks.lock().await,
+        max_duration: match *state.max_duration_secs.lock().await {
+            _ if file.is_some() => None,
+            0 => None,
+            secs => Some(std::time::Duration::from_secs(secs)),
+        },
+        recording_dir,
+        priming_ms: *state.priming_ms.lock().await,
+        trim_priming_artifacts: *state.trim_priming_artifacts.lock().await,
+        stall_notice_ms: *state.stall_notice_ms.lock().await,
+        display_smoothing_ms: *state.display_smoothing_ms.lock().await,
+        keepalive_secs: *state.keepalive_secs.lock().await,
+    };
+    let kind = *state.backend.lock().await;
+    let whisper_model_path = state.whisper_model_path.lock().await.clone();
+
+    // Each session gets its own flag so cancelling can't leak into the next one
+    let cancelled = Arc::new(AtomicBool::new(false));
+    *state.session_cancelled.lock().await = cancelled.clone();
+
+    let generation = backend::SessionGeneration::next(state.session_generation.clone());
+
+    // Files and non-streaming backends keep typing everything on stop, and so
+    // does redaction, whose phrases can span the pieces typed live. Speaker
+    // labels and confidence marks reshape the final text, so it wouldn't line
+    // up with what was typed.
+    let live_text = if file.is_none()
+        && kind == backend::BackendKind::Soniox
+        && *state.live_typing.lock().await
+        && !state.redaction.lock().await.enabled
+        && session.options.speaker_labels.is_none()
+        && session.options.low_confidence_threshold.is_none()
+        && !editor_open(&app)
+    {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        *state.live_typed.lock().await = LiveTyped::default();
+        let typer = tokio::spawn(type_live_text(app.clone(), state.clone(), rx));
+        if let Some(previous) = state.live_typer.lock().await.replace(typer) {
+            previous.abort();
+        }
+        Some(tx)
+    } else {
+        None
+    };
+
+    let source = match file {
+        Some(file) => audio::AudioSource::File(file),
+        None => audio::AudioSource::Microphone(
+            refresh_warm_mic(&app, &state).await.unwrap_or_else(|e| {
+                eprintln!("Could not keep the mic warm: {}", e);
+                None
+            }),
+        ),
+    };
+
+    let session_task = state.session_task.clone();
+    let (panic_app, panic_state, panic_generation) =
+        (app.clone(), state.clone(), generation.clone());
+    let run = async move {
+        let ctx = backend::SessionContext {
+            app: app.clone(),
+            is_recording: state.is_recording.clone(),
+            latest_transcription: state.latest_transcription.clone(),
+            cancelled,
+            segments: state.latest_segments.clone(),
+            generation: generation.clone(),
+            source,
+            usage: state.usage.clone(),
+            live_text,
+        };
+        let result = match backend::create_backend(kind, session, whisper_model_path) {
+            Ok(backend) => backend.transcribe_stream(ctx).await,
+            Err(e) => Err(e),
+        };
+        save_usage(&app, &state).await;
+
+        match result {
+            Ok(text) => {
+                state.history.lock().await.push(&text, now_millis());
+                Some(text)
+            }
+            Err(backend::TranscriptionError::Cancelled) => {
+                println!("Transcription cancelled");
+                None
+            }
+            // A newer session has taken over; leave its state alone
+            Err(e) if !generation.is_current() => {
+                eprintln!("Superseded transcription ended: {}", e);
+                None
+            }
+            Err(e @ backend::TranscriptionError::Auth(_))
+                if kind == backend::BackendKind::Soniox =>
+            {
+                // Don't retry a dead key on every press; ask for a new one
+                eprintln!("SONIOX rejected the API key: {}", e);
+                state.api_key_rejected.store(true, Ordering::Relaxed);
+                end_failed_session(&app, &state).await;
+                let _ = app.emit("auth-error", e.to_string());
+                let _ = show_window_interactive(&app);
+                None
+            }
+            Err(e) => {
+                eprintln!("Transcription error: {}", e);
+                end_failed_session(&app, &state).await;
+                let _ = app.emit("transcription-error", e);
+                None
+            }
+        }
+    };
+    let on_panic = async move {
+        if panic_generation.is_current() {
+            end_failed_session(&panic_app, &panic_state).await;
+            let _ = panic_app.emit(
+                "transcription-error",
+                backend::TranscriptionError::Backend(
+                    "The transcription session stopped unexpectedly".to_string(),
+                ),
+            );
+        }
+    };
+    let task = tokio::spawn(catch_session_panic(run, on_panic));
+    *session_task.lock().await = Some(task);
+}
+
+// Run a session, turning a panic into no text once `on_panic` has cleaned up,
+// so a panic deep in a backend can't leave the app stuck recording. Relies on
+// panics unwinding, which is why the release profile doesn't abort on panic.
+async fn catch_session_panic(
+    run: impl std::future::Future<Output = Option<String>>,
+    on_panic: impl std::future::Future<Output = ()>,
+) -> Option<String> {
+    match AssertUnwindSafe(run).catch_unwind().await {
+        Ok(text) => text,
+        Err(_) => {
+            eprintln!("Transcription task panicked");
+            on_panic.await;
+            None
+        }
+    }
+}
+
+// Popup window transitions. Every show and hide goes through these so
+// focusability, visibility and position can't get out of step.
+
+// Show the recording popup without taking focus from the app being dictated
+// into. Focusable is cleared before showing so the popup can't grab keystrokes.
+fn show_popup_noninteractive(app: &AppHandle, near_cursor: bool) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let _ = window.set_focusable(false);
+    if near_cursor {
+        if let Err(e) = position_near_cursor(&window) {
+            eprintln!("Failed to position popup near cursor: {}", e);
+        }
+    }
+    let _ = window.show();
+}
+
+// Show and focus the popup for input (key setup, review, errors)
+fn show_window_interactive(app: &AppHandle) -> Result<(), String> {
+    let Some(window) = app.get_webview_window("main") else {
+        return Ok(());
+    };
+    let _ = window.set_focusable(true);
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+// Let an already visible popup be clicked and dismissed (e.g. after an error)
+fn make_popup_interactive(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.set_focusable(true);
+    }
+}
+
+// Keep the popup above other windows, or let them cover it. The window keeps
+// the setting across hiding and showing.
+fn apply_popup_on_top(app: &AppHandle, on_top: bool) {
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = window.set_always_on_top(on_top) {
+            eprintln!("Failed to change whether the popup stays on top: {}", e);
+        }
+    }
+}
+
+// Hide the popup, leaving it focusable so the next interactive show works
+fn hide_popup(app: &AppHandle) -> Result<(), String> {
+    let Some(window) = app.get_webview_window("main") else {
+        return Ok(());
+    };
+    let _ = window.set_focusable(true);
+    window.hide().map_err(|e| e.to_string())
+}
+
+// Persist the lifetime usage total so it survives restarts
+async fn save_usage(app: &AppHandle, state: &AppState) {
+    let lifetime = state.usage.stats().lifetime_seconds;
+    if let Err(e) = settings::update(app, |s| s.usage_lifetime_seconds = lifetime).await {
+        eprintln!("Failed to save usage stats: {}", e);
+    }
+}
+
+// Snapshot of the preferences currently in effect
+async fn current_preferences(state: &AppState) -> settings::Preferences {
+    settings::Preferences {
+        input_device: state.selected_input_device.lock().await.clone(),
+        channel_strategy: *state.channel_strategy.lock().await,
+        input_gain_db: *state.input_gain_db.lock().await,
+        auto_gain: *state.auto_gain.lock().await,
+        dither: *state.dither.lock().await,
+        resample_quality: *state.resample_quality.lock().await,
+        sample_rate: *state.sample_rate.lock().await,
+        audio_format: *state.audio_format.lock().await,
+        priming_ms: *state.priming_ms.lock().await,
+        trim_priming_artifacts: *state.trim_priming_artifacts.lock().await,
+        stall_notice_ms: *state.stall_notice_ms.lock().await,
+        display_smoothing_ms: *state.display_smoothing_ms.lock().await,
+        keepalive_secs: *state.keepalive_secs.lock().await,
+        warm_mic: *state.warm_mic_enabled.lock().await,
+        recording_mode: *state.recording_mode.lock().await,
+        finish_mode: *state.finish_mode.lock().await,
+        no_speech_notice: *state.no_speech_notice.lock().await,
+        push_to_talk_min_ms: *state.push_to_talk_min_ms.lock().await,
+        hold_cancel_modifier: *state.hold_cancel_modifier.lock().await,
+        type_mode: *state.type_mode.lock().await,
+        text_direction: *state.text_direction.lock().await,
+        typing_config: *state.typing_config.lock().await,
+        type_delay_ms: *state.type_delay_ms.lock().await,
+        typing_countdown_ms: *state.typing_countdown_ms.lock().await,
+        insert_style: *state.insert_style.lock().await,
+        text_normalization: *state.text_normalization.lock().await,
+        append_mode: *state.append_mode.lock().await,
+        live_typing: *state.live_typing.lock().await,
+        require_text_field: *state.require_text_field.lock().await,
+        target_lock: state.target_lock.lock().await.clone(),
+        transcription_options: state.transcription_options.lock().await.clone(),
+        history_limit: state.history.lock().await.limit(),
+        auto_stop: *state.auto_stop.lock().await,
+        pause_breaks: *state.pause_breaks.lock().await,
+        max_duration_secs: *state.max_duration_secs.lock().await,
+        backend: *state.backend.lock().await,
+        whisper_model_path: state.whisper_model_path.lock().await.clone(),
+        popup_near_cursor: *state.popup_near_cursor.lock().await,
+        popup_always_on_top: *state.popup_always_on_top.lock().await,
+        audio_cues: *state.audio_cues.lock().await,
+        cue_output: state.cue_output.lock().await.clone(),
+        save_recordings: *state.save_recordings.lock().await,
+        recordings_dir: state.recordings_dir.lock().await.clone(),
+    }
+}
+
+// Write the current preferences to the settings file
+async fn save_settings(app: &AppHandle, state: &AppState) -> Result<(), String> {
+    // Snapshot under the lock, so two setters can't save in the opposite
+    // order and put the older snapshot on disk
+    let _guard = state.settings_lock.lock().await;
+    let preferences = current_preferences(state).await;
+    settings::update_locked(app, |s| s.preferences = preferences)
+}
+
+// Check a whole set of preferences with the same rules as the individual
+// setters, then make them current. Nothing changes if any value is rejected.
+async fn apply_preferences(
+    app: &AppHandle,
+    state: &AppState,
+    prefs: settings::Preferences,
+) -> Result<(), String> {
+    let prefs = validated_preferences(prefs)?;
+    store_preferences(app, state, prefs).await
+}
+
+// Check a whole set of preferences with the same rules as the individual
+// setters, returning them cleaned up the way the setters would store them
+fn validated_preferences(prefs: settings::Preferences) -> Result<settings::Preferences, String> {
+    if !(MIN_INPUT_GAIN_DB..=MAX_INPUT_GAIN_DB).contains(&prefs.input_gain_db) {
+        return Err(format!(
+            "Input gain must be between {} and {} dB",
+            MIN_INPUT_GAIN_DB, MAX_INPUT_GAIN_DB
+        ));
+    }
+    let sample_rate = soniox::validate_sample_rate(prefs.sample_rate)?;
+    if prefs.priming_ms > soniox::MAX_PRIMING_MS {
+        return Err(format!(
+            "Priming silence must be at most {}ms",
+            soniox::MAX_PRIMING_MS
+        ));
+    }
+    if prefs.stall_notice_ms > soniox::MAX_STALL_NOTICE_MS {
+        return Err(format!(
+            "Stall notice must be at most {}ms",
+            soniox::MAX_STALL_NOTICE_MS
+        ));
+    }
+    if prefs.display_smoothing_ms > soniox::MAX_DISPLAY_SMOOTHING_MS {
+        return Err(format!(
+            "Display smoothing must be at most {}ms",
+            soniox::MAX_DISPLAY_SMOOTHING_MS
+        ));
+    }
+    if prefs.keepalive_secs > soniox::MAX_KEEPALIVE_SECS {
+        return Err(format!(
+            "Keepalive interval must be at most {}s",
+            soniox::MAX_KEEPALIVE_SECS
+        ));
+    }
+    if prefs.push_to_talk_min_ms > MAX_PUSH_TO_TALK_MIN_MS {
+        return Err(format!(
+            "Tap threshold must be at most {}ms so short dictations still get typed",
+            MAX_PUSH_TO_TALK_MIN_MS
+        ));
+    }
+    if prefs.type_delay_ms > MAX_TYPING_DELAY_MS
+        || prefs.typing_config.pre_delay_ms > MAX_TYPING_DELAY_MS
+        || prefs.typing_config.char_delay_ms > MAX_TYPING_DELAY_MS
+    {
+        return Err(format!(
+            "Typing delays must be at most {} ms",
+            MAX_TYPING_DELAY_MS
+        ));
+    }
+    if prefs.typing_countdown_ms > MAX_TYPING_COUNTDOWN_MS {
+        return Err(format!(
+            "Typing countdown must be at most {}ms",
+            MAX_TYPING_COUNTDOWN_MS
+        ));
+    }
+    let transcription_options = prefs.transcription_options.validated()?;
+    let target_lock = prefs
+        .target_lock
+        .map(keyboard::TargetWindow::validated)
+        .transpose()?;
+    if prefs.auto_stop.enabled && prefs.auto_stop.silence_ms == 0 {
+        return Err("Silence duration must be greater than zero".to_string());
+    }
+    if !(0.0..=1.0).contains(&prefs.auto_stop.threshold) {
+        return Err("Silence threshold must be between 0.0 and 1.0".to_string());
+    }
+    if prefs.pause_breaks.enabled && prefs.pause_breaks.pause_ms == 0 {
+        return Err("Pause length must be greater than zero".to_string());
+    }
+    if !(0.0..=1.0).contains(&prefs.cue_output.volume) {
+        return Err("Cue volume must be between 0.0 and 1.0".to_string());
+    }
+    if !prefs.backend.is_available() {
+        return Err("This transcription backend is not included in this build".to_string());
+    }
+    let whisper_model_path = prefs.whisper_model_path.filter(|p| !p.trim().is_empty());
+    if let Some(path) = &whisper_model_path {
+        if !std::path::Path::new(path).is_file() {
+            return Err(format!("Whisper model not found: {}", path));
+        }
+    }
+    let recordings_dir = prefs
+        .recordings_dir
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    if let Some(dir) = &recordings_dir {
+        if std::path::Path::new(dir).is_file() {
+            return Err(format!("\"{}\" is a file, not a folder", dir));
+        }
+    }
+
+    Ok(settings::Preferences {
+        input_device: prefs.input_device.filter(|id| !id.trim().is_empty()),
+        sample_rate,
+        transcription_options,
+        target_lock,
+        whisper_model_path,
+        recordings_dir,
+        cue_output: cues::CueOutput {
+            device: prefs.cue_output.device.filter(|id| !id.trim().is_empty()),
+            volume: prefs.cue_output.volume,
+        },
+        ..prefs
+    })
+}
+
+// Make a set of preferences from `validated_preferences` current
+async fn store_preferences(
+    app: &AppHandle,
+    state: &AppState,
+    prefs: settings::Preferences,
+) -> Result<(), String> {
+    *state.selected_input_device.lock().await = prefs.input_device;
+    *state.channel_strategy.lock().await = prefs.channel_strategy;
+    *state.input_gain_db.lock().await = prefs.input_gain_db;
+    *state.auto_gain.lock().await = prefs.auto_gain;
+    *state.dither.lock().await = prefs.dither;
+    *state.resample_quality.lock().await = prefs.resample_quality;
+    *state.sample_rate.lock().await = prefs.sample_rate;
+    *state.audio_format.lock().await = prefs.audio_format;
+    *state.priming_ms.lock().await = prefs.priming_ms;
+    *state.trim_priming_artifacts.lock().await = prefs.trim_priming_artifacts;
+    *state.stall_notice_ms.lock().await = prefs.stall_notice_ms;
+    *state.display_smoothing_ms.lock().await = prefs.display_smoothing_ms;
+    *state.keepalive_secs.lock().await = prefs.keepalive_secs;
+    *state.warm_mic_enabled.lock().await = prefs.warm_mic;
+    *state.recording_mode.lock().await = prefs.recording_mode;
+    *state.finish_mode.lock().await = prefs.finish_mode;
+    *state.no_speech_notice.lock().await = prefs.no_speech_notice;
+    *state.push_to_talk_min_ms.lock().await = prefs.push_to_talk_min_ms;
+    *state.hold_cancel_modifier.lock().await = prefs.hold_cancel_modifier;
+    *state.type_mode.lock().await = prefs.type_mode;
+    *state.text_direction.lock().await = prefs.text_direction;
+    *state.typing_config.lock().await = prefs.typing_config;
+    *state.type_delay_ms.lock().await = prefs.type_delay_ms;
+    *state.typing_countdown_ms.lock().await = prefs.typing_countdown_ms;
+    *state.insert_style.lock().await = prefs.insert_style;
+    *state.text_normalization.lock().await = prefs.text_normalization;
+    *state.append_mode.lock().await = prefs.append_mode;
+    *state.live_typing.lock().await = prefs.live_typing;
+    *state.require_text_field.lock().await = prefs.require_text_field;
+    *state.target_lock.lock().await = prefs.target_lock;
+    *state.transcription_options.lock().await = prefs.transcription_options;
+    state.history.lock().await.set_limit(prefs.history_limit);
+    *state.auto_stop.lock().await = prefs.auto_stop;
+    *state.pause_breaks.lock().await = prefs.pause_breaks;
+    *state.max_duration_secs.lock().await = prefs.max_duration_secs;
+    *state.backend.lock().await = prefs.backend;
+    *state.whisper_model_path.lock().await = prefs.whisper_model_path;
+    *state.popup_near_cursor.lock().await = prefs.popup_near_cursor;
+    *state.popup_always_on_top.lock().await = prefs.popup_always_on_top;
+    apply_popup_on_top(app, prefs.popup_always_on_top);
+    *state.audio_cues.lock().await = prefs.audio_cues;
+    *state.cue_output.lock().await = prefs.cue_output;
+    *state.save_recordings.lock().await = prefs.save_recordings;
+    *state.recordings_dir.lock().await = prefs.recordings_dir;
+
+    refresh_warm_mic(app, state)
+        .await
+        .map_err(|e| format!("Could not keep the mic warm: {}", e))?;
+    Ok(())
+}
+
+// The API key the selected backend authenticates with (empty for local ones)
+async fn backend_api_key(state: &AppState, kind: backend::BackendKind) -> String {
+    match kind {
+        backend::BackendKind::Soniox => state.soniox_api_key.lock().await.clone(),
+        backend::BackendKind::OpenAi => state.openai_api_key.lock().await.clone(),
+        backend::BackendKind::WhisperLocal => String::new(),
+    }
+}
+
+// The selected backend's API key, or why it can't be used
+async fn usable_api_key(state: &AppState) -> Result<String, String> {
+    let kind = *state.backend.lock().await;
+    let api_key = backend_api_key(state, kind).await;
+    match kind {
+        backend::BackendKind::Soniox if api_key.is_empty() => {
+            Err("SONIOX API key not set".to_string())
+        }
+        backend::BackendKind::Soniox if state.api_key_rejected.load(Ordering::Relaxed) => {
+            Err("SONIOX rejected the API key; enter a new one".to_string())
+        }
+        backend::BackendKind::OpenAi if api_key.is_empty() => {
+            Err("OpenAI API key not set".to_string())
+        }
+        _ => Ok(api_key),
+    }
+}
+
+// Note which window the dictation is meant for, before the popup appears
+async fn remember_target_window(state: &AppState) {
+    *state.target_window.lock().await = keyboard::foreground_window();
+}
+
+// Start recording from the global shortcut
+async fn begin_recording(
+    app: AppHandle,
+    state: AppState,
+    options: Option<soniox::TranscriptionOptions>,
+) {
+    println!("Starting recording...");
+    let kind = *state.backend.lock().await;
+    let api_key = backend_api_key(&state, kind).await;
+    // Only SONIOX has a setup screen; a missing OpenAI key is reported as an error
+    if kind == backend::BackendKind::Soniox
+        && (api_key.is_empty() || state.api_key_rejected.load(Ordering::Relaxed))
+    {
+        // Show window for API key setup
+        println!("No usable API key, showing setup window");
+        let _ = show_window_interactive(&app);
+        return;
+    }
+    if microphone_blocked(&app) {
+        let _ = show_window_interactive(&app);
+        return;
+    }
+    // Another press got there first
+    if !try_start_recording(&state.is_recording) {
+        return;
+    }
+
+    remember_target_window(&state).await;
+
+    // Show a small popup while recording (don't steal focus!)
+    let near_cursor = *state.popup_near_cursor.lock().await;
+    show_popup_noninteractive(&app, near_cursor);
+
+    state.last_start_ms.store(now_millis(), Ordering::Relaxed);
+    let _ = app.emit(
+        "recording-state",
+        RecordingStateEvent { is_recording: true },
+    );
+    spawn_elapsed_timer(app.clone(), state.clone());
+
+    // Clear previous transcription
+    *state.latest_transcription.lock().await = String::new();
+    state.latest_segments.lock().await.clear();
+
+    // Finish the start cue before capture opens so the mic doesn't record it.
+    play_cue(&state, cues::Cue::Start).await;
+
+    spawn_transcription(app, state, api_key, options, None).await;
+}
+
+// Stop recording from the global shortcut and type the transcription
+async fn finish_and_type(app: AppHandle, state: AppState) {
+    println!("Stopping recording...");
+    try_stop_recording(&state.is_recording);
+    let _ = app.emit(
+        "recording-state",
+        RecordingStateEvent {
+            is_recording: false,
+        },
+    );
+
+    let cue_state = state.clone();
+    tokio::spawn(async move { play_cue(&cue_state, cues::Cue::Stop).await });
+
+    let text = collect_session_text(&state).await;
+    println!("Got transcription for typing: {} chars", text.len());
+
+    if let Some(typed) = finish_live_typing(&state).await {
+        // Already typed as it was spoken, so there's nothing to review
+        let _ = hide_popup(&app);
+        *state.latest_transcription.lock().await = String::new();
+        let _ = app.emit("finish-and-type", ());
+        let mut output = typed.output;
+        let rest = untyped_words(&text, &typed.source);
+        if !rest.trim().is_empty() {
+            let piece = prepare_live_piece(&state, rest, &output, true).await;
+            match live_target_ok(&state).await {
+                Ok(()) => match type_live_piece(&state, &piece).await {
+                    Ok(()) => output.push_str(&piece),
+                    Err(e) => {
+                        eprintln!("Failed to type the last words: {}", e);
+                        notify_if_permission_missing(&app);
+                        let _ = app.emit("typing-error", e);
+                    }
+                },
+                Err(reason) => hold_on_clipboard(&app, &state, rest, &reason).await,
+            }
+        }
+        // Typed in pieces, but undo takes back everything that went out
+        *state.last_typed.lock().await = Some((output, now_millis()));
+        return;
+    }
+
+    if editor_open(&app) {
+        let _ = hide_popup(&app);
+        *state.latest_transcription.lock().await = String::new();
+        let _ = app.emit("finish-and-type", ());
+        if text.trim().is_empty() {
+            notify_no_speech(&app, &state).await;
+        } else {
+            add_to_editor(&app, &state, &text).await;
+        }
+        return;
+    }
+
+    let combined = if *state.append_mode.lock().await {
+        Some(append_dictation(&state, &text).await)
+    } else {
+        None
+    };
+
+    if *state.finish_mode.lock().await == FinishMode::ConfirmBeforeType && !text.trim().is_empty() {
+        // The review covers every dictation gathered so far
+        show_review(&app, &state, combined.unwrap_or(text)).await;
+        return;
+    }
+
+    // Earlier dictations were already typed; only the new one goes out
+    let no_speech = text.trim().is_empty();
+    hide_and_type(app.clone(), state.clone(), text).await;
+    if no_speech {
+        notify_no_speech(&app, &state).await;
+    }
+}
+
+// Let the user know nothing was heard, so a muted or too-quiet mic doesn't
+// look like the app silently failing
+async fn notify_no_speech(app: &AppHandle, state: &AppState) {
+    let notice = *state.no_speech_notice.lock().await;
+    if notice == NoSpeechNotice::Off {
+        return;
+    }
+    println!("No speech detected");
+    let started_ms = state.last_start_ms.load(Ordering::Relaxed);
+    let near_cursor = *state.popup_near_cursor.lock().await;
+    show_popup_noninteractive(app, near_cursor);
+    let _ = app.emit("no-speech-detected", ());
+    if notice == NoSpeechNotice::PopupAndSound {
+        play_cue_now(state, cues::Cue::NoSpeech).await;
+    }
+
+    tokio::time::sleep(NO_SPEECH_NOTICE_DURATION).await;
+    // Leave the popup alone if a new recording has taken it over
+    if !state.is_recording.load(Ordering::SeqCst)
+        && state.last_start_ms.load(Ordering::Relaxed) == started_ms
+    {
+        let _ = hide_popup(app);
+    }
+}
+
+// What live typing has done during the current dictation
+#[derive(Default)]
+pub struct LiveTyped {
+    // SONIOX's words that have gone out, to line up with the final text
+    source: String,
+    // What they were typed as, after the same cleanup as a whole dictation;
+    // this is what undo has to erase
+    output: String,
+}
+
+// Type finalized text as the session sends it, until the session ends and
+// drops its sender. Stops at the first failure, leaving the rest for stop.
+async fn type_live_text(
+    app: AppHandle,
+    state: AppState,
+    mut live_text: tokio::sync::mpsc::UnboundedReceiver<backend::LiveText>,
+) {
+    let mut pending = String::new();
+    while let Some(change) = live_text.recv().await {
+        apply_live_change(&mut pending, change);
+        // Hold back the last word, which SONIOX may still be adding pieces
+        // to, so replacements and redaction see whole words
+        let Some(end) = pending.rfind(char::is_whitespace) else {
+            continue;
+        };
+        let ready: String = pending.drain(..end).collect();
+
+        let typed_output = state.live_typed.lock().await.output.clone();
+        let piece = prepare_live_piece(&state, &ready, &typed_output, false).await;
+        if !piece.is_empty() {
+            if let Err(reason) = live_target_ok(&state).await {
+                println!("Live typing stopped: {}", reason);
+                return;
+            }
+            if let Err(e) = type_live_piece(&state, &piece).await {
+                eprintln!("Live typing failed: {}", e);
+                notify_if_permission_missing(&app);
+                let _ = app.emit("typing-error", e);
+                return;
+            }
+        }
+        let mut typed = state.live_typed.lock().await;
+        typed.source.push_str(&ready);
+        typed.output.push_str(&piece);
+    }
+}
+
+// Apply a change from the session to the text live typing hasn't typed yet.
+// A rewind normally only reaches the held-back last word; past that the text
+// is already on screen and stop has to reconcile it.
+fn apply_live_change(pending: &mut String, change: backend::LiveText) {
+    match change {
+        backend::LiveText::Append(delta) => pending.push_str(&delta),
+        backend::LiveText::Rewind(bytes) => {
+            let keep = pending.len().saturating_sub(bytes);
+            if bytes > pending.len() {
+                eprintln!("Live typing can't take back text it already typed");
+            }
+            // Rewinds end on the char boundaries the session sent
+            pending.truncate(keep);
+        }
+    }
+}
+
+// Turn a piece of a live dictation into what gets typed, through the same
+// steps as a whole dictation: replacements, redaction, normalization, insert
+// style and direction marks. `typed` is what the earlier pieces were typed
+// as, empty for the first; `last` marks the final piece.
+async fn prepare_live_piece(state: &AppState, piece: &str, typed: &str, last: bool) -> String {
+    let text = clean_text(state, piece).await;
+    let options = *state.text_normalization.lock().await;
+    let text = keyboard::normalize_piece(&text, options, typed, last);
+    if text.is_empty() {
+        return text;
+    }
+
+    // The cleanup trims the piece, so put back a sentence break before it
+    let line_breaks: String = piece
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .filter(|&c| c == '\n')
+        .collect();
+    let text = if typed.is_empty() {
+        let style = *state.insert_style.lock().await;
+        let last_typed = state.last_typed.lock().await;
+        keyboard::apply_insert_style(&text, style, continued_from(&last_typed))
+    } else if !line_breaks.is_empty() {
+        format!("{}{}", line_breaks, text)
+    } else {
+        // Pieces of one dictation only need a space between them
+        keyboard::apply_insert_style(&text, keyboard::InsertStyle::AutoSpace, Some(typed))
+    };
+    let starts_line = typed.is_empty() || typed.ends_with('\n');
+    keyboard::add_direction_marks(&text, *state.text_direction.lock().await, starts_line)
+}
+
+// Type a prepared piece of a live dictation. The target kept focus while
+// recording, so there's nothing to wait for.
+async fn type_live_piece(state: &AppState, piece: &str) -> Result<(), String> {
+    let mode = type_mode_for(state, piece).await;
+    let typing = keyboard::TypingConfig {
+        pre_delay_ms: 0,
+        ..*state.typing_config.lock().await
+    };
+    let piece = piece.to_string();
+    tokio::task::spawn_blocking(move || keyboard::type_text(&piece, mode, typing))
+        .await
+        .map_err(|e| format!("Live typing task failed: {}", e))?
+}
+
+// Whether live typing may type into what has focus now: the locked target
+// window if there is one, and a text field if one is required
+async fn live_target_ok(state: &AppState) -> Result<(), String> {
+    if let Some(lock) = state.target_lock.lock().await.clone() {
+        let foreground = keyboard::foreground_window();
+        if foreground.is_none() || keyboard::find_window(&lock) != foreground {
+            return Err("The target window isn't in front".to_string());
+        }
+    }
+    if *state.require_text_field.lock().await
+        && keyboard::focused_element_accepts_text() == Some(false)
+    {
+        return Err("No text field is focused".to_string());
+    }
+    Ok(())
+}
+
+// Wait for live typing to catch up and return what it typed, or None if this
+// session wasn't typed live or nothing went out, leaving the whole dictation
+// to be typed the usual way
+async fn finish_live_typing(state: &AppState) -> Option<LiveTyped> {
+    let mut typer = state.live_typer.lock().await.take()?;
+    if tokio::time::timeout(LIVE_TYPING_FINISH_TIMEOUT, &mut typer)
+        .await
+        .is_err()
+    {
+        eprintln!("Live typing didn't finish in time");
+        typer.abort();
+    }
+
+    let typed = std::mem::take(&mut *state.live_typed.lock().await);
+    (!typed.output.is_empty()).then_some(typed)
+}
+
+// The part of the final `text` after the words live typing already sent.
+// SONIOX can still revise a word after it went out, so words are matched by
+// position: however the typed ones differ, they stand for as many final words.
+fn untyped_words<'a>(text: &'a str, typed: &str) -> &'a str {
+    let typed_words: Vec<&str> = typed.split_whitespace().collect();
+    let shared = typed_words
+        .iter()
+        .zip(text.split_whitespace())
+        .take_while(|(typed, word)| **typed == *word)
+        .count();
+    if shared < typed_words.len() {
+        eprintln!(
+            "Final text differs from what was typed live after {} words",
+            shared
+        );
+    }
+
+    let mut rest = text;
+    for _ in 0..typed_words.len() {
+        let start = rest.len() - rest.trim_start().len();
+        let word_len = rest[start..]
+            .find(char::is_whitespace)
+            .unwrap_or(rest.len() - start);
+        rest = &rest[start + word_len..];
+    }
+    rest
+}
+
+// Add a dictation to the text gathered in append mode, spaced so words don't
+// run together, and return everything gathered so far
+async fn append_dictation(state: &AppState, text: &str) -> String {
+    let mut accumulated = state.accumulated_text.lock().await;
+    let text = text.trim();
+    if !text.is_empty() {
+        let previous = (!accumulated.is_empty()).then(|| accumulated.clone());
+        let spaced = keyboard::apply_insert_style(
+            text,
+            keyboard::InsertStyle::AutoSpace,
+            previous.as_deref(),
+        );
+        accumulated.push_str(&spaced);
+    }
+    accumulated.clone()
+}
+
+// Whether the editor window is showing, so dictations should go into it
+fn editor_open(app: &AppHandle) -> bool {
+    app.get_webview_window(EDITOR_WINDOW)
+        .is_some_and(|window| window.is_visible().unwrap_or(false))
+}
+
+// Add a finished dictation to the editor's text and send the editor the result
+async fn add_to_editor(app: &AppHandle, state: &AppState, text: &str) {
+    let text = prepare_text(state, text).await;
+    let mut editor_text = state.editor_text.lock().await;
+    let previous = (!editor_text.is_empty()).then(|| editor_text.clone());
+    let spaced =
+        keyboard::apply_insert_style(&text, keyboard::InsertStyle::AutoSpace, previous.as_deref());
+    editor_text.push_str(&spaced);
+    let _ = app.emit("editor-text", editor_text.as_str());
+}
+
+// Let the session collect its trailing final words, falling back to what has
+// been shown so far if it fails or takes too long
+async fn collect_session_text(state: &AppState) -> String {
+    let timeout = if state.backend.lock().await.is_streaming() {
+        SESSION_FINISH_TIMEOUT
+    } else {
+        UPLOAD_FINISH_TIMEOUT
+    };
+    let cancelled = state.session_cancelled.lock().await.clone();
+    let session = state.session_task.lock().await.take();
+    let finished = match session {
+        Some(task) => tokio::time::timeout(timeout, task)
+            .await
+            .ok()
+            .and_then(|joined| joined.ok())
+            .flatten(),
+        None => None,
+    };
+    // Cancelled while finishing up: there's nothing to type
+    if cancelled.load(Ordering::Relaxed) {
+        return String::new();
+    }
+    match finished {
+        Some(text) => text,
+        None => state.latest_transcription.lock().await.clone(),
+    }
+}
+
+// Keep the popup up with the finished text so the user can edit and confirm it
+async fn show_review(app: &AppHandle, state: &AppState, text: String) {
+    *state.latest_transcription.lock().await = String::new();
+    let _ = app.emit("review-transcription", text.trim());
+    let _ = show_window_interactive(app);
+}
+
+// Hide the popup and type `text` into whatever app gets focus back
+async fn hide_and_type(app: AppHandle, state: AppState, text: String) {
+    // Hide window first
+    let _ = hide_popup(&app);
+
+    // Clear the transcription state
+    *state.latest_transcription.lock().await = String::new();
+
+    // Emit event for frontend to clear its state
+    let _ = app.emit("finish-and-type", ());
+
+    // Type the text directly from Rust
+    if !text.trim().is_empty() {
+        // Make sure the popup is really gone, then let the user release the
+        // shortcut keys and the OS restore focus to the target window.
+        wait_for_popup_hidden(&app).await;
+        let delay = *state.type_delay_ms.lock().await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
+
+        let countdown_ms = *state.typing_countdown_ms.lock().await;
+        if countdown_ms > 0 {
+            run_typing_countdown(&app, &state, countdown_ms).await;
+        }
+
+        // Hiding the popup doesn't always hand focus back on Windows, so put
+        // the original window in front explicitly, or the locked target if set
+        let target = state.target_window.lock().await.take();
+        let target_lock = state.target_lock.lock().await.clone();
+        if let Some(lock) = target_lock {
+            let focused = keyboard::find_window(&lock).is_some_and(keyboard::restore_focus);
+            if !focused {
+                println!("Locked target window not found; holding dictation on the clipboard");
+                hold_on_clipboard(&app, &state, &text, "The target window isn't open").await;
+                return;
+            }
+        } else if let Some(target) = target {
+            if !keyboard::restore_focus(target) {
+                eprintln!("Couldn't refocus the window recording started in");
+            }
+        }
+
+        // Don't dump text into a game or a command field by accident
+        if *state.require_text_field.lock().await
+            && keyboard::focused_element_accepts_text() == Some(false)
+        {
+            println!("Focused control doesn't take text; holding dictation on the clipboard");
+            hold_on_clipboard(&app, &state, &text, "No text field is focused").await;
+            return;
+        }
+
+        match type_dictation(&state, text.trim()).await {
+            Ok(_) => println!("Text typed successfully!"),
+            Err(e) => {
+                eprintln!("Failed to type text: {}", e);
+                notify_if_permission_missing(&app);
+                // Bring the popup back so the user sees why nothing appeared
+                let _ = app.emit("typing-error", e);
+                let _ = show_window_interactive(&app);
+            }
+        }
+    } else {
+        println!("No text to type (empty transcription)");
+    }
+}
+
+// Put a dictation that couldn't be typed on the clipboard and tell the user why
+async fn hold_on_clipboard(app: &AppHandle, state: &AppState, text: &str, reason: &str) {
+    let held = prepare_text(state, text).await;
+    let message = match keyboard::copy_to_clipboard(&held) {
+        Ok(()) => format!(
+            "{}, so nothing was typed. The text was copied to the clipboard.",
+            reason
+        ),
+        Err(e) => format!("{}, so nothing was typed ({})", reason, e),
+    };
+    let _ = app.emit("typing-skipped", message);
+    let _ = show_window_interactive(app);
+}
+
+// After a typing failure, tell the UI if the cause is the missing macOS
+// Accessibility permission so it can show how to grant it
+fn notify_if_permission_missing(app: &AppHandle) {
+    if !keyboard::has_input_permission() {
+        let _ = app.emit("permission-required", "accessibility");
+    }
+}
+
+// Count down in the popup, shown without focus, so the user can take their
+// hands off the keyboard; emits `typing-countdown` with the milliseconds left
+// and hides the popup again at zero
+async fn run_typing_countdown(app: &AppHandle, state: &AppState, countdown_ms: u64) {
+    let near_cursor = *state.popup_near_cursor.lock().await;
+    show_popup_noninteractive(app, near_cursor);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(countdown_ms);
+    loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        let _ = app.emit("typing-countdown", remaining.as_millis() as u64);
+        if remaining.is_zero() {
+            break;
+        }
+        tokio::time::sleep(remaining.min(TYPING_COUNTDOWN_TICK)).await;
+    }
+
+    let _ = hide_popup(app);
+    wait_for_popup_hidden(app).await;
+}
+
+// Wait until the popup is hidden and unfocused so typing can't land in it
+async fn wait_for_popup_hidden(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+
+    let started = std::time::Instant::now();
+    while started.elapsed() < POPUP_HIDE_TIMEOUT {
+        let visible = window.is_visible().unwrap_or(false);
+        let focused = window.is_focused().unwrap_or(false);
+        if !visible && !focused {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    eprintln!(
+        "Popup still visible after {:?}; typing anyway",
+        POPUP_HIDE_TIMEOUT
+    );
+}
+
+// Replacements, redaction and normalization, everything done to a
+// dictation's own text before it's typed or copied
+async fn prepare_text(state: &AppState, text: &str) -> String {
+    let text = clean_text(state, text).await;
+    keyboard::normalize_text(&text, *state.text_normalization.lock().await)
+}
+
+// Replacements and redaction, the word-level part of `prepare_text`
+async fn clean_text(state: &AppState, text: &str) -> String {
+    let text = state.replacements.lock().await.apply(text.trim());
+    state.redaction.lock().await.apply(&text)
+}
+
+// The dictation typed just before, if it was recent enough that the next one
+// continues it
+fn continued_from(last_typed: &Option<(String, u64)>) -> Option<&str> {
+    last_typed
+        .as_ref()
+        .filter(|(_, at_ms)| now_millis().saturating_sub(*at_ms) < INSERT_CONTINUATION_MS)
+        .map(|(prev, _)| prev.as_str())
+}
+
+// The typing mode for `text`, which the direction setting may switch to a
+// paste for right-to-left text
+async fn type_mode_for(state: &AppState, text: &str) -> keyboard::TypeMode {
+    let mode = *state.type_mode.lock().await;
+    keyboard::mode_for_text(text, mode, *state.text_direction.lock().await)
+}
+
+// Type a dictation with the chosen insert style, remembering it as context
+// for the next one
+async fn type_dictation(state: &AppState, text: &str) -> Result<(), String> {
+    let text = prepare_text(state, text).await;
+    let style = *state.insert_style.lock().await;
+    let mut last_typed = state.last_typed.lock().await;
+    let text = keyboard::apply_insert_style(&text, style, continued_from(&last_typed));
+    // Marks are part of what's typed, so undo erases them too
+    let direction = *state.text_direction.lock().await;
+    let text = keyboard::add_direction_marks(&text, direction, true);
+
+    let mode = keyboard::mode_for_text(&text, *state.type_mode.lock().await, direction);
+    let typing = *state.typing_config.lock().await;
+    keyboard::type_text(&text, mode, typing)?;
+
+    *last_typed = Some((text, now_millis()));
+    Ok(())
+}
+
+// Show the next older recent transcription in the popup, wrapping back to the
+// newest, and type it once the history shortcut hasn't been pressed for a moment
+async fn cycle_history(app: AppHandle, state: AppState) {
+    let entries = state.history.lock().await.entries();
+    let count = entries.len().min(HISTORY_PICK_SIZE);
+    if count == 0 {
+        println!("No transcriptions to type again");
+        return;
+    }
+    let offset = {
+        let mut pick = state.history_pick.lock().await;
+        let offset = pick.map_or(0, |offset| (offset + 1) % count);
+        *pick = Some(offset);
+        offset
+    };
+    let seq = state.history_pick_seq.fetch_add(1, Ordering::SeqCst) + 1;
+    let text = entries[entries.len() - 1 - offset].text.clone();
+
+    let near_cursor = *state.popup_near_cursor.lock().await;
+    show_popup_noninteractive(&app, near_cursor);
+    let _ = app.emit(
+        "history-candidate",
+        HistoryCandidateEvent {
+            text: text.clone(),
+            position: offset + 1,
+            count,
+        },
+    );
+
+    tokio::time::sleep(HISTORY_PICK_CONFIRM_DELAY).await;
+    // Pressed again or cancelled in the meantime
+    if state.history_pick_seq.load(Ordering::SeqCst) != seq
+        || state.history_pick.lock().await.take().is_none()
+    {
+        return;
+    }
+    // A dictation started meanwhile owns the popup now
+    if state.is_recording.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let _ = app.emit("finish-and-type", ());
+    let _ = hide_popup(&app);
+    wait_for_popup_hidden(&app).await;
+    if let Err(e) = type_dictation(&state, &text).await {
+        eprintln!("Failed to type transcription from history: {}", e);
+        notify_if_permission_missing(&app);
+        let _ = app.emit("typing-error", e);
+    }
+}
+
+// Switch to the connected microphone after the selected one, or the first if
+// the selected one is unplugged, and flash its name in the popup through
+// `device-switched`. A dictation in progress keeps its microphone; the switch
+// applies from the next one.
+async fn cycle_input_device(app: AppHandle, state: AppState) {
+    let devices = match tokio::task::spawn_blocking(audio::list_input_devices).await {
+        Ok(Ok(devices)) => devices,
+        Ok(Err(e)) => {
+            eprintln!("Failed to list microphones: {}", e);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Microphone listing task failed: {}", e);
+            return;
+        }
+    };
+
+    let next = {
+        let mut selected = state.selected_input_device.lock().await;
+        // No selection means the system default, which is in the list too
+        let current = match selected.as_deref() {
+            Some(id) => devices.iter().position(|d| d.id == id),
+            None => devices.iter().position(|d| d.is_default),
+        };
+        let next = match current {
+            Some(index) => devices.get((index + 1) % devices.len()),
+            None => devices.first(),
+        };
+        *selected = next.map(|d| d.id.clone());
+        next
+    };
+    let name = match next {
+        Some(device) => device.name.clone(),
+        None => {
+            // Nothing to switch to; leave it to whatever the system picks
+            eprintln!("No microphones found; using the system default");
+            "Default".to_string()
+        }
+    };
+    println!("Switched microphone to {}", name);
+    if let Err(e) = save_settings(&app, &state).await {
+        eprintln!("Failed to save microphone choice: {}", e);
+    }
+
+    let seq = state.device_notice_seq.fetch_add(1, Ordering::SeqCst) + 1;
+    let recording = state.is_recording.load(Ordering::SeqCst);
+    if !recording {
+        let near_cursor = *state.popup_near_cursor.lock().await;
+        show_popup_noninteractive(&app, near_cursor);
+    }
+    let _ = app.emit("device-switched", name);
+    if recording {
+        return;
+    }
+
+    tokio::time::sleep(DEVICE_NOTICE_DURATION).await;
+    // Pressed again, or a dictation or history pick took over the popup
+    if state.device_notice_seq.load(Ordering::SeqCst) == seq
+        && !state.is_recording.load(Ordering::SeqCst)
+        && state.history_pick.lock().await.is_none()
+    {
+        let _ = hide_popup(&app);
+    }
+}
+
+// Stop any dictation and release the microphone before exiting, so the OS
+// doesn't keep showing it as in use. The session closes its SONIOX socket
+// itself once it sees recording stop.
+async fn shutdown(state: &AppState) {
+    println!("Shutting down");
+    state
+        .session_cancelled
+        .lock()
+        .await
+        .store(true, Ordering::Relaxed);
+    try_stop_recording(&state.is_recording);
+    state.history_pick_seq.fetch_add(1, Ordering::SeqCst);
+    if let Some(typer) = state.live_typer.lock().await.take() {
+        typer.abort();
+    }
+    if let Some(mut task) = state.session_task.lock().await.take() {
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut task)
+            .await
+            .is_err()
+        {
+            eprintln!("Dictation didn't stop in time; exiting anyway");
+            task.abort();
+        }
+    }
+    *state.warm_mic.lock().await = None;
+    // Give the capture thread time to see it's stopped and close the device
+    tokio::time::sleep(audio::CAPTURE_STOP_POLL * 2).await;
+}
+
+// Close the history picker without typing anything
+async fn cancel_history_pick(app: &AppHandle, state: &AppState) {
+    if state.history_pick.lock().await.take().is_none() {
+        return;
+    }
+    state.history_pick_seq.fetch_add(1, Ordering::SeqCst);
+    let _ = app.emit("finish-and-type", ());
+    let _ = hide_popup(app);
+}
+
+// Erase the last typed dictation, assuming the cursor is still right after it
+async fn undo_typing(state: &AppState) -> Result<(), String> {
+    let mut last_typed = state.last_typed.lock().await;
+    let Some((text, _)) = last_typed.as_ref() else {
+        return Err("Nothing to undo".to_string());
+    };
+    let count = text.chars().count();
+    let typing = *state.typing_config.lock().await;
+    keyboard::erase_chars(count, typing)?;
+
+    println!("Undid last dictation ({} chars)", count);
+    // Gone from the document, so it's no longer context for the next one
+    *last_typed = None;
+    Ok(())
+}
+
+// Stop recording and hide the popup without typing anything
+async fn discard_recording(app: AppHandle, state: AppState) {
+    // Tell the running session to drop its result before it sees recording stop
+    state
+        .session_cancelled
+        .lock()
+        .await
+        .store(true, Ordering::Relaxed);
+    // Retire its generation too, so nothing it still reports is taken as current
+    state.session_generation.fetch_add(1, Ordering::SeqCst);
+    if let Some(task) = state.session_task.lock().await.take() {
+        task.abort();
+    }
+    if let Some(typer) = state.live_typer.lock().await.take() {
+        typer.abort();
+    }
+    try_stop_recording(&state.is_recording);
+    *state.latest_transcription.lock().await = String::new();
+    state.last_start_ms.store(0, Ordering::Relaxed);
+
+    let _ = app.emit(
+        "recording-state",
+        RecordingStateEvent {
+            is_recording: false,
+        },
+    );
+    let _ = app.emit("finish-and-type", ());
+    let _ = hide_popup(&app);
+}
+
+// While push-to-talk is held, listen for the shortcut with the cancel modifier added
+async fn register_hold_cancel(app: &AppHandle, state: &AppState, base: Shortcut) {
+    let Some(extra) = state.hold_cancel_modifier.lock().await.modifiers() else {
+        return;
+    };
+    if base.mods.contains(extra) {
+        // Already part of the shortcut, so it can't signal anything
+        return;
+    }
+    let variant = Shortcut::new(Some(base.mods | extra), base.key);
+    if Some(variant) == *state.cancel_shortcut.lock().await
+        || Some(variant) == *state.stop_shortcut.lock().await
+    {
+        return;
+    }
+
+    let mut current = state.hold_cancel_shortcut.lock().await;
+    if current.is_some() {
+        return;
+    }
+    match app.global_shortcut().register(variant) {
+        Ok(()) => {
+            state
+                .hold_cancel_shortcut_id
+                .store(variant.id(), Ordering::Relaxed);
+            *current = Some(variant);
+        }
+        Err(e) => eprintln!("Failed to register hold-to-cancel shortcut: {}", e),
+    }
+}
+
+async fn unregister_hold_cancel(app: &AppHandle, state: &AppState) {
+    if let Some(variant) = state.hold_cancel_shortcut.lock().await.take() {
+        state.hold_cancel_shortcut_id.store(0, Ordering::Relaxed);
+        if let Err(e) = app.global_shortcut().unregister(variant) {
+            eprintln!("Failed to unregister hold-to-cancel shortcut: {}", e);
+        }
+    }
+}
+
+// Emergency stop: get back to idle whatever state things are in. Everything
+// that doesn't wait on a lock happens first, so a wedged session can't keep
+// the popup on screen.
+fn force_reset(app: &AppHandle, state: AppState) {
+    println!("Emergency stop");
+    // A running session sees it has been superseded and shuts down
+    state.session_generation.fetch_add(1, Ordering::SeqCst);
+    state.shortcut_is_down.store(false, Ordering::Relaxed);
+    state.last_start_ms.store(0, Ordering::Relaxed);
+    let _ = app.emit(
+        "recording-state",
+        RecordingStateEvent {
+            is_recording: false,
+        },
+    );
+    let _ = app.emit("still-listening", false);
+    let _ = app.emit("finish-and-type", ());
+    if let Err(e) = hide_popup(app) {
+        eprintln!("Emergency stop could not hide the popup: {}", e);
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        state
+            .session_cancelled
+            .lock()
+            .await
+            .store(true, Ordering::Relaxed);
+        // Dropping the session closes its connection and audio capture
+        if let Some(task) = state.session_task.lock().await.take() {
+            task.abort();
+        }
+        if let Some(typer) = state.live_typer.lock().await.take() {
+            typer.abort();
+        }
+        try_stop_recording(&state.is_recording);
+        state.latest_transcription.lock().await.clear();
+        state.accumulated_text.lock().await.clear();
+        state.latest_segments.lock().await.clear();
+        unregister_hold_cancel(&app, &state).await;
+
+        // Reopen a warm mic from scratch in case its stream was what got stuck
+        *state.warm_mic.lock().await = None;
+        if let Err(e) = refresh_warm_mic(&app, &state).await {
+            eprintln!("Could not keep the mic warm: {}", e);
+        }
+    });
+}
+
+// Global shortcut handler, shared by every registered shortcut
+fn handle_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    let state = app.state::<AppState>().inner().clone();
+
+    if shortcut.id() == panic_shortcut().id() {
+        if event.state == ShortcutState::Pressed {
+            force_reset(app, state);
+        }
+        return;
+    }
+
+    if shortcut.id() == state.cancel_shortcut_id.load(Ordering::Relaxed) {
+        if event.state == ShortcutState::Pressed {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if state.is_recording.load(Ordering::SeqCst) {
+                    println!("Cancel shortcut pressed, discarding recording");
+                    discard_recording(app, state).await;
+                } else {
+                    cancel_history_pick(&app, &state).await;
+                }
+            });
+        }
+        return;
+    }
+
+    // Act on release so the shortcut's modifiers don't turn Backspace into
+    // something else (Alt+Backspace, Ctrl+Backspace delete whole words)
+    if shortcut.id() == state.undo_shortcut_id.load(Ordering::Relaxed) {
+        if event.state == ShortcutState::Released {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = undo_typing(&state).await {
+                    eprintln!("Undo failed: {}", e);
+                    notify_if_permission_missing(&app);
+                }
+            });
+        }
+        return;
+    }
+
+    if shortcut.id() == state.stop_shortcut_id.load(Ordering::Relaxed) {
+        if event.state == ShortcutState::Pressed {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if state.is_recording.load(Ordering::SeqCst) {
+                    println!("Stop shortcut pressed, finishing dictation");
+                    finish_and_type(app, state).await;
+                }
+            });
+        }
+        return;
+    }
+
+    if shortcut.id() == state.history_shortcut_id.load(Ordering::Relaxed) {
+        if event.state == ShortcutState::Pressed {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if !state.is_recording.load(Ordering::SeqCst) {
+                    cycle_history(app, state).await;
+                }
+            });
+        }
+        return;
+    }
+
+    if shortcut.id() == state.device_shortcut_id.load(Ordering::Relaxed) {
+        if event.state == ShortcutState::Pressed {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                cycle_input_device(app, state).await;
+            });
+        }
+        return;
+    }
+
+    // Cancel modifier pressed mid-hold: abort instead of typing
+    if shortcut.id() == state.hold_cancel_shortcut_id.load(Ordering::Relaxed) {
+        if event.state == ShortcutState::Pressed {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                unregister_hold_cancel(&app, &state).await;
+                if state.is_recording.load(Ordering::SeqCst) {
+                    println!("Cancel modifier pressed during push-to-talk, discarding");
+                    discard_recording(app, state).await;
+                }
+            });
+        }
+        return;
+    }
+
+    if event.state == ShortcutState::Released {
+        state.shortcut_is_down.store(false, Ordering::Relaxed);
+
+        // Push-to-talk: releasing the shortcut ends the dictation
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if *state.recording_mode.lock().await != RecordingMode::PushToTalk {
+                return;
+            }
+            unregister_hold_cancel(&app, &state).await;
+            if !state.is_recording.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let held_ms = now_millis().saturating_sub(state.last_start_ms.load(Ordering::Relaxed));
+            if held_ms < *state.push_to_talk_min_ms.lock().await {
+                println!("Push-to-talk tap too short ({}ms), discarding", held_ms);
+                discard_recording(app, state).await;
+            } else {
+                finish_and_type(app, state).await;
+            }
+        });
+        return;
+    }
+
+    if event.state == ShortcutState::Pressed {
+        // Ignore auto-repeat while the shortcut is held down.
+        if state.shortcut_is_down.swap(true, Ordering::Relaxed) {
+            println!("Shortcut press ignored (key held)");
+            return;
+        }
+
+        // Debounce: ignore if less than 500ms since last press
+        let now = now_millis();
+        let last = state.last_shortcut_ms.load(Ordering::Relaxed);
+        if now.saturating_sub(last) < 500 {
+            println!("Shortcut debounced (too fast)");
+            return;
+        }
+        state.last_shortcut_ms.store(now, Ordering::Relaxed);
+
+        let app = app.clone();
+        let shortcut = *shortcut;
+        tauri::async_runtime::spawn(async move {
+            // Check recording state, not window visibility
+            let is_recording = state.is_recording.load(Ordering::SeqCst);
+            println!("Shortcut pressed, is_recording: {}", is_recording);
+
+            if is_recording {
+                // With a separate stop shortcut, pressing start again does nothing
+                if state.stop_shortcut_id.load(Ordering::Relaxed) != 0 {
+                    println!("Already recording; use the stop shortcut to finish");
+                    return;
+                }
+                // Any recording shortcut finishes, whichever profile started it
+                finish_and_type(app, state).await;
+            } else {
+                let profile = state
+                    .profiles
+                    .lock()
+                    .await
+                    .iter()
+                    .find(|(s, _)| s.id() == shortcut.id())
+                    .map(|(_, profile)| profile.clone());
+                if let Some(profile) = &profile {
+                    println!("Dictating with profile \"{}\"", profile.name);
+                }
+
+                begin_recording(app.clone(), state.clone(), profile.map(|p| p.options)).await;
+                let push_to_talk = *state.recording_mode.lock().await == RecordingMode::PushToTalk;
+                if push_to_talk && state.is_recording.load(Ordering::SeqCst) {
+                    register_hold_cancel(&app, &state, shortcut).await;
+                }
+            }
+        });
+    }
+}
+
+// Command to start recording
+#[tauri::command]
+async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    // Validate API key before switching to recording state.
+    let api_key = usable_api_key(&state).await?;
+    if microphone_blocked(&app) {
+        return Err("Microphone access is turned off for this app".to_string());
+    }
+
+    if !try_start_recording(&state.is_recording) {
+        return Ok(());
+    }
+    state.last_start_ms.store(now_millis(), Ordering::Relaxed);
+    remember_target_window(&state).await;
+
+    // Emit event to frontend
+    app.emit(
+        "recording-state",
+        RecordingStateEvent { is_recording: true },
+    )
+    .map_err(|e| e.to_string())?;
+    spawn_elapsed_timer(app.clone(), state.inner().clone());
+
+    // Start audio capture and streaming
+    spawn_transcription(app.clone(), state.inner().clone(), api_key, None, None).await;
+
+    Ok(())
+}
+
+// Command to stop recording. Returns true when the text is being held for
+// review, in which case the caller must not type it.
+#[tauri::command]
+async fn stop_recording(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    reason: Option<String>,
+) -> Result<bool, String> {
+    let reason = reason.unwrap_or_else(|| "unknown".to_string());
+    println!("stop_recording invoked (reason={})", reason);
+    if !try_stop_recording(&state.is_recording) {
+        println!("stop_recording ignored; already stopped");
+        return Ok(false);
+    }
+
+    app.emit(
+        "recording-state",
+        RecordingStateEvent {
+            is_recording: false,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    // In review mode the caller must not type; the popup shows the text instead
+    if *state.finish_mode.lock().await == FinishMode::ConfirmBeforeType {
+        let text = collect_session_text(&state).await;
+        if !text.trim().is_empty() {
+            show_review(&app, &state, text).await;
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+// Command to type a reviewed (and possibly edited) transcription
+#[tauri::command]
+async fn confirm_and_type(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    text: String,
+) -> Result<(), String> {
+    println!("confirm_and_type invoked ({} chars)", text.chars().count());
+    // Typed now, so the next dictation starts fresh
+    state.accumulated_text.lock().await.clear();
+    hide_and_type(app, state.inner().clone(), text).await;
+    Ok(())
+}
+
+// Command to force stop and hide popup immediately (used by Cancel/Escape).
+#[tauri::command]
+async fn cancel_and_hide(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let reason = reason.unwrap_or_else(|| "ui:force-cancel".to_string());
+    println!("cancel_and_hide invoked (reason={})", reason);
+
+    discard_recording(app, state.inner().clone()).await;
+    Ok(())
+}
+
+// Command to type text at cursor
+#[tauri::command]
+async fn type_text(app: AppHandle, state: State<'_, AppState>, text: String) -> Result<(), String> {
+    println!("type_text called ({} chars)", text.chars().count());
+    let mode = type_mode_for(&state, &text).await;
+    let typing = *state.typing_config.lock().await;
+    let result = keyboard::type_text(&text, mode, typing).map_err(|e| e.to_string());
+    match &result {
+        Ok(_) => println!("type_text succeeded"),
+        Err(e) => {
+            println!("type_text failed: {}", e);
+            notify_if_permission_missing(&app);
+        }
+    }
+    result
+}
+
+// Command to toggle the check that a text field is focused before typing
+#[tauri::command]
+async fn set_require_text_field(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.require_text_field.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to send every dictation to one window, found by title and/or
+// process, instead of whatever has focus (None unlocks). Windows only.
+#[tauri::command]
+async fn set_target_window(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    target: Option<keyboard::TargetWindow>,
+) -> Result<(), String> {
+    *state.target_lock.lock().await = target.map(keyboard::TargetWindow::validated).transpose()?;
+    save_settings(&app, &state).await
+}
+
+// Command to check whether the app may simulate typing (macOS Accessibility;
+// always true elsewhere)
+#[tauri::command]
+async fn check_accessibility_permission() -> Result<bool, String> {
+    Ok(keyboard::has_input_permission())
+}
+
+// Command to open the macOS Accessibility settings so the user can grant access
+#[tauri::command]
+async fn open_accessibility_settings() -> Result<(), String> {
+    keyboard::open_accessibility_settings()
+}
+
+// Recording with microphone access denied captures only silence, so tell the
+// UI to show how to allow it instead (macOS)
+fn microphone_blocked(app: &AppHandle) -> bool {
+    let blocked = matches!(
+        audio::microphone_permission(),
+        audio::PermissionStatus::Denied | audio::PermissionStatus::Restricted
+    );
+    if blocked {
+        println!("Microphone access denied; not recording");
+        let _ = app.emit("permission-required", "microphone");
+    }
+    blocked
+}
+
+// Command to check microphone access (always granted outside macOS). Asks for
+// it if the user was never asked; a denial emits `permission-required`.
+#[tauri::command]
+async fn check_microphone_permission(app: AppHandle) -> Result<audio::PermissionStatus, String> {
+    let status = audio::microphone_permission();
+    if status == audio::PermissionStatus::NotDetermined {
+        tokio::task::spawn_blocking(audio::request_microphone_permission)
+            .await
+            .map_err(|e| e.to_string())??;
+    } else {
+        microphone_blocked(&app);
+    }
+    Ok(status)
+}
+
+// Command to open the macOS Microphone privacy settings
+#[tauri::command]
+async fn open_microphone_settings() -> Result<(), String> {
+    audio::open_microphone_settings()
+}
+
+// Command to show the config directory (where settings.json lives) in the
+// file manager, e.g. to back up settings or attach them to a bug report
+#[tauri::command]
+async fn open_config_dir(app: AppHandle) -> Result<(), String> {
+    let dir = settings::config_dir(&app)?;
+    // Nothing is saved until a setting changes, so it may not exist yet
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let opener = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(opener)
+        .arg(&dir)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to open {}: {}", dir.display(), e))
+}
+
+#[derive(Clone, Serialize)]
+struct TestTypingEvent {
+    success: bool,
+    error: Option<String>,
+}
+
+// Command to type a sample phrase after a countdown, so users can check that
+// typing reaches their editor before dictating
+#[tauri::command]
+async fn test_typing(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    sample: Option<String>,
+) -> Result<(), String> {
+    let sample = sample
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| TEST_TYPING_SAMPLE.to_string());
+
+    // Time for the user to focus the target app
+    tokio::time::sleep(TEST_TYPING_COUNTDOWN).await;
+
+    // Typed the way a dictation of the same text would be
+    let direction = *state.text_direction.lock().await;
+    let sample = keyboard::add_direction_marks(&sample, direction, true);
+    let mode = type_mode_for(&state, &sample).await;
+    let typing = *state.typing_config.lock().await;
+    let result = tokio::task::spawn_blocking(move || keyboard::type_text(&sample, mode, typing))
+        .await
+        .map_err(|e| format!("Test typing task failed: {}", e))
+        .and_then(|result| result);
+    if result.is_err() {
+        notify_if_permission_missing(&app);
+    }
+    let _ = app.emit(
+        "test-typing-complete",
+        TestTypingEvent {
+            success: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        },
+    );
+    result
+}
+
+// Command to set API key (persisted so it survives restarts)
+#[tauri::command]
+async fn set_api_key(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    api_key: String,
+) -> Result<(), String> {
+    let api_key = api_key.trim().to_string();
+    settings::update(&app, |s| s.soniox_api_key = api_key.clone()).await?;
+    *state.soniox_api_key.lock().await = api_key;
+    state.api_key_rejected.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+// Command to set the OpenAI API key used by the OpenAI backend
+#[tauri::command]
+async fn set_openai_api_key(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    api_key: String,
+) -> Result<(), String> {
+    let api_key = api_key.trim().to_string();
+    settings::update(&app, |s| s.openai_api_key = api_key.clone()).await?;
+    *state.openai_api_key.lock().await = api_key;
+    Ok(())
+}
+
+/// What this build and platform support, so the settings UI can hide the rest
+#[derive(Clone, Serialize)]
+struct Capabilities {
+    version: String,
+    /// `windows`, `macos` or `linux`
+    os: &'static str,
+    /// Backends compiled into this build
+    backends: Vec<backend::BackendKind>,
+    /// Typing needs the Accessibility permission granted (macOS)
+    accessibility_permission: bool,
+    /// Recording needs microphone access granted to the app (macOS)
+    microphone_permission: bool,
+    autostart: bool,
+    /// Typing can be held back when no text field is focused
+    text_field_detection: bool,
+    /// Focus is handed back to the target window before typing
+    focus_restore: bool,
+    /// Dictation can be locked to one window with `set_target_window`
+    target_lock: bool,
+    /// The `unicode` type mode inserts text natively instead of pasting
+    unicode_input: bool,
+}
+
+// Command to describe what this build supports
+#[tauri::command]
+async fn get_capabilities(app: AppHandle) -> Result<Capabilities, String> {
+    Ok(Capabilities {
+        version: app.package_info().version.to_string(),
+        os: std::env::consts::OS,
+        backends: backend::BackendKind::ALL
+            .into_iter()
+            .filter(|kind| kind.is_available())
+            .collect(),
+        accessibility_permission: cfg!(target_os = "macos"),
+        microphone_permission: cfg!(target_os = "macos"),
+        autostart: cfg!(any(
+            target_os = "windows",
+            target_os = "macos",
+            target_os = "linux"
+        )),
+        text_field_detection: cfg!(target_os = "windows"),
+        focus_restore: cfg!(target_os = "windows"),
+        target_lock: cfg!(target_os = "windows"),
+        unicode_input: cfg!(any(target_os = "windows", target_os = "macos")),
+    })
+}
+
+// Command to get how much audio has been sent for transcription
+#[tauri::command]
+async fn get_usage_stats(state: State<'_, AppState>) -> Result<backend::UsageStats, String> {
+    Ok(state.usage.stats())
+}
+
+// Command to zero the usage counters (e.g. at the start of a billing period)
+#[tauri::command]
+async fn reset_usage_stats(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    state.usage.reset();
+    settings::update(&app, |s| s.usage_lifetime_seconds = 0.0).await
+}
+
+// Command to check an API key against SONIOX without storing it
+#[tauri::command]
+async fn validate_api_key(state: State<'_, AppState>, key: String) -> Result<bool, String> {
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return Ok(false);
+    }
+    let endpoint_url = state
+        .endpoint_url
+        .lock()
+        .await
+        .clone()
+        .unwrap_or_else(|| soniox::SONIOX_WS_URL.to_string());
+    let options = state.transcription_options.lock().await.clone();
+    soniox::check_api_key(&endpoint_url, &key, &options)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Command to point at a different SONIOX host (None or empty resets to the default)
+#[tauri::command]
+async fn set_endpoint(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    url: Option<String>,
+) -> Result<(), String> {
+    let url = match url.filter(|u| !u.trim().is_empty()) {
+        Some(url) => Some(soniox::validate_endpoint(&url)?),
+        None => None,
+    };
+    settings::update(&app, |s| s.endpoint_url = url.clone()).await?;
+    *state.endpoint_url.lock().await = url;
+    Ok(())
+}
+
+// Command to check whether an API key is configured
+#[tauri::command]
+async fn has_api_key(state: State<'_, AppState>) -> Result<bool, String> {
+    // A rejected key is as good as none
+    Ok(!state.soniox_api_key.lock().await.is_empty()
+        && !state.api_key_rejected.load(Ordering::Relaxed))
+}
+
+// Command to keep adding dictations to the same text until it's typed or cleared
+#[tauri::command]
+async fn set_append_mode(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.append_mode.lock().await = enabled;
+    if !enabled {
+        state.accumulated_text.lock().await.clear();
+    }
+    save_settings(&app, &state).await
+}
+
+// Command to type finalized words into the focused app while still recording
+// (SONIOX only). Review before typing doesn't apply to live dictations.
+#[tauri::command]
+async fn set_live_typing(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.live_typing.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to drop the text gathered in append mode and start over
+#[tauri::command]
+async fn clear_transcription(state: State<'_, AppState>) -> Result<(), String> {
+    state.accumulated_text.lock().await.clear();
+    state.latest_transcription.lock().await.clear();
+    Ok(())
+}
+
+// Command to transcribe an audio file (WAV, MP3, FLAC, ...) with the selected
+// backend. The file is streamed through a normal session, so the usual
+// transcription events go out; returns the text and also types it if asked.
+#[tauri::command]
+async fn transcribe_file(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    type_result: Option<bool>,
+) -> Result<String, String> {
+    let api_key = usable_api_key(&state).await?;
+    let path = std::path::PathBuf::from(path.trim());
+    let file = tokio::task::spawn_blocking(move || audiofile::decode(&path))
+        .await
+        .map_err(|e| e.to_string())??;
+    let file = Arc::new(file);
+    println!(
+        "Transcribing {:.1}s of audio from a file",
+        file.duration_secs()
+    );
+
+    if !try_start_recording(&state.is_recording) {
+        return Err("Finish the current dictation first".to_string());
+    }
+    remember_target_window(&state).await;
+    let _ = app.emit(
+        "recording-state",
+        RecordingStateEvent { is_recording: true },
+    );
+    spawn_transcription(
+        app.clone(),
+        state.inner().clone(),
+        api_key,
+        None,
+        Some(file.clone()),
+    )
+    .await;
+
+    // The session records until told to stop; stop it once the file has played.
+    // Recording ending sooner means it was cancelled or failed.
+    while !file.is_finished() {
+        if !state.is_recording.load(Ordering::SeqCst) {
+            return Err("Transcription stopped before the end of the file".to_string());
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    try_stop_recording(&state.is_recording);
+    let _ = app.emit(
+        "recording-state",
+        RecordingStateEvent {
+            is_recording: false,
+        },
+    );
+
+    let text = collect_session_text(&state).await;
+    if type_result.unwrap_or(false) {
+        hide_and_type(app, state.inner().clone(), text.clone()).await;
+    }
+    Ok(text)
+}
+
+// Command to get recording state
+#[tauri::command]
+async fn get_recording_state(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.is_recording.load(Ordering::SeqCst))
+}
+
+// Command to list available microphones
+#[tauri::command]
+async fn list_input_devices() -> Result<Vec<audio::AudioDeviceInfo>, String> {
+    // cpal device enumeration can block on some backends.
+    tokio::task::spawn_blocking(audio::list_input_devices)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Command to choose the microphone used for recording (None = system default)
+#[tauri::command]
+async fn set_input_device(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    device_id: Option<String>,
+) -> Result<(), String> {
+    let device_id = device_id.filter(|id| !id.trim().is_empty());
+    *state.selected_input_device.lock().await = device_id;
+    save_settings(&app, &state).await
+}
+
+// Command to choose how multi-channel microphones are mixed down to mono
+#[tauri::command]
+async fn set_channel_strategy(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    strategy: audio::ChannelStrategy,
+) -> Result<(), String> {
+    *state.channel_strategy.lock().await = strategy;
+    save_settings(&app, &state).await
+}
+
+// Command to boost or cut the microphone level, in dB
+#[tauri::command]
+async fn set_input_gain(app: AppHandle, state: State<'_, AppState>, db: f32) -> Result<(), String> {
+    if !(MIN_INPUT_GAIN_DB..=MAX_INPUT_GAIN_DB).contains(&db) {
+        return Err(format!(
+            "Input gain must be between {} and {} dB",
+            MIN_INPUT_GAIN_DB, MAX_INPUT_GAIN_DB
+        ));
+    }
+    *state.input_gain_db.lock().await = db;
+    save_settings(&app, &state).await
+}
+
+// Command to choose the sample rate streamed to SONIOX (applies to the next recording)
+#[tauri::command]
+async fn set_sample_rate(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    rate: u32,
+) -> Result<(), String> {
+    *state.sample_rate.lock().await = soniox::validate_sample_rate(rate)?;
+    save_settings(&app, &state).await
+}
+
+// Command to set how long speech may go without new text before the popup
+// shows it's still listening (0 to turn the notice off)
+#[tauri::command]
+async fn set_stall_notice(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ms: u64,
+) -> Result<(), String> {
+    if ms > soniox::MAX_STALL_NOTICE_MS {
+        return Err(format!(
+            "Stall notice must be at most {}ms",
+            soniox::MAX_STALL_NOTICE_MS
+        ));
+    }
+    *state.stall_notice_ms.lock().await = ms;
+    save_settings(&app, &state).await
+}
+
+// Command to set how long revised live words must settle before the popup
+// shows them, so fast speech doesn't make the text jump around (0 to show
+// every revision)
+#[tauri::command]
+async fn set_display_smoothing(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ms: u64,
+) -> Result<(), String> {
+    if ms > soniox::MAX_DISPLAY_SMOOTHING_MS {
+        return Err(format!(
+            "Display smoothing must be at most {}ms",
+            soniox::MAX_DISPLAY_SMOOTHING_MS
+        ));
+    }
+    *state.display_smoothing_ms.lock().await = ms;
+    save_settings(&app, &state).await
+}
+
+// Command to set how long the SONIOX connection may go without sending
+// anything before a keepalive goes out (0 to never send one)
+#[tauri::command]
+async fn set_keepalive_interval(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    secs: u64,
+) -> Result<(), String> {
+    if secs > soniox::MAX_KEEPALIVE_SECS {
+        return Err(format!(
+            "Keepalive interval must be at most {}s",
+            soniox::MAX_KEEPALIVE_SECS
+        ));
+    }
+    *state.keepalive_secs.lock().await = secs;
+    save_settings(&app, &state).await
+}
+
+// Command to choose 16-bit or float samples for streaming to SONIOX
+#[tauri::command]
+async fn set_audio_format(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    format: audio::AudioFormat,
+) -> Result<(), String> {
+    *state.audio_format.lock().await = format;
+    save_settings(&app, &state).await
+}
+
+// Command to set the silence sent to SONIOX before the audio (0 to skip it)
+#[tauri::command]
+async fn set_priming_silence(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ms: u64,
+) -> Result<(), String> {
+    if ms > soniox::MAX_PRIMING_MS {
+        return Err(format!(
+            "Priming silence must be at most {}ms",
+            soniox::MAX_PRIMING_MS
+        ));
+    }
+    *state.priming_ms.lock().await = ms;
+    save_settings(&app, &state).await
+}
+
+// Command to drop stray leading text SONIOX sometimes hears in the priming silence
+#[tauri::command]
+async fn set_trim_priming_artifacts(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.trim_priming_artifacts.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to keep the microphone open between dictations (holds the mic-in-use indicator on)
+#[tauri::command]
+async fn set_warm_mic(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.warm_mic_enabled.lock().await = enabled;
+    refresh_warm_mic(&app, &state)
+        .await
+        .map_err(|e| format!("Could not keep the mic warm: {}", e))?;
+    save_settings(&app, &state).await
+}
+
+// Command to turn automatic input level normalization on or off
+#[tauri::command]
+async fn set_auto_gain(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.auto_gain.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to toggle dithering when audio is quantized to 16 bits (helps quiet input)
+#[tauri::command]
+async fn set_dither(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.dither.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to pick the resampler preset: fast for low latency on weak CPUs,
+// high quality for accuracy-critical dictation
+#[tauri::command]
+async fn set_resample_quality(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    quality: audio::ResampleQuality,
+) -> Result<(), String> {
+    *state.resample_quality.lock().await = quality;
+    save_settings(&app, &state).await
+}
+
+// Command to switch between toggle and push-to-talk shortcut behavior
+#[tauri::command]
+async fn set_recording_mode(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mode: RecordingMode,
+) -> Result<(), String> {
+    *state.recording_mode.lock().await = mode;
+    save_settings(&app, &state).await
+}
+
+// Command to set how short a push-to-talk hold is discarded as a tap (0 = never)
+#[tauri::command]
+async fn set_push_to_talk_min_hold(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ms: u64,
+) -> Result<(), String> {
+    if ms > MAX_PUSH_TO_TALK_MIN_MS {
+        return Err(format!(
+            "Tap threshold must be at most {}ms so short dictations still get typed",
+            MAX_PUSH_TO_TALK_MIN_MS
+        ));
+    }
+    *state.push_to_talk_min_ms.lock().await = ms;
+    save_settings(&app, &state).await
+}
+
+// Command to choose the modifier that discards a push-to-talk dictation mid-hold
+#[tauri::command]
+async fn set_hold_cancel_modifier(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    modifier: HoldCancelModifier,
+) -> Result<(), String> {
+    *state.hold_cancel_modifier.lock().await = modifier;
+    save_settings(&app, &state).await
+}
+
+// Command to choose whether a dictation is typed right away or reviewed first
+#[tauri::command]
+async fn set_finish_mode(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mode: FinishMode,
+) -> Result<(), String> {
+    *state.finish_mode.lock().await = mode;
+    save_settings(&app, &state).await
+}
+
+// Command to choose how an empty dictation is reported
+#[tauri::command]
+async fn set_no_speech_notice(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    notice: NoSpeechNotice,
+) -> Result<(), String> {
+    *state.no_speech_notice.lock().await = notice;
+    save_settings(&app, &state).await
+}
+
+// Command to choose between keystroke typing and clipboard paste
+#[tauri::command]
+async fn set_type_mode(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mode: keyboard::TypeMode,
+) -> Result<(), String> {
+    *state.type_mode.lock().await = mode;
+    save_settings(&app, &state).await
+}
+
+// Command to choose how right-to-left dictations are typed: pasted instead of
+// typed key by key, optionally with direction marks, or like any other text
+#[tauri::command]
+async fn set_text_direction(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    direction: keyboard::TextDirection,
+) -> Result<(), String> {
+    *state.text_direction.lock().await = direction;
+    save_settings(&app, &state).await
+}
+
+// Command to change how long to wait after the popup hides before typing
+#[tauri::command]
+async fn set_type_delay(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    delay_ms: u64,
+) -> Result<(), String> {
+    if delay_ms > MAX_TYPING_DELAY_MS {
+        return Err(format!(
+            "Typing delays must be at most {} ms",
+            MAX_TYPING_DELAY_MS
+        ));
+    }
+    *state.type_delay_ms.lock().await = delay_ms;
+    save_settings(&app, &state).await
+}
+
+// Command to set the countdown shown before a dictation is typed (0 to type right away)
+#[tauri::command]
+async fn set_typing_countdown(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ms: u64,
+) -> Result<(), String> {
+    if ms > MAX_TYPING_COUNTDOWN_MS {
+        return Err(format!(
+            "Typing countdown must be at most {}ms",
+            MAX_TYPING_COUNTDOWN_MS
+        ));
+    }
+    *state.typing_countdown_ms.lock().await = ms;
+    save_settings(&app, &state).await
+}
+
+// Command to choose how dictations are spaced and capitalized when typed
+#[tauri::command]
+async fn set_insert_style(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    style: keyboard::InsertStyle,
+) -> Result<(), String> {
+    *state.insert_style.lock().await = style;
+    save_settings(&app, &state).await
+}
+
+// Command to set the word replacements applied before typing (persisted)
+#[tauri::command]
+async fn set_replacements(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    config: replacements::ReplacementConfig,
+) -> Result<(), String> {
+    let config = config.validated();
+    settings::update(&app, |s| s.replacements = config.clone()).await?;
+    *state.replacements.lock().await = config;
+    Ok(())
+}
+
+// Command to set the words masked or removed from transcriptions (persisted)
+#[tauri::command]
+async fn set_redaction(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    config: replacements::RedactionConfig,
+) -> Result<(), String> {
+    let config = config.validated();
+    settings::update(&app, |s| s.redaction = config.clone()).await?;
+    *state.redaction.lock().await = config;
+    Ok(())
+}
+
+// Command to choose how dictations are cleaned up (spaces, lines, capitals) before typing
+#[tauri::command]
+async fn set_text_normalization(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    options: keyboard::TextNormalization,
+) -> Result<(), String> {
+    *state.text_normalization.lock().await = options;
+    save_settings(&app, &state).await
+}
+
+// Command to slow down keystroke typing for apps that drop fast input
+#[tauri::command]
+async fn set_typing_config(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    config: keyboard::TypingConfig,
+) -> Result<(), String> {
+    if config.pre_delay_ms > MAX_TYPING_DELAY_MS || config.char_delay_ms > MAX_TYPING_DELAY_MS {
+        return Err(format!(
+            "Typing delays must be at most {} ms",
+            MAX_TYPING_DELAY_MS
+        ));
+    }
+    *state.typing_config.lock().await = config;
+    save_settings(&app, &state).await
+}
+
+// Command to set the SONIOX model and language hints for new recordings
+#[tauri::command]
+async fn set_transcription_options(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    options: soniox::TranscriptionOptions,
+) -> Result<(), String> {
+    *state.transcription_options.lock().await = options.validated()?;
+    save_settings(&app, &state).await
+}
+
+// Command to type a translation into `target` instead of the spoken text
+// (None turns translation off). SONIOX only.
+#[tauri::command]
+async fn set_translation(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    source: Option<String>,
+    target: Option<String>,
+) -> Result<(), String> {
+    let mut options = state.transcription_options.lock().await.clone();
+    options.translation = target.map(|target| soniox::Translation { source, target });
+    *state.transcription_options.lock().await = options.validated()?;
+    save_settings(&app, &state).await
+}
+
+// Command to list recent transcriptions (oldest first)
+#[tauri::command]
+async fn get_history(state: State<'_, AppState>) -> Result<Vec<history::HistoryEntry>, String> {
+    Ok(state.history.lock().await.entries())
+}
+
+// Command to type a past transcription again
+#[tauri::command]
+async fn retype_entry(state: State<'_, AppState>, id: u64) -> Result<(), String> {
+    let text = state
+        .history
+        .lock()
+        .await
+        .get(id)
+        .map(|entry| entry.text.clone())
+        .ok_or_else(|| format!("No history entry with id {}", id))?;
+    type_dictation(&state, &text).await
+}
+
+// Command to save the current transcription, or the last finished one, to a
+// file. Subtitle formats need the word timings of a finished transcription.
+#[tauri::command]
+async fn export_transcription(
+    state: State<'_, AppState>,
+    path: String,
+    format: export::ExportFormat,
+) -> Result<(), String> {
+    let segments = state.latest_segments.lock().await.clone();
+    if format.is_timed() && segments.is_empty() {
+        return Err("No word timings for the last transcription; export it as text instead".into());
+    }
+
+    let current = state.latest_transcription.lock().await.clone();
+    let text = if !current.trim().is_empty() {
+        current
+    } else if let Some(entry) = state.history.lock().await.last() {
+        entry.text.clone()
+    } else {
+        // History may be turned off; the timings still hold the words
+        segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    if text.trim().is_empty() {
+        return Err("There's no transcription to export yet".into());
+    }
+
+    export::write(std::path::Path::new(&path), format, &text, &segments)
+}
+
+// Command to open the editor window for long-form dictation, or bring it to
+// the front. While it's open, finished dictations are added to its text
+// instead of being typed; the text can then be typed or exported as a whole.
+#[tauri::command]
+async fn open_editor(app: AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(EDITOR_WINDOW) {
+        window.show().map_err(|e| e.to_string())?;
+        return window.set_focus().map_err(|e| e.to_string());
+    }
+    WebviewWindowBuilder::new(
+        &app,
+        EDITOR_WINDOW,
+        WebviewUrl::App("index.html?window=editor".into()),
+    )
+    .title("محرر الإملاء")
+    .inner_size(720.0, 540.0)
+    .min_inner_size(420.0, 300.0)
+    .resizable(true)
+    .center()
+    .build()
+    .map(|_| ())
+    .map_err(|e| format!("Failed to open the editor: {}", e))
+}
+
+// Command to get the editor's text, e.g. when its window (re)loads
+#[tauri::command]
+async fn get_editor_text(state: State<'_, AppState>) -> Result<String, String> {
+    Ok(state.editor_text.lock().await.clone())
+}
+
+// Command to store the user's edits to the editor's text
+#[tauri::command]
+async fn set_editor_text(state: State<'_, AppState>, text: String) -> Result<(), String> {
+    *state.editor_text.lock().await = text;
+    Ok(())
+}
+
+// Command to hide the editor and type its text into the app that gets focus
+// back. The text is kept, so it can still be exported afterwards.
+#[tauri::command]
+async fn type_editor_text(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let text = state.editor_text.lock().await.clone();
+    if text.trim().is_empty() {
+        return Err("The editor is empty".into());
+    }
+    if let Some(window) = app.get_webview_window(EDITOR_WINDOW) {
+        window.hide().map_err(|e| e.to_string())?;
+    }
+    hide_and_type(app, state.inner().clone(), text).await;
+    Ok(())
+}
+
+// Command to save the editor's text to a file. Edits have no word timings,
+// so it's always plain text.
+#[tauri::command]
+async fn export_editor_text(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    let text = state.editor_text.lock().await.clone();
+    if text.trim().is_empty() {
+        return Err("The editor is empty".into());
+    }
+    export::write(
+        std::path::Path::new(&path),
+        export::ExportFormat::Text,
+        &text,
+        &[],
+    )
+}
+
+// Command to forget all past transcriptions
+#[tauri::command]
+async fn clear_history(state: State<'_, AppState>) -> Result<(), String> {
+    state.history.lock().await.clear();
+    Ok(())
+}
+
+// Command to change how many transcriptions are kept
+#[tauri::command]
+async fn set_history_limit(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    limit: usize,
+) -> Result<(), String> {
+    state.history.lock().await.set_limit(limit);
+    save_settings(&app, &state).await
+}
+
+// Command to configure stopping after trailing silence
+#[tauri::command]
+async fn set_auto_stop(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+    silence_ms: u64,
+    threshold: Option<f32>,
+) -> Result<(), String> {
+    if enabled && silence_ms == 0 {
+        return Err("Silence duration must be greater than zero".to_string());
+    }
+
+    if threshold.is_some_and(|t| !(0.0..=1.0).contains(&t)) {
+        return Err("Silence threshold must be between 0.0 and 1.0".to_string());
+    }
+
+    let mut auto_stop = state.auto_stop.lock().await;
+    auto_stop.enabled = enabled;
+    auto_stop.silence_ms = silence_ms;
+    if let Some(threshold) = threshold {
+        auto_stop.threshold = threshold;
+    }
+    drop(auto_stop);
+    save_settings(&app, &state).await
+}
+
+// Command to start a new sentence on its own line wherever the speaker pauses
+#[tauri::command]
+async fn set_auto_punctuation_breaks(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+    pause_ms: u64,
+) -> Result<(), String> {
+    if enabled && pause_ms == 0 {
+        return Err("Pause length must be greater than zero".to_string());
+    }
+
+    *state.pause_breaks.lock().await = soniox::PauseBreakConfig { enabled, pause_ms };
+    save_settings(&app, &state).await
+}
+
+// Command to cap recording length in seconds (0 = unlimited)
+#[tauri::command]
+async fn set_max_duration(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    seconds: u64,
+) -> Result<(), String> {
+    *state.max_duration_secs.lock().await = seconds;
+    save_settings(&app, &state).await
+}
+
+// Command to choose the transcription backend
+#[tauri::command]
+async fn set_backend(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    backend: backend::BackendKind,
+) -> Result<(), String> {
+    if !backend.is_available() {
+        return Err("This transcription backend is not included in this build".to_string());
+    }
+    *state.backend.lock().await = backend;
+    save_settings(&app, &state).await
+}
+
+// Command to set the ggml model file used by the local Whisper backend
+#[tauri::command]
+async fn set_whisper_model(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: Option<String>,
+) -> Result<(), String> {
+    let path = path.filter(|p| !p.trim().is_empty());
+    if let Some(path) = &path {
+        if !std::path::Path::new(path).is_file() {
+            return Err(format!("Whisper model not found: {}", path));
+        }
+    }
+    *state.whisper_model_path.lock().await = path;
+    save_settings(&app, &state).await
+}
+
+// Command to toggle showing the popup next to the mouse cursor
+#[tauri::command]
+async fn set_popup_near_cursor(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.popup_near_cursor.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to keep the popup above other windows, e.g. to watch the live
+// transcript, or let other windows cover it
+#[tauri::command]
+async fn set_popup_always_on_top(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.popup_always_on_top.lock().await = enabled;
+    apply_popup_on_top(&app, enabled);
+    save_settings(&app, &state).await
+}
+
+// Command to toggle the start/stop sound cues
+#[tauri::command]
+async fn set_audio_cues(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.audio_cues.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to list speakers and headphones cues can play on
+#[tauri::command]
+async fn list_output_devices() -> Result<Vec<audio::AudioDeviceInfo>, String> {
+    tokio::task::spawn_blocking(audio::list_output_devices)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Command to choose where cues play (None = system default) and their volume (0.0 - 1.0)
+#[tauri::command]
+async fn set_cue_output(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    device_id: Option<String>,
+    volume: f32,
+) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&volume) {
+        return Err("Cue volume must be between 0.0 and 1.0".to_string());
+    }
+
+    *state.cue_output.lock().await = cues::CueOutput {
+        device: device_id.filter(|id| !id.trim().is_empty()),
+        volume,
+    };
+    save_settings(&app, &state).await
+}
+
+// Command to save each dictation's audio as a WAV file for debugging
+#[tauri::command]
+async fn set_save_recordings(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.save_recordings.lock().await = enabled;
+    save_settings(&app, &state).await
+}
+
+// Command to choose where recordings are saved (None for the app data folder)
+#[tauri::command]
+async fn set_recordings_dir(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: Option<String>,
+) -> Result<(), String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(dir) = &path {
+        if std::path::Path::new(dir).is_file() {
+            return Err(format!("\"{}\" is a file, not a folder", dir));
+        }
+    }
+    *state.recordings_dir.lock().await = path;
+    save_settings(&app, &state).await
+}
+
+// Command to get every saved setting, with preferences as currently in effect
+#[tauri::command]
+async fn get_settings(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<settings::Settings, String> {
+    let mut saved = settings::load(&app);
+    saved.preferences = current_preferences(&state).await;
+    Ok(saved)
+}
+
+// Command to replace all settings at once, e.g. when importing a config.
+// Everything is checked before anything changes, and the file is written
+// once; the usage total is kept.
+#[tauri::command]
+async fn update_settings(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    settings: settings::Settings,
+) -> Result<(), String> {
+    let shortcuts = parse_shortcuts(&settings)?;
+    let soniox_api_key = settings.soniox_api_key.trim().to_string();
+    let openai_api_key = settings.openai_api_key.trim().to_string();
+    let endpoint_url = match settings.endpoint_url.filter(|u| !u.trim().is_empty()) {
+        Some(url) => Some(soniox::validate_endpoint(&url)?),
+        None => None,
+    };
+    let replacements = settings.replacements.validated();
+    let redaction = settings.redaction.validated();
+    let preferences = validated_preferences(settings.preferences)?;
+
+    // Registering, the login item and the save can still fail, so each undoes
+    // what came before it
+    let old_shortcuts = registered_shortcuts(&state).await;
+    let added = register_shortcuts(&app, &old_shortcuts, &shortcuts)?;
+    let release_added = |added: Vec<Shortcut>| {
+        for shortcut in added {
+            let _ = app.global_shortcut().unregister(shortcut);
+        }
+    };
+    let was_autostart = app.autolaunch().is_enabled().unwrap_or(false);
+    if let Err(e) = apply_autostart(&app, settings.autostart) {
+        release_added(added);
+        return Err(e);
+    }
+
+    // Save before switching over, so a failed save leaves the app as it was
+    let profiles: Vec<settings::ShortcutProfile> = shortcuts
+        .profiles
+        .iter()
+        .map(|(_, profile)| profile.clone())
+        .collect();
+    // Written whole rather than updated, so this also recovers from a
+    // settings file that can't be read
+    let saved = settings::replace(
+        &app,
+        &settings::Settings {
+            soniox_api_key: soniox_api_key.clone(),
+            openai_api_key: openai_api_key.clone(),
+            shortcut: settings.shortcut.trim().to_string(),
+            cancel_shortcut: settings.cancel_shortcut.trim().to_string(),
+            undo_shortcut: settings.undo_shortcut.trim().to_string(),
+            stop_shortcut: settings.stop_shortcut.trim().to_string(),
+            history_shortcut: settings.history_shortcut.trim().to_string(),
+            device_shortcut: settings.device_shortcut.trim().to_string(),
+            endpoint_url: endpoint_url.clone(),
+            replacements: replacements.clone(),
+            redaction: redaction.clone(),
+            profiles,
+            autostart: settings.autostart,
+            usage_lifetime_seconds: state.usage.stats().lifetime_seconds,
+            preferences: preferences.clone(),
+        },
+    )
+    .await;
+    if let Err(e) = saved {
+        release_added(added);
+        if was_autostart != settings.autostart {
+            if let Err(e) = apply_autostart(&app, was_autostart) {
+                eprintln!("{}", e);
+            }
+        }
+        return Err(e);
+    }
+
+    for old in old_shortcuts {
+        if !shortcuts.all().any(|s| s == old) {
+            if let Err(e) = app.global_shortcut().unregister(old) {
+                eprintln!("Failed to unregister previous shortcut: {}", e);
+            }
+        }
+    }
+    *state.shortcut.lock().await = shortcuts.recording;
+    for (slot, shortcut) in ShortcutSlot::ALL.into_iter().zip(shortcuts.slots) {
+        *slot.current(&state).lock().await = shortcut;
+        slot.id(&state)
+            .store(shortcut.map_or(0, |s| s.id()), Ordering::Relaxed);
+    }
+    *state.profiles.lock().await = shortcuts.profiles;
+
+    *state.soniox_api_key.lock().await = soniox_api_key;
+    state.api_key_rejected.store(false, Ordering::Relaxed);
+    *state.openai_api_key.lock().await = openai_api_key;
+    *state.endpoint_url.lock().await = endpoint_url;
+    *state.replacements.lock().await = replacements;
+    *state.redaction.lock().await = redaction;
+    // Only the warm mic can fail here, and the saved settings stand if it does
+    store_preferences(&app, &state, preferences).await
+}
 
-// App state to track recording status
-#[derive(Clone)]
-pub struct AppState {
-    pub is_recording: Arc<Mutex<bool>>,
-    pub soniox_api_key: Arc<Mutex<String>>,
-    pub last_start_ms: Arc<AtomicU64>,
-    pub latest_transcription: Arc<Mutex<String>>,
+// Parse an accelerator and check it may be bound at all, whatever else is
+fn parse_shortcut(accelerator: &str) -> Result<Shortcut, String> {
+    let shortcut = accelerator
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid shortcut \"{}\": {}", accelerator, e))?;
+    check_not_panic_shortcut(shortcut)?;
+    check_bare_key(shortcut)?;
+    Ok(shortcut)
 }
 
-#[derive(Clone, Serialize)]
-struct RecordingStateEvent {
-    is_recording: bool,
+// Every shortcut of a settings file, parsed and checked against each other
+struct ShortcutSet {
+    recording: Shortcut,
+    // In `ShortcutSlot::ALL` order
+    slots: [Option<Shortcut>; 5],
+    profiles: Vec<(Shortcut, settings::ShortcutProfile)>,
 }
 
-fn now_millis() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+impl ShortcutSet {
+    fn all(&self) -> impl Iterator<Item = Shortcut> + '_ {
+        std::iter::once(self.recording)
+            .chain(self.slots.iter().flatten().copied())
+            .chain(self.profiles.iter().map(|(shortcut, _)| *shortcut))
+    }
 }
 
-// Command to start recording
-#[tauri::command]
-async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
-    // Validate API key before switching to recording state.
-    let api_key = state.soniox_api_key.lock().await.clone();
-    if api_key.is_empty() {
-        return Err("SONIOX API key not set".to_string());
+// Parse the shortcuts of `settings` with the same rules as their setters,
+// checking them against each other rather than the ones now registered
+fn parse_shortcuts(settings: &settings::Settings) -> Result<ShortcutSet, String> {
+    let recording = parse_shortcut(settings.shortcut.trim())?;
+    let mut slots = [None; 5];
+    for (i, slot) in ShortcutSlot::ALL.into_iter().enumerate() {
+        let accelerator = slot.saved(settings).trim();
+        if accelerator.is_empty() {
+            continue;
+        }
+        let shortcut = parse_shortcut(accelerator)?;
+        if shortcut == recording {
+            return Err(format!(
+                "The {} shortcut must differ from the recording shortcut",
+                slot.name()
+            ));
+        }
+        if let Some(other) = (0..i).find(|&j| slots[j] == Some(shortcut)) {
+            return Err(format!(
+                "The {} shortcut must differ from the {} shortcut",
+                slot.name(),
+                ShortcutSlot::ALL[other].name()
+            ));
+        }
+        slots[i] = Some(shortcut);
     }
 
-    let mut is_recording = state.is_recording.lock().await;
-    if *is_recording {
-        return Ok(());
+    let mut profiles: Vec<(Shortcut, settings::ShortcutProfile)> = Vec::new();
+    for profile in settings.profiles.iter().cloned() {
+        let profile = profile.validated()?;
+        let shortcut = parse_shortcut(&profile.shortcut)
+            .map_err(|e| format!("Profile \"{}\": {}", profile.name, e))?;
+        if shortcut == recording || slots.contains(&Some(shortcut)) {
+            return Err(format!(
+                "Profile \"{}\" must use a shortcut other than the recording, cancel, undo, stop, history and device shortcuts",
+                profile.name
+            ));
+        }
+        if let Some((_, other)) = profiles.iter().find(|(s, _)| *s == shortcut) {
+            return Err(format!(
+                "Profiles \"{}\" and \"{}\" share the same shortcut",
+                other.name, profile.name
+            ));
+        }
+        profiles.push((shortcut, profile));
     }
-    *is_recording = true;
-    drop(is_recording);
-    state.last_start_ms.store(now_millis(), Ordering::Relaxed);
 
-    // Emit event to frontend
-    app.emit(
-        "recording-state",
-        RecordingStateEvent { is_recording: true },
-    )
-    .map_err(|e| e.to_string())?;
+    Ok(ShortcutSet {
+        recording,
+        slots,
+        profiles,
+    })
+}
 
-    // Start audio capture and streaming
-    let app_clone = app.clone();
-    let state_recording = state.is_recording.clone();
-    let state_transcription = state.latest_transcription.clone();
-
-    tokio::spawn(async move {
-        match soniox::start_transcription(
-            app_clone.clone(),
-            api_key,
-            state_recording.clone(),
-            state_transcription.clone(),
-        )
-        .await
-        {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Transcription error: {}", e);
-                *state_recording.lock().await = false;
-                let _ = app_clone.emit(
-                    "recording-state",
-                    RecordingStateEvent {
-                        is_recording: false,
-                    },
-                );
-                let _ = app_clone.emit("transcription-error", e.to_string());
+// The recording, optional and profile shortcuts registered now
+async fn registered_shortcuts(state: &AppState) -> Vec<Shortcut> {
+    let mut shortcuts = vec![*state.shortcut.lock().await];
+    for slot in ShortcutSlot::ALL {
+        shortcuts.extend(*slot.current(state).lock().await);
+    }
+    shortcuts.extend(state.profiles.lock().await.iter().map(|(s, _)| *s));
+    shortcuts
+}
+
+// Register the shortcuts of `new` that aren't in `registered` yet, returning
+// them. If one fails, those added before it are released again.
+fn register_shortcuts(
+    app: &AppHandle,
+    registered: &[Shortcut],
+    new: &ShortcutSet,
+) -> Result<Vec<Shortcut>, String> {
+    let mut added = Vec::new();
+    for shortcut in new.all() {
+        if registered.contains(&shortcut) {
+            continue;
+        }
+        if let Err(e) = app.global_shortcut().register(shortcut) {
+            for shortcut in added {
+                let _ = app.global_shortcut().unregister(shortcut);
             }
+            return Err(format!("Failed to register a shortcut: {}", e));
         }
-    });
+        added.push(shortcut);
+    }
+    Ok(added)
+}
 
+// Command to put every setting back to its default, for recovering from a bad
+// shortcut, device or typing setup, or a settings file that can't be read.
+// API keys are kept unless `clear_api_keys` is set, and the usage total is
+// always kept. Emits `settings-reset`.
+#[tauri::command]
+async fn reset_settings(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    clear_api_keys: bool,
+) -> Result<(), String> {
+    let mut defaults = settings::Settings::default();
+    if !clear_api_keys {
+        defaults.soniox_api_key = state.soniox_api_key.lock().await.clone();
+        defaults.openai_api_key = state.openai_api_key.lock().await.clone();
+    }
+
+    update_settings(app.clone(), state, defaults).await?;
+    println!("Settings reset to defaults");
+    let _ = app.emit("settings-reset", ());
     Ok(())
 }
 
-// Command to stop recording
+// Command to change the global shortcut (e.g. "Ctrl+Alt+Space", or "F13" for a
+// foot pedal; see `check_bare_key`)
 #[tauri::command]
-async fn stop_recording(
+async fn set_global_shortcut(
     app: AppHandle,
     state: State<'_, AppState>,
-    reason: Option<String>,
+    accelerator: String,
 ) -> Result<(), String> {
-    let reason = reason.unwrap_or_else(|| "unknown".to_string());
-    println!("stop_recording invoked (reason={})", reason);
-    let mut is_recording = state.is_recording.lock().await;
-    if !*is_recording {
-        println!("stop_recording ignored; already stopped");
-        return Ok(());
+    let accelerator = accelerator.trim().to_string();
+    let new_shortcut = accelerator
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid shortcut \"{}\": {}", accelerator, e))?;
+    check_not_panic_shortcut(new_shortcut)?;
+    check_bare_key(new_shortcut)?;
+    check_shortcut_free(&state, new_shortcut, None).await?;
+
+    let mut current = state.shortcut.lock().await;
+    if *current != new_shortcut {
+        // Register the new shortcut first so a failure keeps the old one active.
+        app.global_shortcut()
+            .register(new_shortcut)
+            .map_err(|e| format!("Failed to register shortcut \"{}\": {}", accelerator, e))?;
+        if let Err(e) = app.global_shortcut().unregister(*current) {
+            eprintln!("Failed to unregister previous shortcut: {}", e);
+        }
+        *current = new_shortcut;
     }
+    drop(current);
 
-    *is_recording = false;
+    settings::update(&app, |s| s.shortcut = accelerator).await
+}
 
-    app.emit(
-        "recording-state",
-        RecordingStateEvent {
-            is_recording: false,
-        },
-    )
-    .map_err(|e| e.to_string())?;
+// The optional shortcuts, each disabled by an empty setting. They're kept
+// apart from each other, the recording shortcut and the profiles.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ShortcutSlot {
+    Cancel,
+    Undo,
+    Stop,
+    History,
+    Device,
+}
+
+impl ShortcutSlot {
+    // In the order they're restored at startup
+    const ALL: [ShortcutSlot; 5] = [
+        ShortcutSlot::Cancel,
+        ShortcutSlot::Undo,
+        ShortcutSlot::Stop,
+        ShortcutSlot::History,
+        ShortcutSlot::Device,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ShortcutSlot::Cancel => "cancel",
+            ShortcutSlot::Undo => "undo",
+            ShortcutSlot::Stop => "stop",
+            ShortcutSlot::History => "history",
+            ShortcutSlot::Device => "device",
+        }
+    }
+
+    fn current(self, state: &AppState) -> &Mutex<Option<Shortcut>> {
+        match self {
+            ShortcutSlot::Cancel => &state.cancel_shortcut,
+            ShortcutSlot::Undo => &state.undo_shortcut,
+            ShortcutSlot::Stop => &state.stop_shortcut,
+            ShortcutSlot::History => &state.history_shortcut,
+            ShortcutSlot::Device => &state.device_shortcut,
+        }
+    }
+
+    // The id `handle_shortcut` matches presses against
+    fn id(self, state: &AppState) -> &AtomicU32 {
+        match self {
+            ShortcutSlot::Cancel => &state.cancel_shortcut_id,
+            ShortcutSlot::Undo => &state.undo_shortcut_id,
+            ShortcutSlot::Stop => &state.stop_shortcut_id,
+            ShortcutSlot::History => &state.history_shortcut_id,
+            ShortcutSlot::Device => &state.device_shortcut_id,
+        }
+    }
+
+    fn saved(self, settings: &settings::Settings) -> &str {
+        match self {
+            ShortcutSlot::Cancel => &settings.cancel_shortcut,
+            ShortcutSlot::Undo => &settings.undo_shortcut,
+            ShortcutSlot::Stop => &settings.stop_shortcut,
+            ShortcutSlot::History => &settings.history_shortcut,
+            ShortcutSlot::Device => &settings.device_shortcut,
+        }
+    }
+
+    fn saved_mut(self, settings: &mut settings::Settings) -> &mut String {
+        match self {
+            ShortcutSlot::Cancel => &mut settings.cancel_shortcut,
+            ShortcutSlot::Undo => &mut settings.undo_shortcut,
+            ShortcutSlot::Stop => &mut settings.stop_shortcut,
+            ShortcutSlot::History => &mut settings.history_shortcut,
+            ShortcutSlot::Device => &mut settings.device_shortcut,
+        }
+    }
+}
 
+// Check that no other shortcut is already bound to `shortcut`: the recording
+// shortcut, another slot or a profile. `slot` is the one being set, or None
+// for the recording shortcut.
+async fn check_shortcut_free(
+    state: &AppState,
+    shortcut: Shortcut,
+    slot: Option<ShortcutSlot>,
+) -> Result<(), String> {
+    let what = slot.map_or("recording", ShortcutSlot::name);
+    if slot.is_some() && shortcut == *state.shortcut.lock().await {
+        let hint = if slot == Some(ShortcutSlot::Stop) {
+            "; clear it to toggle"
+        } else {
+            ""
+        };
+        return Err(format!(
+            "The {} shortcut must differ from the recording shortcut{}",
+            what, hint
+        ));
+    }
+    for other in ShortcutSlot::ALL {
+        if Some(other) != slot && Some(shortcut) == *other.current(state).lock().await {
+            return Err(format!(
+                "The {} shortcut must differ from the {} shortcut",
+                what,
+                other.name()
+            ));
+        }
+    }
+    if let Some(name) = profile_using(state, shortcut).await {
+        return Err(format!("Shortcut is already used by profile \"{}\"", name));
+    }
     Ok(())
 }
 
-// Command to force stop and hide popup immediately (used by Cancel/Escape).
+// Change one of the optional shortcuts (empty to disable it) and save it.
+// The new shortcut is registered before the old one goes, so a failure keeps
+// the old one working.
+async fn set_slot_shortcut(
+    app: &AppHandle,
+    state: &AppState,
+    slot: ShortcutSlot,
+    accelerator: String,
+) -> Result<(), String> {
+    let accelerator = accelerator.trim().to_string();
+    let new_shortcut = if accelerator.is_empty() {
+        None
+    } else {
+        let shortcut = parse_shortcut(&accelerator)?;
+        check_shortcut_free(state, shortcut, Some(slot)).await?;
+        Some(shortcut)
+    };
+
+    let mut current = slot.current(state).lock().await;
+    if *current != new_shortcut {
+        if let Some(shortcut) = new_shortcut {
+            app.global_shortcut()
+                .register(shortcut)
+                .map_err(|e| format!("Failed to register shortcut \"{}\": {}", accelerator, e))?;
+        }
+        if let Some(old) = *current {
+            if let Err(e) = app.global_shortcut().unregister(old) {
+                eprintln!(
+                    "Failed to unregister previous {} shortcut: {}",
+                    slot.name(),
+                    e
+                );
+            }
+        }
+        slot.id(state)
+            .store(new_shortcut.map_or(0, |s| s.id()), Ordering::Relaxed);
+        *current = new_shortcut;
+    }
+    drop(current);
+
+    settings::update(app, |s| *slot.saved_mut(s) = accelerator).await
+}
+
+// Register an optional shortcut from the settings file at startup, with the
+// same rules as its setter. A bad or clashing one is only logged, since the
+// app works without it.
+fn restore_slot_shortcut(app: &AppHandle, slot: ShortcutSlot, accelerator: &str) {
+    if accelerator.trim().is_empty() {
+        return;
+    }
+    let state = app.state::<AppState>();
+    let recording = *state.shortcut.blocking_lock();
+    let taken = |shortcut: Shortcut| {
+        shortcut == recording
+            || ShortcutSlot::ALL
+                .iter()
+                .any(|other| *other.current(&state).blocking_lock() == Some(shortcut))
+    };
+    match parse_shortcut(accelerator.trim()) {
+        Ok(shortcut) if !taken(shortcut) => match app.global_shortcut().register(shortcut) {
+            Ok(()) => {
+                *slot.current(&state).blocking_lock() = Some(shortcut);
+                slot.id(&state).store(shortcut.id(), Ordering::Relaxed);
+            }
+            Err(e) => eprintln!(
+                "Could not register {} shortcut \"{}\": {}",
+                slot.name(),
+                accelerator,
+                e
+            ),
+        },
+        Ok(_) => eprintln!(
+            "Ignoring {} shortcut \"{}\": already in use",
+            slot.name(),
+            accelerator
+        ),
+        Err(e) => eprintln!("Ignoring {} shortcut: {}", slot.name(), e),
+    }
+}
+
+// Name of the profile bound to `shortcut`, if any
+async fn profile_using(state: &AppState, shortcut: Shortcut) -> Option<String> {
+    state
+        .profiles
+        .lock()
+        .await
+        .iter()
+        .find(|(s, _)| *s == shortcut)
+        .map(|(_, profile)| profile.name.clone())
+}
+
+// Command to replace the shortcut profiles, registering each profile's shortcut
 #[tauri::command]
-async fn cancel_and_hide(
+async fn set_shortcut_profiles(
     app: AppHandle,
     state: State<'_, AppState>,
-    reason: Option<String>,
+    profiles: Vec<settings::ShortcutProfile>,
 ) -> Result<(), String> {
-    let reason = reason.unwrap_or_else(|| "ui:force-cancel".to_string());
-    println!("cancel_and_hide invoked (reason={})", reason);
+    let main_shortcut = *state.shortcut.lock().await;
+    let mut slots = Vec::with_capacity(ShortcutSlot::ALL.len());
+    for slot in ShortcutSlot::ALL {
+        slots.push(*slot.current(&state).lock().await);
+    }
 
-    *state.is_recording.lock().await = false;
-    *state.latest_transcription.lock().await = String::new();
-    state.last_start_ms.store(0, Ordering::Relaxed);
+    let mut parsed: Vec<(Shortcut, settings::ShortcutProfile)> = Vec::with_capacity(profiles.len());
+    for profile in profiles {
+        let profile = profile.validated()?;
+        let shortcut = profile.shortcut.parse::<Shortcut>().map_err(|e| {
+            format!(
+                "Invalid shortcut \"{}\" for profile \"{}\": {}",
+                profile.shortcut, profile.name, e
+            )
+        })?;
+        check_not_panic_shortcut(shortcut)?;
+        check_bare_key(shortcut)?;
+        if shortcut == main_shortcut || slots.contains(&Some(shortcut)) {
+            return Err(format!(
+                "Profile \"{}\" must use a shortcut other than the recording, cancel, undo, stop, history and device shortcuts",
+                profile.name
+            ));
+        }
+        if let Some((_, other)) = parsed.iter().find(|(s, _)| *s == shortcut) {
+            return Err(format!(
+                "Profiles \"{}\" and \"{}\" share the same shortcut",
+                other.name, profile.name
+            ));
+        }
+        parsed.push((shortcut, profile));
+    }
 
-    let _ = app.emit(
-        "recording-state",
-        RecordingStateEvent {
-            is_recording: false,
-        },
-    );
-    let _ = app.emit("finish-and-type", ());
+    let mut current = state.profiles.lock().await;
+    let is_current = |shortcut: &Shortcut| current.iter().any(|(s, _)| s == shortcut);
 
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.set_focusable(true);
-        window.hide().map_err(|e| e.to_string())?;
+    // Register new shortcuts first so a failure leaves the old set intact
+    let mut added = Vec::new();
+    for (shortcut, profile) in &parsed {
+        if is_current(shortcut) {
+            continue;
+        }
+        if let Err(e) = app.global_shortcut().register(*shortcut) {
+            for shortcut in added {
+                let _ = app.global_shortcut().unregister(shortcut);
+            }
+            return Err(format!(
+                "Failed to register shortcut \"{}\" for profile \"{}\": {}",
+                profile.shortcut, profile.name, e
+            ));
+        }
+        added.push(*shortcut);
+    }
+    for (old, _) in current.iter() {
+        if !parsed.iter().any(|(s, _)| s == old) {
+            if let Err(e) = app.global_shortcut().unregister(*old) {
+                eprintln!("Failed to unregister profile shortcut: {}", e);
+            }
+        }
     }
 
-    Ok(())
+    let saved: Vec<settings::ShortcutProfile> =
+        parsed.iter().map(|(_, profile)| profile.clone()).collect();
+    *current = parsed;
+    drop(current);
+
+    settings::update(&app, |s| s.profiles = saved).await
 }
 
-// Command to type text at cursor
+// Command to list the shortcut profiles
 #[tauri::command]
-async fn type_text(text: String) -> Result<(), String> {
-    println!("type_text called ({} chars)", text.chars().count());
-    let result = keyboard::type_text(&text).map_err(|e| e.to_string());
-    match &result {
-        Ok(_) => println!("type_text succeeded"),
-        Err(e) => println!("type_text failed: {}", e),
+async fn get_shortcut_profiles(
+    state: State<'_, AppState>,
+) -> Result<Vec<settings::ShortcutProfile>, String> {
+    Ok(state
+        .profiles
+        .lock()
+        .await
+        .iter()
+        .map(|(_, profile)| profile.clone())
+        .collect())
+}
+
+// Command to change the shortcut that aborts a dictation (empty to disable)
+#[tauri::command]
+async fn set_cancel_shortcut(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    accelerator: String,
+) -> Result<(), String> {
+    set_slot_shortcut(&app, &state, ShortcutSlot::Cancel, accelerator).await
+}
+
+// Command to erase the last typed dictation with Backspace. Only reliable
+// right after typing, before the user types or moves the cursor.
+#[tauri::command]
+async fn undo_last_typing(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let result = undo_typing(&state).await;
+    if result.is_err() {
+        notify_if_permission_missing(&app);
     }
     result
 }
 
-// Command to set API key
+// Command to change the shortcut that erases the last dictation (empty to disable)
+#[tauri::command]
+async fn set_undo_shortcut(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    accelerator: String,
+) -> Result<(), String> {
+    set_slot_shortcut(&app, &state, ShortcutSlot::Undo, accelerator).await
+}
+
+// Command to give stop-and-type its own shortcut (empty to toggle with the
+// recording shortcut again)
+#[tauri::command]
+async fn set_stop_shortcut(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    accelerator: String,
+) -> Result<(), String> {
+    set_slot_shortcut(&app, &state, ShortcutSlot::Stop, accelerator).await
+}
+
+// Command to change the shortcut that cycles through recent transcriptions
+// to type one again (empty to disable)
+#[tauri::command]
+async fn set_history_shortcut(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    accelerator: String,
+) -> Result<(), String> {
+    set_slot_shortcut(&app, &state, ShortcutSlot::History, accelerator).await
+}
+
+// Command to change the shortcut that switches to the next connected
+// microphone (empty to disable)
+#[tauri::command]
+async fn set_device_shortcut(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    accelerator: String,
+) -> Result<(), String> {
+    set_slot_shortcut(&app, &state, ShortcutSlot::Device, accelerator).await
+}
+
+// Login item errors, with a hint where the platform needs one
+fn autostart_error(action: &str, e: impl std::fmt::Display) -> String {
+    if cfg!(target_os = "linux") {
+        format!(
+            "Failed to {} start on login: {} (this uses ~/.config/autostart, which some desktops ignore)",
+            action, e
+        )
+    } else {
+        format!("Failed to {} start on login: {}", action, e)
+    }
+}
+
+// Command to launch the app when the user logs in, or stop doing so
 #[tauri::command]
-async fn set_api_key(state: State<'_, AppState>, api_key: String) -> Result<(), String> {
-    let mut key = state.soniox_api_key.lock().await;
-    *key = api_key;
+async fn set_autostart(app: AppHandle, enabled: bool) -> Result<(), String> {
+    apply_autostart(&app, enabled)?;
+    settings::update(&app, |s| s.autostart = enabled).await
+}
+
+// Add or remove the login item, without saving the setting
+fn apply_autostart(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let autolaunch = app.autolaunch();
+    if enabled {
+        autolaunch
+            .enable()
+            .map_err(|e| autostart_error("enable", e))?;
+    } else {
+        autolaunch
+            .disable()
+            .map_err(|e| autostart_error("disable", e))?;
+    }
     Ok(())
 }
 
-// Command to get recording state
+// Command to check whether the app is registered to start on login
 #[tauri::command]
-async fn get_recording_state(state: State<'_, AppState>) -> Result<bool, String> {
-    let is_recording = state.is_recording.lock().await;
-    Ok(*is_recording)
+async fn get_autostart(app: AppHandle) -> Result<bool, String> {
+    app.autolaunch()
+        .is_enabled()
+        .map_err(|e| autostart_error("check", e))
 }
 
 // Command to show the window
 #[tauri::command]
 async fn show_window(app: AppHandle) -> Result<(), String> {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.set_focusable(true);
-        window.show().map_err(|e| e.to_string())?;
-        window.set_focus().map_err(|e| e.to_string())?;
-    }
-    Ok(())
+    show_window_interactive(&app)
 }
 
 // Command to hide the window
 #[tauri::command]
 async fn hide_window(app: AppHandle) -> Result<(), String> {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.set_focusable(true);
-        window.hide().map_err(|e| e.to_string())?;
-    }
-    Ok(())
+    hide_popup(&app)
 }
 
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_autostart::init(
+            MacosLauncher::LaunchAgent,
+            None,
+        ))
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(handle_shortcut)
+                .build(),
+        )
         .manage(AppState {
-            is_recording: Arc::new(Mutex::new(false)),
+            is_recording: Arc::new(AtomicBool::new(false)),
             soniox_api_key: Arc::new(Mutex::new(String::new())),
+            api_key_rejected: Arc::new(AtomicBool::new(false)),
+            openai_api_key: Arc::new(Mutex::new(String::new())),
+            usage: Arc::new(backend::UsageMeter::default()),
+            endpoint_url: Arc::new(Mutex::new(None)),
             last_start_ms: Arc::new(AtomicU64::new(0)),
             latest_transcription: Arc::new(Mutex::new(String::new())),
+            append_mode: Arc::new(Mutex::new(false)),
+            accumulated_text: Arc::new(Mutex::new(String::new())),
+            editor_text: Arc::new(Mutex::new(String::new())),
+            latest_segments: Arc::new(Mutex::new(Vec::new())),
+            selected_input_device: Arc::new(Mutex::new(None)),
+            channel_strategy: Arc::new(Mutex::new(audio::ChannelStrategy::default())),
+            input_gain_db: Arc::new(Mutex::new(0.0)),
+            auto_gain: Arc::new(Mutex::new(false)),
+            dither: Arc::new(Mutex::new(false)),
+            resample_quality: Arc::new(Mutex::new(audio::ResampleQuality::default())),
+            sample_rate: Arc::new(Mutex::new(audio::DEFAULT_SAMPLE_RATE)),
+            priming_ms: Arc::new(Mutex::new(soniox::DEFAULT_PRIMING_MS)),
+            trim_priming_artifacts: Arc::new(Mutex::new(true)),
+            stall_notice_ms: Arc::new(Mutex::new(soniox::DEFAULT_STALL_NOTICE_MS)),
+            display_smoothing_ms: Arc::new(Mutex::new(soniox::DEFAULT_DISPLAY_SMOOTHING_MS)),
+            keepalive_secs: Arc::new(Mutex::new(soniox::DEFAULT_KEEPALIVE_SECS)),
+            audio_format: Arc::new(Mutex::new(audio::AudioFormat::default())),
+            warm_mic_enabled: Arc::new(Mutex::new(false)),
+            warm_mic: Arc::new(Mutex::new(None)),
+            recording_mode: Arc::new(Mutex::new(RecordingMode::default())),
+            finish_mode: Arc::new(Mutex::new(FinishMode::default())),
+            no_speech_notice: Arc::new(Mutex::new(NoSpeechNotice::default())),
+            push_to_talk_min_ms: Arc::new(Mutex::new(DEFAULT_PUSH_TO_TALK_MIN_MS)),
+            hold_cancel_modifier: Arc::new(Mutex::new(HoldCancelModifier::default())),
+            hold_cancel_shortcut: Arc::new(Mutex::new(None)),
+            hold_cancel_shortcut_id: Arc::new(AtomicU32::new(0)),
+            type_mode: Arc::new(Mutex::new(keyboard::TypeMode::default())),
+            text_direction: Arc::new(Mutex::new(keyboard::TextDirection::default())),
+            typing_config: Arc::new(Mutex::new(keyboard::TypingConfig::default())),
+            type_delay_ms: Arc::new(Mutex::new(DEFAULT_TYPE_DELAY_MS)),
+            typing_countdown_ms: Arc::new(Mutex::new(DEFAULT_TYPING_COUNTDOWN_MS)),
+            insert_style: Arc::new(Mutex::new(keyboard::InsertStyle::default())),
+            require_text_field: Arc::new(Mutex::new(false)),
+            target_window: Arc::new(Mutex::new(None)),
+            target_lock: Arc::new(Mutex::new(None)),
+            replacements: Arc::new(Mutex::new(replacements::ReplacementConfig::default())),
+            redaction: Arc::new(Mutex::new(replacements::RedactionConfig::default())),
+            text_normalization: Arc::new(Mutex::new(keyboard::TextNormalization::default())),
+            last_typed: Arc::new(Mutex::new(None)),
+            transcription_options: Arc::new(Mutex::new(soniox::TranscriptionOptions::default())),
+            history: Arc::new(Mutex::new(history::TranscriptionHistory::new(
+                history::DEFAULT_HISTORY_LIMIT,
+            ))),
+            auto_stop: Arc::new(Mutex::new(soniox::AutoStopConfig::default())),
+            pause_breaks: Arc::new(Mutex::new(soniox::PauseBreakConfig::default())),
+            max_duration_secs: Arc::new(Mutex::new(DEFAULT_MAX_DURATION_SECS)),
+            backend: Arc::new(Mutex::new(backend::BackendKind::default())),
+            whisper_model_path: Arc::new(Mutex::new(None)),
+            popup_near_cursor: Arc::new(Mutex::new(true)),
+            popup_always_on_top: Arc::new(Mutex::new(true)),
+            audio_cues: Arc::new(Mutex::new(false)),
+            cue_output: Arc::new(Mutex::new(cues::CueOutput::default())),
+            save_recordings: Arc::new(Mutex::new(false)),
+            recordings_dir: Arc::new(Mutex::new(None)),
+            shortcut: Arc::new(Mutex::new(Shortcut::new(
+                Some(Modifiers::ALT | Modifiers::SHIFT),
+                Code::KeyO,
+            ))),
+            cancel_shortcut: Arc::new(Mutex::new(None)),
+            profiles: Arc::new(Mutex::new(Vec::new())),
+            cancel_shortcut_id: Arc::new(AtomicU32::new(0)),
+            undo_shortcut: Arc::new(Mutex::new(None)),
+            undo_shortcut_id: Arc::new(AtomicU32::new(0)),
+            stop_shortcut: Arc::new(Mutex::new(None)),
+            stop_shortcut_id: Arc::new(AtomicU32::new(0)),
+            history_shortcut: Arc::new(Mutex::new(None)),
+            history_shortcut_id: Arc::new(AtomicU32::new(0)),
+            history_pick: Arc::new(Mutex::new(None)),
+            history_pick_seq: Arc::new(AtomicU64::new(0)),
+            device_shortcut: Arc::new(Mutex::new(None)),
+            device_shortcut_id: Arc::new(AtomicU32::new(0)),
+            device_notice_seq: Arc::new(AtomicU64::new(0)),
+            shutdown_started: Arc::new(AtomicBool::new(false)),
+            shutdown_done: Arc::new(AtomicBool::new(false)),
+            session_generation: Arc::new(AtomicU64::new(0)),
+            session_cancelled: Arc::new(Mutex::new(Arc::new(AtomicBool::new(false)))),
+            session_task: Arc::new(Mutex::new(None)),
+            live_typing: Arc::new(Mutex::new(false)),
+            live_typed: Arc::new(Mutex::new(LiveTyped::default())),
+            live_typer: Arc::new(Mutex::new(None)),
+            shortcut_is_down: Arc::new(AtomicBool::new(false)),
+            last_shortcut_ms: Arc::new(AtomicU64::new(0)),
+            settings_lock: Arc::new(Mutex::new(())),
         })
         .setup(|app| {
+            // Restore persisted settings
+            let saved = settings::load(app.handle());
+            // Read before the fields below are moved out of `saved`
+            let slot_shortcuts = ShortcutSlot::ALL.map(|slot| slot.saved(&saved).to_string());
+            {
+                let state = app.state::<AppState>();
+                *state.soniox_api_key.blocking_lock() = saved.soniox_api_key;
+                *state.openai_api_key.blocking_lock() = saved.openai_api_key;
+                state.usage.restore_lifetime(saved.usage_lifetime_seconds);
+                *state.replacements.blocking_lock() = saved.replacements.validated();
+                *state.redaction.blocking_lock() = saved.redaction.validated();
+                *state.endpoint_url.blocking_lock() =
+                    saved.endpoint_url.as_deref().and_then(|url| {
+                        soniox::validate_endpoint(url)
+                            .map_err(|e| eprintln!("Ignoring saved endpoint: {}", e))
+                            .ok()
+                    });
+            }
+
+            // A moved model file or a backend missing from this build shouldn't
+            // cost the user every other preference
+            {
+                let mut preferences = saved.preferences;
+                if let Some(path) = preferences.whisper_model_path.clone() {
+                    if !std::path::Path::new(&path).is_file() {
+                        eprintln!("Ignoring missing Whisper model: {}", path);
+                        preferences.whisper_model_path = None;
+                    }
+                }
+                if !preferences.backend.is_available() {
+                    preferences.backend = backend::BackendKind::default();
+                }
+                let state = app.state::<AppState>().inner().clone();
+                if let Err(e) = tauri::async_runtime::block_on(apply_preferences(
+                    app.handle(),
+                    &state,
+                    preferences,
+                )) {
+                    eprintln!("Ignoring saved preferences: {}", e);
+                }
+            }
+
+            // Re-register the login item if it went missing (e.g. the app moved)
+            if saved.autostart {
+                let autolaunch = app.autolaunch();
+                if !autolaunch.is_enabled().unwrap_or(false) {
+                    if let Err(e) = autolaunch.enable() {
+                        eprintln!("{}", autostart_error("restore", e));
+                    }
+                }
+            }
+
             // Create system tray menu
+            let editor = MenuItem::with_id(app, "editor", "محرر الإملاء", true, None::<&str>)?;
             let quit = MenuItem::with_id(app, "quit", "إغلاق الناسخ المحلي", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&quit])?;
+            let menu = Menu::with_items(app, &[&editor, &quit])?;
 
             // Create tray icon
             let _tray = TrayIconBuilder::new()
@@ -221,7 +4144,14 @@ fn main() {
                 .menu(&menu)
                 .show_menu_on_left_click(false)
                 .on_menu_event(|app, event| {
-                    if event.id == "quit" {
+                    if event.id == "editor" {
+                        let app = app.clone();
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = open_editor(app).await {
+                                eprintln!("{}", e);
+                            }
+                        });
+                    } else if event.id == "quit" {
                         app.exit(0);
                     }
                 })
@@ -232,176 +4162,339 @@ fn main() {
                         ..
                     } = event
                     {
-                        let app = tray.app_handle();
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.set_focusable(true);
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                        }
+                        let _ = show_window_interactive(tray.app_handle());
                     }
                 })
                 .build(app)?;
 
-            // Register global shortcut (Alt+Shift+O)
-            let shortcut = Shortcut::new(Some(Modifiers::ALT | Modifiers::SHIFT), Code::KeyO);
-            let app_handle = app.handle().clone();
-
-            // Debounce: track last shortcut time to prevent double-firing
-            let last_shortcut_time = Arc::new(AtomicU64::new(0));
-            let last_shortcut_clone = last_shortcut_time.clone();
-            let shortcut_is_down = Arc::new(AtomicBool::new(false));
-            let shortcut_is_down_clone = shortcut_is_down.clone();
-
-            // Get state for shortcut handler
-            let shortcut_state = app.state::<AppState>().inner().clone();
+            // Registered first so no saved shortcut can take its place
+            if let Err(e) = app.global_shortcut().register(panic_shortcut()) {
+                eprintln!(
+                    "Could not register the emergency stop shortcut {}: {}",
+                    PANIC_SHORTCUT, e
+                );
+            }
 
-            app.global_shortcut()
-                .on_shortcut(shortcut, move |_app, _shortcut, event| {
-                    if event.state == ShortcutState::Released {
-                        shortcut_is_down_clone.store(false, Ordering::Relaxed);
-                        return;
-                    }
+            // Register the saved global shortcut, falling back to the default
+            let shortcut = saved
+                .shortcut
+                .parse::<Shortcut>()
+                .ok()
+                .filter(|shortcut| app.global_shortcut().register(*shortcut).is_ok());
+            let shortcut = match shortcut {
+                Some(shortcut) => shortcut,
+                None => {
+                    eprintln!(
+                        "Could not register shortcut \"{}\", using {}",
+                        saved.shortcut,
+                        settings::DEFAULT_SHORTCUT
+                    );
+                    let fallback = settings::DEFAULT_SHORTCUT.parse::<Shortcut>()?;
+                    app.global_shortcut().register(fallback)?;
+                    fallback
+                }
+            };
+            *app.state::<AppState>().shortcut.blocking_lock() = shortcut;
 
-                    if event.state == ShortcutState::Pressed {
-                        // Ignore auto-repeat while the shortcut is held down.
-                        if shortcut_is_down_clone.swap(true, Ordering::Relaxed) {
-                            println!("Shortcut press ignored (key held)");
-                            return;
-                        }
+            for (slot, accelerator) in ShortcutSlot::ALL.into_iter().zip(&slot_shortcuts) {
+                restore_slot_shortcut(app.handle(), slot, accelerator);
+            }
 
-                        // Debounce: ignore if less than 500ms since last press
-                        let now = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_millis() as u64;
-                        let last = last_shortcut_clone.load(Ordering::Relaxed);
-                        if now - last < 500 {
-                            println!("Shortcut debounced (too fast)");
-                            return;
+            // Profiles are extras, so one that can't be registered is skipped
+            {
+                let state = app.state::<AppState>();
+                let slots: Vec<Option<Shortcut>> = ShortcutSlot::ALL
+                    .iter()
+                    .map(|slot| *slot.current(&state).blocking_lock())
+                    .collect();
+                let mut profiles = state.profiles.blocking_lock();
+                for profile in saved.profiles {
+                    let profile = match profile.validated() {
+                        Ok(profile) => profile,
+                        Err(e) => {
+                            eprintln!("Ignoring saved profile: {}", e);
+                            continue;
                         }
-                        last_shortcut_clone.store(now, Ordering::Relaxed);
-
-                        let app = app_handle.clone();
-                        let state = shortcut_state.clone();
-
-                        tauri::async_runtime::spawn(async move {
-                            // Check recording state, not window visibility
-                            let is_recording = *state.is_recording.lock().await;
-                            println!("Shortcut pressed, is_recording: {}", is_recording);
-
-                            if is_recording {
-                                // Stop recording
-                                println!("Stopping recording...");
-                                *state.is_recording.lock().await = false;
-                                let _ = app.emit(
-                                    "recording-state",
-                                    RecordingStateEvent {
-                                        is_recording: false,
-                                    },
-                                );
-
-                                // Get the transcription text BEFORE hiding window
-                                let text = state.latest_transcription.lock().await.clone();
-                                println!("Got transcription for typing: {} chars", text.len());
-
-                                // Hide window first
-                                if let Some(window) = app.get_webview_window("main") {
-                                    let _ = window.set_focusable(true);
-                                    let _ = window.hide();
-                                }
-
-                                // Clear the transcription state
-                                *state.latest_transcription.lock().await = String::new();
-
-                                // Emit event for frontend to clear its state
-                                let _ = app.emit("finish-and-type", ());
-
-                                // Type the text directly from Rust
-                                if !text.trim().is_empty() {
-                                    // Let user release Alt/Shift/O and OS restore focus.
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(280))
-                                        .await;
-
-                                    match keyboard::type_text(text.trim()) {
-                                        Ok(_) => println!("Text typed successfully!"),
-                                        Err(e) => eprintln!("Failed to type text: {}", e),
-                                    }
-                                } else {
-                                    println!("No text to type (empty transcription)");
-                                }
-                            } else {
-                                // Start recording
-                                println!("Starting recording...");
-                                let api_key = state.soniox_api_key.lock().await.clone();
-                                if api_key.is_empty() {
-                                    // Show window for API key setup
-                                    println!("No API key, showing setup window");
-                                    if let Some(window) = app.get_webview_window("main") {
-                                        let _ = window.set_focusable(true);
-                                        let _ = window.show();
-                                        let _ = window.set_focus();
-                                    }
-                                    return;
-                                }
-
-                                // Show a small popup while recording (don't steal focus!)
-                                if let Some(window) = app.get_webview_window("main") {
-                                    let _ = window.set_focusable(false);
-                                    let _ = window.show();
-                                }
-
-                                *state.is_recording.lock().await = true;
-                                state.last_start_ms.store(now_millis(), Ordering::Relaxed);
-                                let _ = app.emit(
-                                    "recording-state",
-                                    RecordingStateEvent { is_recording: true },
-                                );
-
-                                // Clear previous transcription
-                                *state.latest_transcription.lock().await = String::new();
-
-                                // Start transcription
-                                let app_clone = app.clone();
-                                let is_rec = state.is_recording.clone();
-                                let transcription_state = state.latest_transcription.clone();
-                                tokio::spawn(async move {
-                                    if let Err(e) = soniox::start_transcription(
-                                        app_clone.clone(),
-                                        api_key,
-                                        is_rec.clone(),
-                                        transcription_state,
-                                    )
-                                    .await
-                                    {
-                                        eprintln!("Transcription error: {}", e);
-                                        *is_rec.lock().await = false;
-                                        let _ = app_clone.emit(
-                                            "recording-state",
-                                            RecordingStateEvent {
-                                                is_recording: false,
-                                            },
-                                        );
-                                        let _ =
-                                            app_clone.emit("transcription-error", e.to_string());
-                                    }
-                                });
+                    };
+                    let registered = profile
+                        .shortcut
+                        .parse::<Shortcut>()
+                        .map_err(|e| e.to_string())
+                        .and_then(|s| {
+                            if s == shortcut
+                                || slots.contains(&Some(s))
+                                || profiles.iter().any(|(p, _)| *p == s)
+                            {
+                                return Err("already in use".to_string());
                             }
+                            app.global_shortcut()
+                                .register(s)
+                                .map(|()| s)
+                                .map_err(|e| e.to_string())
                         });
+                    match registered {
+                        Ok(s) => profiles.push((s, profile)),
+                        Err(e) => eprintln!(
+                            "Could not register shortcut \"{}\" for profile \"{}\": {}",
+                            profile.shortcut, profile.name, e
+                        ),
+                    }
+                }
+            }
+
+            // The session asks to stop itself (trailing silence or max duration);
+            // finish exactly like a shortcut press would.
+            let app_handle = app.handle().clone();
+            app.listen("auto-stop", move |event| {
+                println!("Auto-stop requested ({})", event.payload());
+                let app = app_handle.clone();
+                let state = app.state::<AppState>().inner().clone();
+                tauri::async_runtime::spawn(async move {
+                    if state.is_recording.load(Ordering::SeqCst) {
+                        finish_and_type(app, state).await;
                     }
-                })?;
+                });
+            });
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            get_settings,
+            reset_settings,
+            update_settings,
             start_recording,
             stop_recording,
             cancel_and_hide,
+            confirm_and_type,
             type_text,
+            check_accessibility_permission,
+            check_microphone_permission,
+            open_microphone_settings,
+            open_config_dir,
+            set_require_text_field,
+            set_target_window,
+            open_accessibility_settings,
+            test_typing,
             set_api_key,
+            set_openai_api_key,
+            get_usage_stats,
+            get_capabilities,
+            reset_usage_stats,
+            has_api_key,
+            validate_api_key,
+            set_endpoint,
             get_recording_state,
+            list_input_devices,
+            set_input_device,
+            set_channel_strategy,
+            set_input_gain,
+            set_auto_gain,
+            transcribe_file,
+            set_append_mode,
+            set_live_typing,
+            clear_transcription,
+            set_dither,
+            set_resample_quality,
+            set_sample_rate,
+            set_audio_format,
+            set_stall_notice,
+            set_display_smoothing,
+            set_keepalive_interval,
+            set_warm_mic,
+            set_priming_silence,
+            set_trim_priming_artifacts,
+            set_recording_mode,
+            set_finish_mode,
+            set_no_speech_notice,
+            set_push_to_talk_min_hold,
+            set_hold_cancel_modifier,
+            set_type_mode,
+            set_text_direction,
+            set_typing_config,
+            set_type_delay,
+            set_typing_countdown,
+            set_insert_style,
+            set_text_normalization,
+            set_replacements,
+            set_redaction,
+            set_transcription_options,
+            set_translation,
+            get_history,
+            export_transcription,
+            retype_entry,
+            clear_history,
+            set_history_limit,
+            set_auto_stop,
+            set_auto_punctuation_breaks,
+            set_max_duration,
+            set_backend,
+            set_whisper_model,
+            set_popup_near_cursor,
+            set_popup_always_on_top,
+            set_audio_cues,
+            list_output_devices,
+            set_cue_output,
+            set_save_recordings,
+            set_recordings_dir,
+            set_global_shortcut,
+            set_cancel_shortcut,
+            undo_last_typing,
+            set_undo_shortcut,
+            set_stop_shortcut,
+            set_history_shortcut,
+            set_device_shortcut,
+            open_editor,
+            get_editor_text,
+            set_editor_text,
+            type_editor_text,
+            export_editor_text,
+            set_autostart,
+            get_autostart,
+            set_shortcut_profiles,
+            get_shortcut_profiles,
             show_window,
             hide_window,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Quitting from the tray or closing the last window: clean up
+            // first, then exit for real
+            if let RunEvent::ExitRequested { code, api, .. } = event {
+                let state = app.state::<AppState>().inner().clone();
+                if state.shutdown_done.load(Ordering::SeqCst) {
+                    return;
+                }
+                api.prevent_exit();
+                if state.shutdown_started.swap(true, Ordering::SeqCst) {
+                    return;
+                }
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    shutdown(&state).await;
+                    state.shutdown_done.store(true, Ordering::SeqCst);
+                    app.exit(code.unwrap_or(0));
+                });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_starts_once() {
+        let is_recording = AtomicBool::new(false);
+        assert!(try_start_recording(&is_recording));
+        assert!(is_recording.load(Ordering::SeqCst));
+        // A second press while recording doesn't start another session
+        assert!(!try_start_recording(&is_recording));
+        assert!(is_recording.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn stop_ends_recording_once() {
+        let is_recording = AtomicBool::new(false);
+        assert!(try_start_recording(&is_recording));
+        assert!(try_stop_recording(&is_recording));
+        assert!(!is_recording.load(Ordering::SeqCst));
+        // Stopping again is ignored
+        assert!(!try_stop_recording(&is_recording));
+    }
+
+    #[test]
+    fn cancel_after_stop_is_ignored() {
+        let is_recording = AtomicBool::new(false);
+        assert!(try_start_recording(&is_recording));
+        assert!(try_stop_recording(&is_recording));
+        // Cancel racing the stop finds nothing to cancel
+        assert!(!try_stop_recording(&is_recording));
+        // A new dictation can start and be cancelled
+        assert!(try_start_recording(&is_recording));
+        assert!(try_stop_recording(&is_recording));
+        assert!(!is_recording.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn live_rewind_replaces_held_back_punctuation() {
+        let mut pending = String::new();
+        apply_live_change(&mut pending, backend::LiveText::Append(" hello,".into()));
+        apply_live_change(&mut pending, backend::LiveText::Rewind(1));
+        apply_live_change(&mut pending, backend::LiveText::Append(".\n".into()));
+        assert_eq!(pending, " hello.\n");
+        // Nothing left to take back once it's been typed
+        let mut pending = String::from(" ");
+        apply_live_change(&mut pending, backend::LiveText::Rewind(2));
+        assert_eq!(pending, "");
+    }
+
+    #[test]
+    fn untyped_words_follow_the_typed_ones() {
+        assert_eq!(untyped_words("hello world again", "hello world"), " again");
+        // Sentence breaks after the typed words are kept
+        assert_eq!(
+            untyped_words("Hi there.\nNext one", "Hi there."),
+            "\nNext one"
+        );
+        assert_eq!(untyped_words("all typed", "all typed"), "");
+    }
+
+    #[test]
+    fn revised_words_still_leave_the_rest() {
+        // SONIOX revised "word" after it was typed; the new words still go out
+        assert_eq!(untyped_words("a ward and more", "a word"), " and more");
+        assert_eq!(untyped_words("short", "typed more than final"), "");
+    }
+
+    #[test]
+    fn saved_shortcuts_follow_the_setter_rules() {
+        assert!(parse_shortcut("X").is_err());
+        assert!(parse_shortcut(PANIC_SHORTCUT).is_err());
+        assert!(parse_shortcut("F13").is_ok());
+    }
+
+    #[test]
+    fn imported_shortcuts_must_differ() {
+        let mut imported = settings::Settings::default();
+        imported.undo_shortcut = imported.cancel_shortcut.clone();
+        assert!(parse_shortcuts(&imported).is_err());
+    }
+
+    #[test]
+    fn imported_shortcuts_can_trade_places() {
+        // Checked against each other, not the ones registered now
+        let mut imported = settings::Settings::default();
+        std::mem::swap(&mut imported.shortcut, &mut imported.cancel_shortcut);
+        let shortcuts = parse_shortcuts(&imported).unwrap();
+        assert!(shortcuts.recording == settings::DEFAULT_CANCEL_SHORTCUT.parse().unwrap());
+        assert!(shortcuts.slots[0] == settings::DEFAULT_SHORTCUT.parse().ok());
+    }
+
+    #[tokio::test]
+    async fn session_panic_clears_recording() {
+        let is_recording = Arc::new(AtomicBool::new(true));
+        let flag = is_recording.clone();
+        let text = catch_session_panic(
+            async {
+                panic!("backend bug");
+            },
+            async move { flag.store(false, Ordering::SeqCst) },
+        )
+        .await;
+        assert_eq!(text, None);
+        assert!(!is_recording.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn finished_session_skips_panic_cleanup() {
+        let cleaned_up = Arc::new(AtomicBool::new(false));
+        let flag = cleaned_up.clone();
+        let text = catch_session_panic(async { Some("hello".to_string()) }, async move {
+            flag.store(true, Ordering::SeqCst)
+        })
+        .await;
+        assert_eq!(text.as_deref(), Some("hello"));
+        assert!(!cleaned_up.load(Ordering::SeqCst));
+    }
 }
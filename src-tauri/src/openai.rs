@@ -0,0 +1,217 @@
+use crate::audio::start_capture;
+use crate::backend::{SessionContext, TranscriptionBackend, TranscriptionError};
+use crate::recordings::SessionRecorder;
+use crate::soniox::{SessionConfig, TranscriptionEvent};
+use futures_util::future::BoxFuture;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tauri::Emitter;
+
+// OpenAI's speech-to-text endpoint
+pub const OPENAI_TRANSCRIPTION_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+const OPENAI_MODEL: &str = "whisper-1";
+
+// The API refuses files larger than 25 MB (about 13 minutes at 16kHz)
+const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+
+// Give up on an upload that hasn't been answered in this long
+pub const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+}
+
+/// Transcription with OpenAI's hosted Whisper API.
+///
+/// The API isn't streaming, so the whole recording is buffered and uploaded
+/// as one WAV file on stop. There are no partial results; a `transcribing`
+/// event tells the popup the upload is under way.
+pub struct OpenAiBackend {
+    session: SessionConfig,
+}
+
+impl OpenAiBackend {
+    pub fn new(session: SessionConfig) -> Self {
+        OpenAiBackend { session }
+    }
+
+    async fn run(self, ctx: SessionContext) -> Result<String, TranscriptionError> {
+        let sample_rate = self.session.capture.sample_rate;
+        let (capture, mut audio_rx) =
+            start_capture(ctx.app.clone(), self.session.capture.clone(), &ctx.source).await?;
+
+        let mut recorder = self.session.recording_dir.as_deref().and_then(|dir| {
+            SessionRecorder::create(dir, sample_rate)
+                .map_err(|e| eprintln!("Not saving this recording: {}", e))
+                .ok()
+        });
+
+        let mut samples: Vec<i16> = Vec::new();
+        let result = loop {
+            let superseded = !ctx.generation.is_current();
+            if ctx.is_recording.load(Ordering::SeqCst) && !superseded {
+                tokio::select! {
+                    Some(chunk) = audio_rx.recv() => {
+                        if let Some(recorder) = recorder.as_mut() {
+                            recorder.write(&chunk.samples);
+                        }
+                        samples.extend_from_slice(&chunk.samples);
+                    }
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                }
+            } else if ctx.cancelled.load(Ordering::Relaxed) || superseded {
+                break Err(TranscriptionError::Cancelled);
+            } else {
+                // Stop capturing and pick up whatever is still queued.
+                capture.stop();
+                while let Ok(chunk) = audio_rx.try_recv() {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.write(&chunk.samples);
+                    }
+                    samples.extend_from_slice(&chunk.samples);
+                }
+                break Ok(());
+            }
+        };
+        capture.stop();
+        if let Some(recorder) = recorder {
+            recorder.finish();
+        }
+        result?;
+
+        let final_text = if samples.is_empty() {
+            String::new()
+        } else {
+            let _ = ctx.app.emit("transcribing", ());
+            let wav = encode_wav(&samples, sample_rate)?;
+            ctx.usage.record(samples.len(), sample_rate);
+            // OpenAI takes a single language; use the first hint or auto-detect.
+            let language = self.session.options.language_hints.first().cloned();
+            let upload = upload(&self.session.api_key, wav, language);
+            tokio::pin!(upload);
+
+            // Keep watching for a cancel while the request is in flight
+            loop {
+                tokio::select! {
+                    result = &mut upload => break result?,
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                        if ctx.cancelled.load(Ordering::Relaxed) || !ctx.generation.is_current() {
+                            return Err(TranscriptionError::Cancelled);
+                        }
+                    }
+                }
+            }
+        };
+
+        // The upload may have finished just as the user cancelled
+        if ctx.cancelled.load(Ordering::Relaxed) || !ctx.generation.is_current() {
+            return Err(TranscriptionError::Cancelled);
+        }
+        *ctx.latest_transcription.lock().await = final_text.clone();
+        let _ = ctx.app.emit(
+            "transcription-complete",
+            TranscriptionEvent::complete(&final_text, &[]),
+        );
+
+        Ok(final_text)
+    }
+}
+
+impl TranscriptionBackend for OpenAiBackend {
+    fn transcribe_stream(
+        self: Box<Self>,
+        ctx: SessionContext,
+    ) -> BoxFuture<'static, Result<String, TranscriptionError>> {
+        Box::pin(self.run(ctx))
+    }
+}
+
+/// Pack mono 16-bit samples into an in-memory WAV file
+fn encode_wav(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>, TranscriptionError> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut buffer = Cursor::new(Vec::with_capacity(44 + samples.len() * 2));
+    let mut writer = WavWriter::new(&mut buffer, spec)
+        .map_err(|e| TranscriptionError::Backend(format!("Failed to encode audio: {}", e)))?;
+    for &sample in samples {
+        writer
+            .write_sample(sample)
+            .map_err(|e| TranscriptionError::Backend(format!("Failed to encode audio: {}", e)))?;
+    }
+    writer
+        .finalize()
+        .map_err(|e| TranscriptionError::Backend(format!("Failed to encode audio: {}", e)))?;
+
+    let wav = buffer.into_inner();
+    if wav.len() > MAX_UPLOAD_BYTES {
+        return Err(TranscriptionError::Backend(
+            "Recording is too long for the OpenAI API (25 MB limit)".to_string(),
+        ));
+    }
+    Ok(wav)
+}
+
+/// POST the recording to the transcription endpoint and return its text
+async fn upload(
+    api_key: &str,
+    wav: Vec<u8>,
+    language: Option<String>,
+) -> Result<String, TranscriptionError> {
+    let file = reqwest::multipart::Part::bytes(wav)
+        .file_name("dictation.wav")
+        .mime_str("audio/wav")
+        .map_err(|e| TranscriptionError::Backend(e.to_string()))?;
+    let mut form = reqwest::multipart::Form::new()
+        .text("model", OPENAI_MODEL)
+        .text("response_format", "json")
+        .part("file", file);
+    if let Some(language) = language {
+        form = form.text("language", language);
+    }
+
+    let response = reqwest::Client::new()
+        .post(OPENAI_TRANSCRIPTION_URL)
+        .bearer_auth(api_key)
+        .multipart(form)
+        .timeout(UPLOAD_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| TranscriptionError::Connect(e.to_string()))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| TranscriptionError::Connect(e.to_string()))?;
+    if !status.is_success() {
+        let message = serde_json::from_str::<ErrorResponse>(&body)
+            .map(|e| e.error.message)
+            .unwrap_or_else(|_| format!("HTTP {}", status));
+        return Err(match status.as_u16() {
+            401 | 403 => TranscriptionError::Auth(message),
+            _ => TranscriptionError::Protocol(format!("OpenAI error: {}", message)),
+        });
+    }
+
+    serde_json::from_str::<TranscriptionResponse>(&body)
+        .map(|r| r.text.trim().to_string())
+        .map_err(|e| TranscriptionError::Protocol(format!("Unexpected OpenAI response: {}", e)))
+}
@@ -0,0 +1,95 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+// Only the newest recordings are kept; older files are deleted
+const MAX_SAVED_RECORDINGS: usize = 20;
+
+/// Default folder for saved recordings (`recordings` in the app data dir)
+pub fn default_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve data dir: {}", e))?;
+    Ok(dir.join("recordings"))
+}
+
+/// Writes a session's audio to a WAV file, exactly as sent for transcription
+/// (mono 16-bit PCM at the session's sample rate).
+pub struct SessionRecorder {
+    writer: WavWriter<BufWriter<File>>,
+    path: PathBuf,
+}
+
+impl SessionRecorder {
+    /// Start a new timestamped recording in `dir`, pruning old ones first
+    pub fn create(dir: &Path, sample_rate: u32) -> Result<Self, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        prune(dir, MAX_SAVED_RECORDINGS.saturating_sub(1));
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("dictation-{}.wav", timestamp_ms));
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let writer = WavWriter::create(&path, spec)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        Ok(SessionRecorder { writer, path })
+    }
+
+    pub fn write(&mut self, samples: &[i16]) {
+        for &sample in samples {
+            if let Err(e) = self.writer.write_sample(sample) {
+                eprintln!("Failed to write recording: {}", e);
+                return;
+            }
+        }
+    }
+
+    /// Flush the WAV header and close the file
+    pub fn finish(self) {
+        let path = self.path;
+        match self.writer.finalize() {
+            Ok(()) => println!("Saved recording to {}", path.display()),
+            Err(e) => eprintln!("Failed to finish recording {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Delete the oldest recordings so at most `keep` remain
+fn prune(dir: &Path, keep: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut recordings: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "wav")
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("dictation-"))
+        })
+        .collect();
+    if recordings.len() <= keep {
+        return;
+    }
+
+    // Names embed the timestamp, so sorting by name sorts by age
+    recordings.sort();
+    let excess = recordings.len() - keep;
+    for path in recordings.into_iter().take(excess) {
+        if let Err(e) = fs::remove_file(&path) {
+            eprintln!("Failed to remove old recording {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+
+// Spoken commands understood when `voice_commands` is on, longest first
+const VOICE_COMMANDS: &[(&str, &str)] = &[
+    ("new paragraph", "\n\n"),
+    ("new line", "\n"),
+    ("فقرة جديدة", "\n\n"),
+    ("سطر جديد", "\n"),
+];
+
+/// User fixes applied to a finished transcription before it's typed
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplacementConfig {
+    /// (spoken, written) pairs, matched as whole words in order
+    pub rules: Vec<(String, String)>,
+    pub case_sensitive: bool,
+    /// Turn phrases like "new line" into line breaks
+    pub voice_commands: bool,
+}
+
+impl Default for ReplacementConfig {
+    fn default() -> Self {
+        ReplacementConfig {
+            rules: Vec::new(),
+            case_sensitive: false,
+            voice_commands: true,
+        }
+    }
+}
+
+impl ReplacementConfig {
+    /// Drop rules with nothing to match
+    pub fn validated(mut self) -> Self {
+        self.rules.retain(|(from, _)| !from.trim().is_empty());
+        for (from, _) in self.rules.iter_mut() {
+            *from = from.trim().to_string();
+        }
+        self
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (from, to) in &self.rules {
+            text = replace_phrase(&text, from, to, self.case_sensitive);
+        }
+
+        if self.voice_commands {
+            let before = text.clone();
+            for (from, to) in VOICE_COMMANDS {
+                text = replace_phrase(&text, from, to, false);
+            }
+            if text != before {
+                text = tidy_line_breaks(&text);
+            }
+        }
+        text
+    }
+}
+
+/// How a redacted word is hidden
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionMode {
+    /// One asterisk per character, so the length still shows
+    #[default]
+    Mask,
+    Remove,
+}
+
+/// Words and phrases hidden from a finished transcription, after the
+/// replacements. Off until the user turns it on.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Matched as whole words, ignoring case
+    pub words: Vec<String>,
+    pub mode: RedactionMode,
+}
+
+impl RedactionConfig {
+    /// Drop blank entries
+    pub fn validated(mut self) -> Self {
+        self.words = self
+            .words
+            .iter()
+            .map(|word| word.trim().to_string())
+            .filter(|word| !word.is_empty())
+            .collect();
+        self
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        if !self.enabled || self.words.is_empty() {
+            return text.to_string();
+        }
+        let mut text = text.to_string();
+        for word in &self.words {
+            text = replace_phrase_with(&text, word, false, |matched| match self.mode {
+                RedactionMode::Mask => "*".repeat(matched.chars().count()),
+                RedactionMode::Remove => String::new(),
+            });
+        }
+        if self.mode == RedactionMode::Remove {
+            text = tidy_spaces(&text);
+        }
+        text
+    }
+}
+
+/// Replace whole-word occurrences of `from` with `to`
+fn replace_phrase(text: &str, from: &str, to: &str, case_sensitive: bool) -> String {
+    replace_phrase_with(text, from, case_sensitive, |_| to.to_string())
+}
+
+/// Replace whole-word occurrences of `from` with whatever `to` makes of the
+/// matched text
+fn replace_phrase_with(
+    text: &str,
+    from: &str,
+    case_sensitive: bool,
+    to: impl Fn(&str) -> String,
+) -> String {
+    if from.is_empty() {
+        return text.to_string();
+    }
+    let needs_start_boundary = from.chars().next().is_some_and(char::is_alphanumeric);
+    let needs_end_boundary = from.chars().last().is_some_and(char::is_alphanumeric);
+
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let at_start = !needs_start_boundary
+            || !text[..i]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric);
+        if at_start {
+            if let Some(len) = match_len(&text[i..], from, case_sensitive) {
+                let at_end = !needs_end_boundary
+                    || !text[i + len..]
+                        .chars()
+                        .next()
+                        .is_some_and(char::is_alphanumeric);
+                if at_end {
+                    out.push_str(&to(&text[i..i + len]));
+                    i += len;
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+        i += c.len_utf8();
+    }
+    out
+}
+
+/// Byte length of the prefix of `haystack` matching `needle`, if any
+fn match_len(haystack: &str, needle: &str, case_sensitive: bool) -> Option<usize> {
+    let mut chars = haystack.char_indices();
+    for n in needle.chars() {
+        let (_, c) = chars.next()?;
+        let equal = if case_sensitive {
+            c == n
+        } else {
+            c.to_lowercase().eq(n.to_lowercase())
+        };
+        if !equal {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(haystack.len(), |(index, _)| index))
+}
+
+/// Remove the spaces and stray punctuation SONIOX leaves around a spoken
+/// line break ("hello, new line. world" -> "hello\nworld")
+fn tidy_line_breaks(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            line.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '.' | ',' | '،'))
+                .trim_end_matches(|c: char| c.is_whitespace() || matches!(c, ',' | '،'))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Close the gaps left where words were removed ("a  b ." -> "a b.")
+fn tidy_spaces(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            let mut out = String::with_capacity(line.len());
+            for word in line.split_whitespace() {
+                let punctuation_only = word
+                    .chars()
+                    .all(|c| matches!(c, '.' | ',' | '،' | '!' | '?' | '؟' | ';' | ':'));
+                if !out.is_empty() && !punctuation_only {
+                    out.push(' ');
+                }
+                out.push_str(word);
+            }
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(pairs: &[(&str, &str)]) -> ReplacementConfig {
+        ReplacementConfig {
+            rules: pairs
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rules_match_whole_words() {
+        let config = rules(&[("cat", "dog")]);
+        assert_eq!(config.apply("Cat and concat"), "dog and concat");
+    }
+
+    #[test]
+    fn case_sensitive_rules_skip_other_cases() {
+        let config = ReplacementConfig {
+            case_sensitive: true,
+            ..rules(&[("cat", "dog")])
+        };
+        assert_eq!(config.apply("Cat and cat"), "Cat and dog");
+    }
+
+    #[test]
+    fn voice_commands_become_line_breaks() {
+        let config = ReplacementConfig::default();
+        assert_eq!(config.apply("hello, new line. world"), "hello\nworld");
+        let off = ReplacementConfig {
+            voice_commands: false,
+            ..Default::default()
+        };
+        assert_eq!(off.apply("hello new line"), "hello new line");
+    }
+
+    #[test]
+    fn blank_rules_are_dropped() {
+        let config = rules(&[("  ", "x"), (" cat ", "dog")]).validated();
+        assert_eq!(config.rules, [("cat".to_string(), "dog".to_string())]);
+    }
+
+    #[test]
+    fn redaction_masks_or_removes() {
+        let mut redaction = RedactionConfig {
+            enabled: true,
+            words: vec!["secret".to_string()],
+            mode: RedactionMode::Mask,
+        };
+        assert_eq!(redaction.apply("the Secret plan"), "the ****** plan");
+        redaction.mode = RedactionMode::Remove;
+        assert_eq!(redaction.apply("the secret , plan"), "the, plan");
+    }
+
+    #[test]
+    fn redaction_sees_the_replaced_text() {
+        // Replacements run first, so a replaced word can still be hidden
+        let replacements = rules(&[("project x", "Apollo")]);
+        let redaction = RedactionConfig {
+            enabled: true,
+            words: vec!["apollo".to_string()],
+            mode: RedactionMode::Mask,
+        };
+        let text = redaction.apply(&replacements.apply("about project X today"));
+        assert_eq!(text, "about ****** today");
+    }
+}
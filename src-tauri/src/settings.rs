@@ -0,0 +1,336 @@
+use crate::audio::{self, AudioFormat, ChannelStrategy, ResampleQuality};
+use crate::backend::BackendKind;
+use crate::cues::CueOutput;
+use crate::keyboard::{
+    InsertStyle, TargetWindow, TextDirection, TextNormalization, TypeMode, TypingConfig,
+};
+use crate::replacements::{RedactionConfig, ReplacementConfig};
+use crate::soniox::{self, AutoStopConfig, PauseBreakConfig, TranscriptionOptions};
+use crate::{history, FinishMode, HoldCancelModifier, NoSpeechNotice, RecordingMode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+// Settings live in `settings.json` inside the app config directory:
+//   Windows: %APPDATA%\com.localwispr.app\settings.json
+//   macOS:   ~/Library/Application Support/com.localwispr.app/settings.json
+//   Linux:   ~/.config/com.localwispr.app/settings.json
+// Deleting the file resets the app (including the stored API key).
+const SETTINGS_FILE: &str = "settings.json";
+
+pub const DEFAULT_SHORTCUT: &str = "Alt+Shift+O";
+pub const DEFAULT_CANCEL_SHORTCUT: &str = "Alt+Shift+X";
+
+/// An extra shortcut that dictates with its own recognition options,
+/// e.g. one hotkey for Arabic and another for English
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ShortcutProfile {
+    pub name: String,
+    pub shortcut: String,
+    pub options: TranscriptionOptions,
+}
+
+impl ShortcutProfile {
+    /// Trim the name and shortcut and check the options
+    pub fn validated(self) -> Result<Self, String> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err("Profile name must not be empty".to_string());
+        }
+        let options = self
+            .options
+            .validated()
+            .map_err(|e| format!("Profile \"{}\": {}", name, e))?;
+        Ok(ShortcutProfile {
+            name,
+            shortcut: self.shortcut.trim().to_string(),
+            options,
+        })
+    }
+}
+
+/// Options mirrored in the app state. Commands that change one save them all
+/// together, so the file never holds a half-applied configuration.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Preferences {
+    // Microphone id from `list_input_devices`; None uses the system default
+    pub input_device: Option<String>,
+    pub channel_strategy: ChannelStrategy,
+    pub input_gain_db: f32,
+    pub auto_gain: bool,
+    pub dither: bool,
+    pub resample_quality: ResampleQuality,
+    pub sample_rate: u32,
+    pub audio_format: AudioFormat,
+    pub priming_ms: u64,
+    pub trim_priming_artifacts: bool,
+    pub stall_notice_ms: u64,
+    pub display_smoothing_ms: u64,
+    pub keepalive_secs: u64,
+    pub warm_mic: bool,
+    pub recording_mode: RecordingMode,
+    pub finish_mode: FinishMode,
+    pub no_speech_notice: NoSpeechNotice,
+    pub push_to_talk_min_ms: u64,
+    pub hold_cancel_modifier: HoldCancelModifier,
+    pub type_mode: TypeMode,
+    pub text_direction: TextDirection,
+    pub typing_config: TypingConfig,
+    pub type_delay_ms: u64,
+    pub typing_countdown_ms: u64,
+    pub insert_style: InsertStyle,
+    pub text_normalization: TextNormalization,
+    pub append_mode: bool,
+    pub live_typing: bool,
+    pub require_text_field: bool,
+    pub target_lock: Option<TargetWindow>,
+    // Model and language hints
+    pub transcription_options: TranscriptionOptions,
+    pub history_limit: usize,
+    pub auto_stop: AutoStopConfig,
+    pub pause_breaks: PauseBreakConfig,
+    // 0 = unlimited
+    pub max_duration_secs: u64,
+    pub backend: BackendKind,
+    pub whisper_model_path: Option<String>,
+    pub popup_near_cursor: bool,
+    pub popup_always_on_top: bool,
+    pub audio_cues: bool,
+    pub cue_output: CueOutput,
+    pub save_recordings: bool,
+    pub recordings_dir: Option<String>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            input_device: None,
+            channel_strategy: ChannelStrategy::default(),
+            input_gain_db: 0.0,
+            auto_gain: false,
+            dither: false,
+            resample_quality: ResampleQuality::default(),
+            sample_rate: audio::DEFAULT_SAMPLE_RATE,
+            audio_format: AudioFormat::default(),
+            priming_ms: soniox::DEFAULT_PRIMING_MS,
+            trim_priming_artifacts: true,
+            stall_notice_ms: soniox::DEFAULT_STALL_NOTICE_MS,
+            display_smoothing_ms: soniox::DEFAULT_DISPLAY_SMOOTHING_MS,
+            keepalive_secs: soniox::DEFAULT_KEEPALIVE_SECS,
+            warm_mic: false,
+            recording_mode: RecordingMode::default(),
+            finish_mode: FinishMode::default(),
+            no_speech_notice: NoSpeechNotice::default(),
+            push_to_talk_min_ms: crate::DEFAULT_PUSH_TO_TALK_MIN_MS,
+            hold_cancel_modifier: HoldCancelModifier::default(),
+            type_mode: TypeMode::default(),
+            text_direction: TextDirection::default(),
+            typing_config: TypingConfig::default(),
+            type_delay_ms: crate::DEFAULT_TYPE_DELAY_MS,
+            typing_countdown_ms: crate::DEFAULT_TYPING_COUNTDOWN_MS,
+            insert_style: InsertStyle::default(),
+            text_normalization: TextNormalization::default(),
+            append_mode: false,
+            live_typing: false,
+            require_text_field: false,
+            target_lock: None,
+            transcription_options: TranscriptionOptions::default(),
+            history_limit: history::DEFAULT_HISTORY_LIMIT,
+            auto_stop: AutoStopConfig::default(),
+            pause_breaks: PauseBreakConfig::default(),
+            max_duration_secs: crate::DEFAULT_MAX_DURATION_SECS,
+            backend: BackendKind::default(),
+            whisper_model_path: None,
+            popup_near_cursor: true,
+            popup_always_on_top: true,
+            audio_cues: false,
+            cue_output: CueOutput::default(),
+            save_recordings: false,
+            recordings_dir: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub soniox_api_key: String,
+    // Used only by the OpenAI backend
+    pub openai_api_key: String,
+    // Global shortcut accelerator, e.g. "Alt+Shift+O"
+    pub shortcut: String,
+    // Aborts the current dictation without typing; empty disables it
+    pub cancel_shortcut: String,
+    // Erases the last typed dictation; empty disables it
+    pub undo_shortcut: String,
+    // Finishes and types; empty keeps the recording shortcut as a toggle
+    pub stop_shortcut: String,
+    // Cycles through recent transcriptions to type one again; empty disables it
+    pub history_shortcut: String,
+    // Switches to the next connected microphone; empty disables it
+    pub device_shortcut: String,
+    // Custom SONIOX WebSocket URL (proxy/on-prem); None uses the public endpoint
+    pub endpoint_url: Option<String>,
+    pub replacements: ReplacementConfig,
+    // Words hidden from transcriptions; off by default
+    pub redaction: RedactionConfig,
+    pub profiles: Vec<ShortcutProfile>,
+    // Launch the app when the user logs in
+    pub autostart: bool,
+    // Total seconds of audio sent to cloud backends, for usage stats
+    pub usage_lifetime_seconds: f64,
+    // Stored inline, alongside the fields above
+    #[serde(flatten)]
+    pub preferences: Preferences,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            soniox_api_key: String::new(),
+            openai_api_key: String::new(),
+            shortcut: DEFAULT_SHORTCUT.to_string(),
+            cancel_shortcut: DEFAULT_CANCEL_SHORTCUT.to_string(),
+            undo_shortcut: String::new(),
+            stop_shortcut: String::new(),
+            history_shortcut: String::new(),
+            device_shortcut: String::new(),
+            endpoint_url: None,
+            replacements: ReplacementConfig::default(),
+            redaction: RedactionConfig::default(),
+            profiles: Vec::new(),
+            autostart: false,
+            usage_lifetime_seconds: 0.0,
+            preferences: Preferences::default(),
+        }
+    }
+}
+
+/// The app config directory, which holds `settings.json`
+pub fn config_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config dir: {}", e))
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(config_dir(app)?.join(SETTINGS_FILE))
+}
+
+/// Load settings from disk, falling back to defaults if missing or unreadable
+pub fn load(app: &AppHandle) -> Settings {
+    read(app).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        Settings::default()
+    })
+}
+
+// Read the settings file; defaults if there's none yet, an error if there is
+// one that can't be read or parsed
+fn read(app: &AppHandle) -> Result<Settings, String> {
+    read_from(&settings_path(app)?)
+}
+
+fn read_from(path: &Path) -> Result<Settings, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    parse(contents.as_deref())
+        .map_err(|e| format!("Malformed settings file {}: {}", path.display(), e))
+}
+
+fn parse(contents: Option<&str>) -> Result<Settings, String> {
+    let mut settings = match contents {
+        Some(contents) => serde_json::from_str::<Settings>(contents).map_err(|e| e.to_string())?,
+        None => Settings::default(),
+    };
+
+    // A whitespace-only key is the same as no key.
+    settings.soniox_api_key = settings.soniox_api_key.trim().to_string();
+    settings.openai_api_key = settings.openai_api_key.trim().to_string();
+    Ok(settings)
+}
+
+/// Write settings to disk (via a temp file so a crash can't truncate it)
+pub fn save(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    save_to(&settings_path(app)?, settings)
+}
+
+fn save_to(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
+    }
+
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json).map_err(|e| format!("Failed to write settings: {}", e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Load, modify and save settings in one step. Updates run one at a time, and
+/// a settings file that can't be read is left alone rather than replaced with
+/// defaults, which would lose the API keys stored in it.
+pub async fn update(app: &AppHandle, f: impl FnOnce(&mut Settings)) -> Result<(), String> {
+    let state = app.state::<crate::AppState>();
+    let _guard = state.settings_lock.lock().await;
+    update_locked(app, f)
+}
+
+/// Write `settings` over the file, whatever it holds now. Unlike `update`
+/// this also replaces a file that can't be read, so a reset can recover from
+/// one.
+pub async fn replace(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    let state = app.state::<crate::AppState>();
+    let _guard = state.settings_lock.lock().await;
+    save(app, settings)
+}
+
+/// `update` for a caller that already holds `AppState::settings_lock`
+pub fn update_locked(app: &AppHandle, f: impl FnOnce(&mut Settings)) -> Result<(), String> {
+    let mut settings = read(app).map_err(|e| format!("Not saving over it: {}", e))?;
+    f(&mut settings);
+    save(app, &settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_is_defaults() {
+        let settings = parse(None).unwrap();
+        assert_eq!(settings.shortcut, DEFAULT_SHORTCUT);
+    }
+
+    #[test]
+    fn malformed_file_is_an_error() {
+        assert!(parse(Some("{\"soniox_api_key\": \"key\",")).is_err());
+    }
+
+    #[test]
+    fn malformed_file_can_be_replaced() {
+        let path = std::env::temp_dir()
+            .join(format!("localwispr-settings-{}", std::process::id()))
+            .join(SETTINGS_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{\"soniox_api_key\": ").unwrap();
+        assert!(read_from(&path).is_err());
+
+        save_to(&path, &Settings::default()).unwrap();
+        let settings = read_from(&path).unwrap();
+        assert_eq!(settings.shortcut, DEFAULT_SHORTCUT);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn keys_are_trimmed() {
+        let settings = parse(Some("{\"soniox_api_key\": \" key \"}")).unwrap();
+        assert_eq!(settings.soniox_api_key, "key");
+    }
+}
@@ -1,17 +1,295 @@
-use crate::audio::samples_to_bytes;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::audio::{
+    start_capture, AudioChunk, AudioFormat, AudioReceiver, CaptureConfig, DEFAULT_SAMPLE_RATE,
+};
+use crate::backend::{
+    LiveText, SessionContext, SessionGeneration, TimedSegment, TranscriptionBackend,
+    TranscriptionError,
+};
+use crate::recordings::SessionRecorder;
+use futures_util::future::BoxFuture;
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
-use tokio::sync::Mutex;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{self, Message},
+    MaybeTlsStream, WebSocketStream,
+};
 
 // SONIOX real-time WebSocket endpoint (docs: /stt/api-reference/websocket-api)
-const SONIOX_WS_URL: &str = "wss://stt-rt.soniox.com/transcribe-websocket";
+pub const SONIOX_WS_URL: &str = "wss://stt-rt.soniox.com/transcribe-websocket";
 const SONIOX_MODEL: &str = "stt-rt-preview";
 
+/// Whether SONIOX's wording points at the API key rather than the request
+fn mentions_bad_key(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["api key", "api_key", "unauthorized", "authentication"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
+/// Map an error code reported in a SONIOX response
+fn response_error(code: u32, message: String) -> TranscriptionError {
+    let formatted = format!("SONIOX error {}: {}", code, message);
+    if (401..=403).contains(&code) || mentions_bad_key(&message) {
+        TranscriptionError::Auth(formatted)
+    } else {
+        TranscriptionError::Protocol(formatted)
+    }
+}
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsWrite = SplitSink<WsStream, Message>;
+type WsRead = SplitStream<WsStream>;
+
+// Reconnect attempts after an unexpected disconnect, with doubling backoff
+const MAX_RECONNECT_ATTEMPTS: u32 = 3;
+const RECONNECT_BACKOFF_MS: u64 = 500;
+// Seconds of audio kept while reconnecting; older samples are dropped
+const RECONNECT_BUFFER_SECS: usize = 10;
+
+/// Sample rates SONIOX accepts for raw PCM input
+pub const SUPPORTED_SAMPLE_RATES: &[u32] = &[8000, 16000, 24000, 44100, 48000];
+
+/// Reject rates SONIOX can't take, so capture and config never disagree
+pub fn validate_sample_rate(rate: u32) -> Result<u32, String> {
+    if SUPPORTED_SAMPLE_RATES.contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!(
+            "Unsupported sample rate {} Hz (supported: {:?})",
+            rate, SUPPORTED_SAMPLE_RATES
+        ))
+    }
+}
+
+// How long to wait after stopping for SONIOX to finalize the last words
+const FINAL_TOKENS_GRACE: Duration = Duration::from_millis(1500);
+
+// Silence sent right after the config to avoid first-audio timeouts, unless changed
+pub const DEFAULT_PRIMING_MS: u64 = 100;
+pub const MAX_PRIMING_MS: u64 = 1000;
+
+// Time without new tokens during speech before the popup shows "still
+// listening", unless changed (0 turns the notice off)
+pub const DEFAULT_STALL_NOTICE_MS: u64 = 1500;
+pub const MAX_STALL_NOTICE_MS: u64 = 10_000;
+// Longest the connection goes without sending anything before a keepalive
+// message, unless changed (0 = never). SONIOX closes a stream that's silent
+// for about 20 seconds, and proxies often sooner.
+pub const DEFAULT_KEEPALIVE_SECS: u64 = 5;
+pub const MAX_KEEPALIVE_SECS: u64 = 15;
+const KEEPALIVE_MESSAGE: &str = r#"{"type":"keepalive"}"#;
+// How long revised tentative words must hold still before the popup shows
+// them, unless changed (0 shows every revision)
+pub const DEFAULT_DISPLAY_SMOOTHING_MS: u64 = 250;
+pub const MAX_DISPLAY_SMOOTHING_MS: u64 = 2000;
+// A revision held this many smoothing windows goes out even if it's still changing
+const MAX_SMOOTHING_HOLDS: u32 = 3;
+// Input level that counts as speech for the stall notice
+const STALL_SPEECH_LEVEL: f32 = 0.05;
+
+// Upper bound for checking an API key, connection included
+const KEY_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// User-adjustable recognition options
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscriptionOptions {
+    pub model: String,
+    /// Language codes to steer recognition, e.g. `["ar", "en"]`
+    pub language_hints: Vec<String>,
+    /// Finalized words below this confidence (0-1) are typed as `[word]` so
+    /// they can be reviewed; None leaves the text untouched
+    pub low_confidence_threshold: Option<f32>,
+    /// Ask SONIOX to tell speakers apart and label each turn in this style;
+    /// None leaves diarization off
+    pub speaker_labels: Option<SpeakerFormat>,
+    /// Type a live translation instead of what was said; None transcribes
+    pub translation: Option<Translation>,
+}
+
+/// Real-time translation of the dictation into another language
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Translation {
+    /// Language being spoken, added to the language hints (None = any)
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Language to type, e.g. `"en"`
+    pub target: String,
+}
+
+/// How speaker turns are written out when diarization is on
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeakerFormat {
+    /// One line per turn: "Speaker 1: …"
+    #[default]
+    Lines,
+    /// Turns run together: "[Speaker 1] … [Speaker 2] …"
+    Inline,
+}
+
+impl Default for TranscriptionOptions {
+    fn default() -> Self {
+        TranscriptionOptions {
+            model: SONIOX_MODEL.to_string(),
+            language_hints: Vec::new(),
+            low_confidence_threshold: None,
+            speaker_labels: None,
+            translation: None,
+        }
+    }
+}
+
+impl TranscriptionOptions {
+    /// Trim values and reject blanks before they reach SONIOX
+    pub fn validated(self) -> Result<Self, String> {
+        let model = self.model.trim().to_string();
+        if model.is_empty() {
+            return Err("Model name must not be empty".to_string());
+        }
+
+        let mut language_hints = Vec::with_capacity(self.language_hints.len());
+        for hint in self.language_hints {
+            let hint = hint.trim().to_lowercase();
+            if hint.is_empty() {
+                return Err("Language hints must be non-empty language codes".to_string());
+            }
+            if !language_hints.contains(&hint) {
+                language_hints.push(hint);
+            }
+        }
+
+        if let Some(threshold) = self.low_confidence_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err("Confidence threshold must be between 0 and 1".to_string());
+            }
+        }
+
+        let translation = match self.translation {
+            Some(translation) => {
+                let target = translation.target.trim().to_lowercase();
+                if target.is_empty() {
+                    return Err("Translation target must be a language code".to_string());
+                }
+                let source = translation
+                    .source
+                    .map(|s| s.trim().to_lowercase())
+                    .filter(|s| !s.is_empty());
+                if source.as_deref() == Some(target.as_str()) {
+                    return Err("Translation source and target must differ".to_string());
+                }
+                Some(Translation { source, target })
+            }
+            None => None,
+        };
+
+        Ok(TranscriptionOptions {
+            model,
+            language_hints,
+            low_confidence_threshold: self.low_confidence_threshold,
+            speaker_labels: self.speaker_labels,
+            translation,
+        })
+    }
+}
+
+/// Stop automatically once speech is followed by enough quiet
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoStopConfig {
+    pub enabled: bool,
+    /// Trailing silence that ends the recording
+    pub silence_ms: u64,
+    /// Input level (0.0 - 1.0, as in `audio-level`) below which audio counts as silence
+    pub threshold: f32,
+}
+
+impl Default for AutoStopConfig {
+    fn default() -> Self {
+        AutoStopConfig {
+            enabled: false,
+            silence_ms: 3000,
+            threshold: 0.05,
+        }
+    }
+}
+
+/// Start a new sentence wherever the speaker paused, so long dictations don't
+/// come out as one unbroken paragraph. SONIOX only; speaker labels and
+/// low-confidence marking rebuild the text without these breaks.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PauseBreakConfig {
+    pub enabled: bool,
+    /// Quiet (below the auto-stop threshold) that counts as a sentence break
+    pub pause_ms: u64,
+}
+
+impl Default for PauseBreakConfig {
+    fn default() -> Self {
+        PauseBreakConfig {
+            enabled: false,
+            pause_ms: 1200,
+        }
+    }
+}
+
+// Slack when matching a detected pause to the words around it, since the
+// level meter and SONIOX's timestamps don't agree exactly
+const PAUSE_MATCH_TOLERANCE_MS: u64 = 250;
+
+/// Check that a custom endpoint is a usable WebSocket URL, returning it trimmed
+pub fn validate_endpoint(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    let parsed =
+        tauri::Url::parse(url).map_err(|e| format!("Invalid endpoint URL \"{}\": {}", url, e))?;
+    if !matches!(parsed.scheme(), "ws" | "wss") {
+        return Err(format!(
+            "Endpoint URL must start with wss:// or ws://, got \"{}\"",
+            url
+        ));
+    }
+    if parsed.host_str().is_none() {
+        return Err(format!("Endpoint URL \"{}\" has no host", url));
+    }
+    Ok(url.to_string())
+}
+
+/// Everything a session needs, captured when recording starts
+pub struct SessionConfig {
+    /// Key for the selected cloud backend (SONIOX or OpenAI)
+    pub api_key: String,
+    /// WebSocket endpoint; `SONIOX_WS_URL` unless overridden for a proxy or on-prem host
+    pub endpoint_url: String,
+    pub capture: CaptureConfig,
+    pub options: TranscriptionOptions,
+    pub auto_stop: AutoStopConfig,
+    pub pause_breaks: PauseBreakConfig,
+    /// Hard cap on recording length (None = unlimited)
+    pub max_duration: Option<Duration>,
+    /// Folder to save the session's audio to, if recordings are enabled
+    pub recording_dir: Option<PathBuf>,
+    /// Silence sent before the audio on each connection (0 = none)
+    pub priming_ms: u64,
+    /// Drop stray tokens SONIOX finalizes from the priming silence itself
+    pub trim_priming_artifacts: bool,
+    /// Speech without new tokens for this long emits `still-listening` (0 = off)
+    pub stall_notice_ms: u64,
+    /// Revised tentative words wait this long to settle before display (0 = off)
+    pub display_smoothing_ms: u64,
+    /// Send a keepalive when nothing went out for this long (0 = off)
+    pub keepalive_secs: u64,
+}
+
 // WebSocket configuration payload
 #[derive(Serialize)]
 struct SonioxConfig {
@@ -20,6 +298,19 @@ struct SonioxConfig {
     audio_format: String,
     sample_rate: u32,
     num_channels: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    language_hints: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    enable_speaker_diarization: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    translation: Option<SonioxTranslation>,
+}
+
+#[derive(Serialize)]
+struct SonioxTranslation {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    target_language: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -28,6 +319,368 @@ struct SonioxToken {
     text: String,
     #[serde(default)]
     is_final: bool,
+    // Position in the audio stream, in milliseconds
+    #[serde(default)]
+    start_ms: u64,
+    #[serde(default)]
+    end_ms: u64,
+    // 0-1; treated as certain when the service leaves it out
+    #[serde(default = "full_confidence")]
+    confidence: f32,
+    // Speaker number, only sent with diarization enabled
+    #[serde(default)]
+    speaker: Option<String>,
+    // "original" or "translation" when translating; absent otherwise
+    #[serde(default)]
+    translation_status: Option<String>,
+}
+
+fn full_confidence() -> f32 {
+    1.0
+}
+
+struct SpeakerTurn {
+    speaker: String,
+    text: String,
+}
+
+/// Finds pauses in the input level, on the same timeline as token times
+struct PauseTracker {
+    pause_ms: u64,
+    threshold: f32,
+    sample_rate: u32,
+    // Audio sent on the current stream, which is where its token times start
+    stream_samples: u64,
+    heard_speech: bool,
+    quiet_since_ms: Option<u64>,
+    // Start of each pause long enough to break at, oldest first
+    pauses: VecDeque<u64>,
+}
+
+impl PauseTracker {
+    fn new(config: PauseBreakConfig, threshold: f32, sample_rate: u32) -> Self {
+        PauseTracker {
+            pause_ms: config.pause_ms,
+            threshold,
+            sample_rate,
+            stream_samples: 0,
+            heard_speech: false,
+            quiet_since_ms: None,
+            pauses: VecDeque::new(),
+        }
+    }
+
+    fn on_audio(&mut self, samples: usize, level: f32, offset_ms: u64) {
+        let start_ms = offset_ms + self.stream_samples * 1000 / self.sample_rate as u64;
+        self.stream_samples += samples as u64;
+        let end_ms = offset_ms + self.stream_samples * 1000 / self.sample_rate as u64;
+
+        if level >= self.threshold {
+            self.heard_speech = true;
+            self.quiet_since_ms = None;
+        } else if self.heard_speech {
+            let since = *self.quiet_since_ms.get_or_insert(start_ms);
+            if end_ms - since >= self.pause_ms && self.pauses.back() != Some(&since) {
+                self.pauses.push_back(since);
+            }
+        }
+    }
+
+    /// Whether a pause fell between a word ending at `previous_end_ms` and one
+    /// starting at `start_ms`
+    fn take_pause(&mut self, previous_end_ms: u64, start_ms: u64) -> bool {
+        let earliest = previous_end_ms.saturating_sub(PAUSE_MATCH_TOLERANCE_MS);
+        while self.pauses.front().is_some_and(|&p| p < earliest) {
+            self.pauses.pop_front();
+        }
+        if self.pauses.front().is_some_and(|&p| p < start_ms) {
+            self.pauses.pop_front();
+            return true;
+        }
+        false
+    }
+
+    fn start_new_stream(&mut self) {
+        self.stream_samples = 0;
+        self.quiet_since_ms = None;
+    }
+}
+
+/// Text and timing gathered over a session
+#[derive(Default)]
+struct Transcript {
+    full_text: String,
+    // Tentative tail, which SONIOX resends in full with every response
+    non_final_text: String,
+    segments: Vec<TimedSegment>,
+    // Final text split at speaker changes, when SONIOX labels speakers
+    speaker_turns: Vec<SpeakerTurn>,
+    // Added to token times after a reconnect, whose clock restarts at zero
+    time_offset_ms: u64,
+    // Priming silence at the start of each stream, which isn't part of the recording
+    priming_ms: u64,
+    // Drop final tokens that come from the priming silence rather than speech
+    trim_priming_artifacts: bool,
+    // Set when pauses should start new sentences
+    pause_tracker: Option<PauseTracker>,
+    // Keep only translated tokens, dropping the original speech
+    translating: bool,
+    // Where newly finalized text goes for live typing, and how much of
+    // `full_text` has gone there already
+    live_text: Option<mpsc::UnboundedSender<LiveText>>,
+    live_sent: usize,
+    // What the popup was last sent: how much of `full_text` and `segments`,
+    // and the tentative tail in full, which stays short
+    emitted_final_len: usize,
+    emitted_words: usize,
+    emitted_tail: String,
+    last_emit: Option<Instant>,
+    // When the whole transcript last went out, and whether text the popup
+    // already has was rewritten since, which a delta can't express
+    last_resync: Option<Instant>,
+    needs_resync: bool,
+    // Set when revisions to tentative words should settle before display
+    smoothing: Option<Duration>,
+    // When the tentative words last changed, and since when a revision of
+    // the displayed ones has been held back
+    tail_changed_at: Option<Instant>,
+    held_since: Option<Instant>,
+}
+
+impl Transcript {
+    fn apply(&mut self, tokens: &[SonioxToken]) {
+        let previous_tail = std::mem::take(&mut self.non_final_text);
+
+        for token in tokens {
+            if self.translating && token.translation_status.as_deref() != Some("translation") {
+                continue;
+            }
+            if token.is_final {
+                if self.is_priming_artifact(token) {
+                    continue;
+                }
+                // Add final tokens to permanent collection
+                if self.pause_before(token) {
+                    self.push_sentence_break();
+                    self.full_text.push_str(token.text.trim_start());
+                } else {
+                    self.full_text.push_str(&token.text);
+                }
+                self.push_segment(token);
+                self.push_speaker_text(token);
+            } else {
+                // Collect non-final tokens separately
+                self.non_final_text.push_str(&token.text);
+            }
+        }
+        if self.non_final_text != previous_tail {
+            self.tail_changed_at = Some(Instant::now());
+        }
+        self.send_live_text();
+    }
+
+    /// Whether to keep showing the tentative words already on screen while
+    /// SONIOX is still revising them. New final text and words added to the
+    /// end go out right away; a revision waits until it has held still for
+    /// the smoothing window, or has been held a few windows already.
+    fn holds_revision(&mut self) -> bool {
+        let Some(window) = self.smoothing else {
+            return false;
+        };
+        if self.full_text.len() != self.emitted_final_len
+            || self.non_final_text.starts_with(&self.emitted_tail)
+        {
+            self.held_since = None;
+            return false;
+        }
+        let held_since = *self.held_since.get_or_insert_with(Instant::now);
+        let settled = self.tail_changed_at.is_none_or(|t| t.elapsed() >= window);
+        !settled && held_since.elapsed() < window * MAX_SMOOTHING_HOLDS
+    }
+
+    /// Whether a final token was heard in the priming silence rather than in
+    /// the recording: it ends before the real audio starts, or it's blank and
+    /// nothing has been said yet
+    fn is_priming_artifact(&self, token: &SonioxToken) -> bool {
+        if !self.trim_priming_artifacts || self.priming_ms == 0 || self.translating {
+            return false;
+        }
+        // Token times restart with each stream, which always opens with priming
+        token.end_ms <= self.priming_ms
+            || (token.text.trim().is_empty() && self.full_text.trim().is_empty())
+    }
+
+    /// Pass on final text that live typing hasn't seen yet
+    fn send_live_text(&mut self) {
+        let Some(live_text) = &self.live_text else {
+            return;
+        };
+        if let Some(delta) = self
+            .full_text
+            .get(self.live_sent..)
+            .filter(|d| !d.is_empty())
+        {
+            let _ = live_text.send(LiveText::Append(delta.to_string()));
+        }
+        self.live_sent = self.full_text.len();
+    }
+
+    /// Take back what live typing was sent past the end of `full_text`, after
+    /// a sentence break trimmed it
+    fn rewind_live_text(&mut self) {
+        let Some(live_text) = &self.live_text else {
+            return;
+        };
+        if self.live_sent > self.full_text.len() {
+            let _ = live_text.send(LiveText::Rewind(self.live_sent - self.full_text.len()));
+            self.live_sent = self.full_text.len();
+        }
+    }
+
+    /// Feed captured audio to the pause tracker, if sentence breaks are on
+    fn on_audio(&mut self, samples: usize, level: f32) {
+        if let Some(tracker) = self.pause_tracker.as_mut() {
+            tracker.on_audio(samples, level, self.time_offset_ms);
+        }
+    }
+
+    /// Whether the speaker paused right before this final token
+    fn pause_before(&mut self, token: &SonioxToken) -> bool {
+        let Some(tracker) = self.pause_tracker.as_mut() else {
+            return false;
+        };
+        // Translated tokens aren't timed against the audio
+        if self.translating {
+            return false;
+        }
+        if self.full_text.trim().is_empty() || !token.text.starts_with(char::is_whitespace) {
+            return false;
+        }
+        let start_ms = token.start_ms.saturating_sub(self.priming_ms) + self.time_offset_ms;
+        let previous_end_ms = self.segments.last().map_or(0, |s| s.end_ms);
+        tracker.take_pause(previous_end_ms, start_ms)
+    }
+
+    /// End the current line, adding a full stop unless SONIOX already
+    /// punctuated the sentence
+    fn push_sentence_break(&mut self) {
+        let trimmed = self.full_text.trim_end().len();
+        self.full_text.truncate(trimmed);
+        let punctuated = self
+            .full_text
+            .ends_with(['.', '!', '?', '؟', '…', ':', ';']);
+        // A comma where the speaker stopped was really the end of a sentence
+        if !punctuated && self.full_text.ends_with([',', '،']) {
+            self.full_text.pop();
+        }
+        // Trimming reached into text the popup or live typing already has
+        if self.full_text.len() < self.emitted_final_len {
+            self.needs_resync = true;
+        }
+        self.rewind_live_text();
+        if !punctuated {
+            self.full_text.push('.');
+        }
+        self.full_text.push('\n');
+    }
+
+    /// Record a final token's timing, joining sub-word pieces into whole words
+    fn push_segment(&mut self, token: &SonioxToken) {
+        let start_ms = token.start_ms.saturating_sub(self.priming_ms) + self.time_offset_ms;
+        let end_ms = token.end_ms.saturating_sub(self.priming_ms) + self.time_offset_ms;
+
+        let starts_word = token.text.starts_with(char::is_whitespace);
+        match self.segments.last_mut() {
+            Some(last) if !starts_word => {
+                last.text.push_str(&token.text);
+                last.end_ms = end_ms;
+                // A word is only as certain as its weakest piece
+                last.confidence = last.confidence.min(token.confidence);
+            }
+            _ => {
+                let text = token.text.trim_start();
+                if !text.is_empty() {
+                    self.segments.push(TimedSegment {
+                        text: text.to_string(),
+                        start_ms,
+                        end_ms,
+                        confidence: token.confidence,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Add a final token to the current speaker's turn, starting a new turn
+    /// when the speaker changes. Unlabelled tokens stay with the last speaker.
+    fn push_speaker_text(&mut self, token: &SonioxToken) {
+        let speaker = token.speaker.as_deref();
+        match self.speaker_turns.last_mut() {
+            Some(turn) if speaker.is_none() || speaker == Some(turn.speaker.as_str()) => {
+                turn.text.push_str(&token.text);
+            }
+            None if speaker.is_none() => {}
+            _ => self.speaker_turns.push(SpeakerTurn {
+                speaker: speaker.unwrap_or_default().to_string(),
+                text: token.text.clone(),
+            }),
+        }
+    }
+
+    /// Final text with each speaker turn labelled, or None if SONIOX didn't
+    /// label any speakers
+    fn labelled_text(&self, format: SpeakerFormat) -> Option<String> {
+        let turns: Vec<String> = self
+            .speaker_turns
+            .iter()
+            .filter(|turn| !turn.text.trim().is_empty())
+            .map(|turn| match format {
+                SpeakerFormat::Lines => format!("Speaker {}: {}", turn.speaker, turn.text.trim()),
+                SpeakerFormat::Inline => format!("[Speaker {}] {}", turn.speaker, turn.text.trim()),
+            })
+            .collect();
+        if turns.is_empty() {
+            return None;
+        }
+        let separator = match format {
+            SpeakerFormat::Lines => "\n",
+            SpeakerFormat::Inline => " ",
+        };
+        Some(turns.join(separator))
+    }
+
+    /// Final text with words below `threshold` wrapped in brackets.
+    /// Rebuilt from the word segments, so runs of whitespace become single spaces.
+    fn marked_text(&self, threshold: f32) -> Option<String> {
+        if !self.segments.iter().any(|s| s.confidence < threshold) {
+            return None;
+        }
+        let words: Vec<String> = self
+            .segments
+            .iter()
+            .map(|s| {
+                if s.confidence < threshold {
+                    format!("[{}]", s.text)
+                } else {
+                    s.text.clone()
+                }
+            })
+            .collect();
+        Some(words.join(" "))
+    }
+
+    /// Continue the timeline after reconnecting to a fresh stream. The new
+    /// stream never hears the audio behind the old one's tentative words, so
+    /// they're kept as final rather than lost.
+    fn start_new_stream(&mut self) {
+        self.time_offset_ms = self.segments.last().map_or(0, |s| s.end_ms);
+        let tail = std::mem::take(&mut self.non_final_text);
+        self.full_text.push_str(&tail);
+        self.send_live_text();
+        if let Some(tracker) = self.pause_tracker.as_mut() {
+            tracker.start_new_stream();
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -42,331 +695,879 @@ struct SonioxResponse {
     error_message: Option<String>,
 }
 
+/// What changed since the last `transcription` or `transcription-delta`
+/// event, sent as `transcription-delta` so long dictations don't resend
+/// everything on every word
 #[derive(Clone, Serialize)]
-struct TranscriptionEvent {
-    text: String,
-    is_final: bool,
+pub struct TranscriptionDelta {
+    /// Words confirmed since the last event, to append to the final text
+    pub final_text: String,
+    /// The tentative words, in full (they're only ever a few)
+    pub partial_text: String,
+    /// Replaces the word list from this index on; the last word can still
+    /// grow as sub-word pieces arrive
+    pub words_from: usize,
+    pub words: Vec<TimedSegment>,
 }
 
 #[derive(Clone, Serialize)]
-struct AudioLevelEvent {
-    level: f32, // 0.0 to 1.0
+pub struct TranscriptionEvent {
+    /// Everything heard so far (`final_text` followed by `partial_text`)
+    pub text: String,
+    /// Words the service has confirmed; these won't change
+    pub final_text: String,
+    /// Tentative words that may still be revised
+    pub partial_text: String,
+    /// Finalized words with their confidence, for highlighting doubtful ones
+    pub words: Vec<TimedSegment>,
+    pub is_final: bool,
 }
 
-// Audio data with level
-struct AudioChunk {
-    samples: Vec<i16>,
-    level: f32,
-}
+impl TranscriptionEvent {
+    /// In-progress update while recording
+    pub fn partial(final_text: &str, partial_text: &str, words: &[TimedSegment]) -> Self {
+        TranscriptionEvent {
+            text: format!("{}{}", final_text, partial_text),
+            final_text: final_text.to_string(),
+            partial_text: partial_text.to_string(),
+            words: words.to_vec(),
+            is_final: false,
+        }
+    }
 
-/// Calculate RMS audio level from samples (returns 0.0 to 1.0)
-fn calculate_audio_level(samples: &[i16]) -> f32 {
-    if samples.is_empty() {
-        return 0.0;
+    /// The finished transcript once the session ends
+    pub fn complete(text: &str, words: &[TimedSegment]) -> Self {
+        TranscriptionEvent {
+            text: text.to_string(),
+            final_text: text.to_string(),
+            partial_text: String::new(),
+            words: words.to_vec(),
+            is_final: true,
+        }
     }
+}
 
-    // Calculate RMS
-    let sum_squares: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
-    let rms = (sum_squares / samples.len() as f64).sqrt();
+/// Lifecycle of the SONIOX connection, sent as the `connection-state` event
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Opening the WebSocket (also while reconnecting)
+    Connecting,
+    /// Connected and configured, waiting for audio
+    Connected,
+    /// Audio is flowing
+    Streaming,
+    /// The session ended normally
+    Closed,
+    /// The session ended with an error
+    Error,
+}
 
-    // Normalize to 0-1 range (32767 is max for i16)
-    // Apply some scaling to make it more sensitive
-    let normalized = (rms / 32767.0) * 4.0;
-    normalized.min(1.0) as f32
+fn emit_connection_state(app: &AppHandle, state: ConnectionState) {
+    let _ = app.emit("connection-state", state);
 }
 
-/// Start transcription with SONIOX
-pub async fn start_transcription(
-    app: AppHandle,
-    api_key: String,
-    is_recording: Arc<Mutex<bool>>,
-    latest_transcription: Arc<Mutex<String>>,
-) -> Result<(), String> {
+#[derive(Clone, Serialize)]
+struct AudioLevelEvent {
+    level: f32, // 0.0 to 1.0
+}
+
+// Minimum interval between `audio-level` events (~20Hz)
+const LEVEL_EMIT_INTERVAL: Duration = Duration::from_millis(50);
+// Minimum interval between transcript updates (~15Hz)
+const TRANSCRIPT_EMIT_INTERVAL: Duration = Duration::from_millis(66);
+// Updates are deltas, with the whole transcript resent this often in case
+// the popup missed or mangled one
+const TRANSCRIPT_RESYNC_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Open a SONIOX WebSocket and send the session configuration
+async fn open_session(session: &SessionConfig) -> Result<(WsWrite, WsRead), TranscriptionError> {
+    // The declared rate comes from the capture config so it always matches
+    // what the resampler produces
+    open_stream(
+        &session.endpoint_url,
+        &session.api_key,
+        &session.options,
+        session.capture.sample_rate,
+        session.capture.audio_format,
+        session.priming_ms,
+    )
+    .await
+}
+
+async fn open_stream(
+    endpoint_url: &str,
+    api_key: &str,
+    options: &TranscriptionOptions,
+    sample_rate: u32,
+    audio_format: AudioFormat,
+    priming_ms: u64,
+) -> Result<(WsWrite, WsRead), TranscriptionError> {
     // Connect to SONIOX WebSocket
-    let (ws_stream, _) = connect_async(SONIOX_WS_URL)
-        .await
-        .map_err(|e| format!("Failed to connect to SONIOX: {}", e))?;
+    let (ws_stream, _) = connect_async(endpoint_url).await.map_err(|e| match e {
+        tungstenite::Error::Http(response) if matches!(response.status().as_u16(), 401 | 403) => {
+            TranscriptionError::Auth(format!("HTTP {}", response.status()))
+        }
+        e => TranscriptionError::Connect(e.to_string()),
+    })?;
 
-    println!("Connected to SONIOX WebSocket: {}", SONIOX_WS_URL);
+    println!("Connected to SONIOX WebSocket: {}", endpoint_url);
 
-    let (mut write, mut read) = ws_stream.split();
+    let (mut write, read) = ws_stream.split();
 
     // Send configuration
+    let mut language_hints = options.language_hints.clone();
+    let translation = options.translation.as_ref().map(|translation| {
+        if let Some(source) = &translation.source {
+            if !language_hints.contains(source) {
+                language_hints.push(source.clone());
+            }
+        }
+        SonioxTranslation {
+            kind: "one_way",
+            target_language: translation.target.clone(),
+        }
+    });
     let config = SonioxConfig {
-        api_key,
-        model: SONIOX_MODEL.to_string(),
-        audio_format: "pcm_s16le".to_string(),
-        sample_rate: 16000,
+        api_key: api_key.to_string(),
+        model: options.model.clone(),
+        audio_format: audio_format.as_str().to_string(),
+        sample_rate,
         num_channels: 1,
+        language_hints,
+        enable_speaker_diarization: options.speaker_labels.is_some(),
+        translation,
     };
 
-    let config_json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    let config_json =
+        serde_json::to_string(&config).map_err(|e| TranscriptionError::Protocol(e.to_string()))?;
     write
         .send(Message::Text(config_json))
         .await
-        .map_err(|e| format!("Failed to send config: {}", e))?;
+        .map_err(|e| TranscriptionError::WebSocket(format!("Failed to send config: {}", e)))?;
 
     println!(
-        "Sent SONIOX configuration (model={}, format={})",
-        SONIOX_MODEL, "pcm_s16le"
+        "Sent SONIOX configuration (model={}, format={}, rate={}, languages={:?})",
+        config.model, config.audio_format, config.sample_rate, config.language_hints
     );
 
     // Send a small silence frame to avoid first-audio timeouts.
-    let priming_silence = vec![0i16; 1600];
-    write
-        .send(Message::Binary(samples_to_bytes(&priming_silence)))
-        .await
-        .map_err(|e| format!("Failed to send priming audio: {}", e))?;
-
-    // Create channel for audio samples with level
-    let (audio_tx, mut audio_rx) = tokio::sync::mpsc::channel::<AudioChunk>(100);
-
-    // Use AtomicBool for thread-safe recording state check (std::thread can't use tokio runtime)
-    let audio_recording_flag = Arc::new(AtomicBool::new(true));
-    let audio_flag_clone = audio_recording_flag.clone();
-
-    // Start audio capture in a blocking thread
-    std::thread::spawn(move || {
-        let host = cpal::default_host();
-        let device = match host.default_input_device() {
-            Some(d) => d,
-            None => {
-                eprintln!("No input device available");
-                return;
-            }
-        };
+    let priming_samples = (sample_rate as u64 * priming_ms / 1000) as usize;
+    if priming_samples > 0 {
+        write
+            .send(Message::Binary(audio_format.silence(priming_samples)))
+            .await
+            .map_err(|e| {
+                TranscriptionError::WebSocket(format!("Failed to send priming audio: {}", e))
+            })?;
+    }
 
-        // Get the default input config (usually 44100 or 48000 Hz)
-        let supported_config = match device.default_input_config() {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("Failed to get default input config: {}", e);
-                return;
+    Ok((write, read))
+}
+
+/// Check an API key with a throwaway session: connect, send the config and
+/// priming audio, end the stream and see whether SONIOX accepts it.
+/// Ok(false) means the key was rejected; other failures are errors.
+pub async fn check_api_key(
+    endpoint_url: &str,
+    api_key: &str,
+    options: &TranscriptionOptions,
+) -> Result<bool, TranscriptionError> {
+    let check = async {
+        let (mut write, mut read) = open_stream(
+            endpoint_url,
+            api_key,
+            options,
+            DEFAULT_SAMPLE_RATE,
+            AudioFormat::default(),
+            DEFAULT_PRIMING_MS,
+        )
+        .await?;
+        // End of audio right away; a valid key gets a normal `finished` reply
+        write
+            .send(Message::Binary(Vec::new()))
+            .await
+            .map_err(|e| TranscriptionError::WebSocket(e.to_string()))?;
+
+        let outcome = loop {
+            match read.next().await {
+                Some(Ok(Message::Text(text))) => {
+                    let Ok(response) = serde_json::from_str::<SonioxResponse>(&text) else {
+                        continue;
+                    };
+                    if response.error_code.is_some() || response.error_message.is_some() {
+                        break Err(response_error(
+                            response.error_code.unwrap_or_default(),
+                            response.error_message.unwrap_or_default(),
+                        ));
+                    }
+                    if response.finished || !response.tokens.is_empty() {
+                        break Ok(());
+                    }
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    break match frame {
+                        Some(frame) if mentions_bad_key(&frame.reason) => {
+                            Err(TranscriptionError::Auth(frame.reason.to_string()))
+                        }
+                        // Closed without complaint after the config was read
+                        _ => Ok(()),
+                    };
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break Err(TranscriptionError::WebSocket(e.to_string())),
+                None => break Ok(()),
             }
         };
+        let _ = write.send(Message::Close(None)).await;
+        outcome
+    };
+
+    match tokio::time::timeout(KEY_CHECK_TIMEOUT, check).await {
+        Ok(Ok(())) => Ok(true),
+        Ok(Err(TranscriptionError::Auth(msg))) => {
+            println!("API key check failed: {}", msg);
+            Ok(false)
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(TranscriptionError::Connect(
+            "Timed out checking the API key".to_string(),
+        )),
+    }
+}
 
-        let sample_rate = supported_config.sample_rate().0;
-        let channels = supported_config.channels();
+/// Re-open the session after a dropped connection, buffering captured audio
+/// meanwhile so it can be replayed once connected
+async fn reconnect(
+    session: &SessionConfig,
+    audio_rx: &mut AudioReceiver,
+    pending_audio: &mut VecDeque<AudioChunk>,
+    is_recording: &AtomicBool,
+    generation: &SessionGeneration,
+) -> Result<(WsWrite, WsRead), TranscriptionError> {
+    let mut last_error = TranscriptionError::WebSocket("Connection lost".to_string());
+    let max_buffered = session.capture.sample_rate as usize * RECONNECT_BUFFER_SECS;
+
+    for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+        if !is_recording.load(Ordering::SeqCst) || !generation.is_current() {
+            break;
+        }
+
+        let backoff = Duration::from_millis(RECONNECT_BACKOFF_MS << (attempt - 1));
         println!(
-            "Using audio config: {} Hz, {} channels",
-            sample_rate, channels
+            "Reconnecting to SONIOX in {}ms (attempt {}/{})",
+            backoff.as_millis(),
+            attempt,
+            MAX_RECONNECT_ATTEMPTS
         );
 
-        let config = cpal::StreamConfig {
-            channels,
-            sample_rate: cpal::SampleRate(sample_rate),
-            buffer_size: cpal::BufferSize::Default,
+        let connect = async {
+            tokio::time::sleep(backoff).await;
+            open_session(session).await
         };
+        tokio::pin!(connect);
 
-        let tx = audio_tx;
-        let resample_ratio = sample_rate as f32 / 16000.0;
-
-        let stream = device.build_input_stream(
-            &config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                // Convert to mono if stereo
-                let mono_data: Vec<f32> = if channels > 1 {
-                    data.chunks(channels as usize)
-                        .map(|chunk| chunk.iter().sum::<f32>() / channels as f32)
-                        .collect()
-                } else {
-                    data.to_vec()
-                };
-
-                // Simple resampling: take every Nth sample to downsample to 16kHz
-                let resampled: Vec<i16> = mono_data
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(i, &sample)| {
-                        let target_idx = (i as f32 / resample_ratio) as usize;
-                        let current_idx = ((i as f32 - 1.0).max(0.0) / resample_ratio) as usize;
-                        if target_idx != current_idx || i == 0 {
-                            let clamped = sample.clamp(-1.0, 1.0);
-                            Some((clamped * 32767.0) as i16)
-                        } else {
-                            None
+        let result = loop {
+            tokio::select! {
+                result = &mut connect => break result,
+                Some(chunk) = audio_rx.recv() => {
+                    pending_audio.push_back(chunk);
+                    let mut buffered: usize =
+                        pending_audio.iter().map(|chunk| chunk.samples.len()).sum();
+                    while buffered > max_buffered {
+                        match pending_audio.pop_front() {
+                            Some(dropped) => buffered -= dropped.samples.len(),
+                            None => break,
                         }
-                    })
-                    .collect();
-
-                if !resampled.is_empty() {
-                    let level = calculate_audio_level(&resampled);
-                    let _ = tx.try_send(AudioChunk {
-                        samples: resampled,
-                        level,
-                    });
-                }
-            },
-            |err| eprintln!("Audio stream error: {}", err),
-            None,
-        );
-
-        match stream {
-            Ok(s) => {
-                if let Err(e) = s.play() {
-                    eprintln!("Failed to start audio stream: {}", e);
-                    return;
-                }
-                println!("Audio capture started");
-
-                // Keep the stream alive while recording
-                while audio_flag_clone.load(Ordering::Relaxed) {
-                    std::thread::sleep(std::time::Duration::from_millis(100));
+                    }
                 }
-                println!("Audio capture stopped");
             }
+        };
+
+        match result {
+            Ok(session) => return Ok(session),
+            // A rejected key won't fix itself by retrying.
+            Err(e @ TranscriptionError::Auth(_)) => return Err(e),
             Err(e) => {
-                eprintln!("Failed to create audio stream: {}", e);
+                eprintln!("Reconnect attempt {} failed: {}", attempt, e);
+                last_error = e;
             }
         }
-    });
+    }
 
-    // Spawn task to receive transcriptions
-    let app_clone = app.clone();
-    let is_recording_clone = is_recording.clone();
-    let transcription_clone = latest_transcription.clone();
+    Err(last_error)
+}
 
-    let receive_task = tokio::spawn(async move {
-        let mut full_text = String::new();
+/// Fold a batch of tokens into the transcript and publish the update
+async fn apply_tokens(
+    app: &AppHandle,
+    tokens: &[SonioxToken],
+    transcript: &mut Transcript,
+    latest_transcription: &Mutex<String>,
+) {
+    transcript.apply(tokens);
 
-        while let Some(msg) = read.next().await {
-            if !*is_recording_clone.lock().await {
-                break;
-            }
+    // Display = all final text so far + current non-final tokens
+    *latest_transcription.lock().await =
+        format!("{}{}", transcript.full_text, transcript.non_final_text);
 
-            match msg {
-                Ok(Message::Text(text)) => {
-                    match serde_json::from_str::<SonioxResponse>(&text) {
-                        Ok(response) => {
-                            // Check for errors
-                            if response.error_code.is_some() || response.error_message.is_some() {
-                                let code = response.error_code.unwrap_or_default();
-                                let msg = response
-                                    .error_message
-                                    .unwrap_or_else(|| "Unknown SONIOX error".to_string());
-                                let formatted = format!("SONIOX error {}: {}", code, msg);
-                                eprintln!("{}", formatted);
-                                let _ = app_clone.emit("transcription-error", formatted);
-                                break;
-                            }
+    emit_transcript(app, transcript);
+}
 
-                            // Check if finished
-                            if response.finished {
-                                println!("SONIOX transcription finished");
-                                break;
-                            }
+/// Send the popup what changed in the text, at most every
+/// `TRANSCRIPT_EMIT_INTERVAL`. Skipped updates go out on a later call.
+/// Usually that's a `transcription-delta`; the first update, one after final
+/// text was rewritten, and one every `TRANSCRIPT_RESYNC_INTERVAL` carry the
+/// whole transcript as `transcription` instead.
+fn emit_transcript(app: &AppHandle, transcript: &mut Transcript) {
+    if transcript
+        .last_emit
+        .is_some_and(|t| t.elapsed() < TRANSCRIPT_EMIT_INTERVAL)
+    {
+        return;
+    }
+    let unchanged = !transcript.needs_resync
+        && transcript.full_text.len() == transcript.emitted_final_len
+        && transcript.non_final_text == transcript.emitted_tail
+        && transcript.segments.len() == transcript.emitted_words;
+    if unchanged || transcript.holds_revision() {
+        return;
+    }
 
-                            // Process tokens - accumulate full text properly
-                            if !response.tokens.is_empty() {
-                                let mut non_final_text = String::new();
-
-                                for token in &response.tokens {
-                                    if token.is_final {
-                                        // Add final tokens to permanent collection
-                                        full_text.push_str(&token.text);
-                                    } else {
-                                        // Collect non-final tokens separately
-                                        non_final_text.push_str(&token.text);
-                                    }
-                                }
+    let resync = transcript.needs_resync
+        || transcript
+            .last_resync
+            .is_none_or(|t| t.elapsed() >= TRANSCRIPT_RESYNC_INTERVAL)
+        || !transcript
+            .full_text
+            .is_char_boundary(transcript.emitted_final_len);
+    if resync {
+        // Keep confirmed and tentative words apart so they can be styled differently
+        let _ = app.emit(
+            "transcription",
+            TranscriptionEvent::partial(
+                &transcript.full_text,
+                &transcript.non_final_text,
+                &transcript.segments,
+            ),
+        );
+        transcript.last_resync = Some(Instant::now());
+        transcript.needs_resync = false;
+    } else {
+        let words_from = transcript.emitted_words.saturating_sub(1);
+        let _ = app.emit(
+            "transcription-delta",
+            TranscriptionDelta {
+                final_text: transcript.full_text[transcript.emitted_final_len..].to_string(),
+                partial_text: transcript.non_final_text.clone(),
+                words_from,
+                words: transcript.segments[words_from..].to_vec(),
+            },
+        );
+    }
+    transcript.emitted_final_len = transcript.full_text.len();
+    transcript.emitted_words = transcript.segments.len();
+    transcript
+        .emitted_tail
+        .clone_from(&transcript.non_final_text);
+    transcript.held_since = None;
+    transcript.last_emit = Some(Instant::now());
+}
 
-                                // Display = all final text so far + current non-final tokens
-                                let display_text = format!("{}{}", full_text, non_final_text);
+/// Notices when speech is being captured but SONIOX hasn't sent tokens for a
+/// while, so the popup can show it's still listening instead of looking hung.
+/// Emits `still-listening` with `true` when that starts and `false` once
+/// tokens arrive again.
+struct StallWatch {
+    timeout: Option<Duration>,
+    last_token: Instant,
+    speech_since_token: bool,
+    stalled: bool,
+}
 
-                                // Store in shared state for direct access
-                                *transcription_clone.lock().await = display_text.clone();
+impl StallWatch {
+    fn new(timeout_ms: u64) -> Self {
+        StallWatch {
+            timeout: (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms)),
+            last_token: Instant::now(),
+            speech_since_token: false,
+            stalled: false,
+        }
+    }
 
-                                // Emit for popup display (full transcription)
-                                let _ = app_clone.emit(
-                                    "transcription",
-                                    TranscriptionEvent {
-                                        text: display_text,
-                                        is_final: false,
-                                    },
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to parse SONIOX response: {} - {}", e, text);
-                        }
+    fn on_audio(&mut self, level: f32) {
+        if level >= STALL_SPEECH_LEVEL {
+            self.speech_since_token = true;
+        }
+    }
+
+    /// Tokens arrived (or the stream restarted): start the wait over
+    fn reset(&mut self, app: &AppHandle) {
+        self.last_token = Instant::now();
+        self.speech_since_token = false;
+        if self.stalled {
+            self.stalled = false;
+            let _ = app.emit("still-listening", false);
+        }
+    }
+
+    fn check(&mut self, app: &AppHandle) {
+        let Some(timeout) = self.timeout else {
+            return;
+        };
+        if !self.stalled && self.speech_since_token && self.last_token.elapsed() >= timeout {
+            self.stalled = true;
+            let _ = app.emit("still-listening", true);
+        }
+    }
+}
+
+/// Flush queued audio, signal end-of-audio with an empty frame and collect
+/// tokens until SONIOX reports `finished` or `FINAL_TOKENS_GRACE` runs out.
+/// Returns how many samples were flushed.
+async fn drain_final_tokens(
+    app: &AppHandle,
+    write: &mut WsWrite,
+    read: &mut WsRead,
+    audio_rx: &mut AudioReceiver,
+    recorder: &mut Option<SessionRecorder>,
+    transcript: &mut Transcript,
+    latest_transcription: &Mutex<String>,
+) -> usize {
+    let mut sent = 0;
+    while let Ok(chunk) = audio_rx.try_recv() {
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.write(&chunk.samples);
+        }
+        if write.send(Message::Binary(chunk.to_bytes())).await.is_err() {
+            return sent;
+        }
+        sent += chunk.samples.len();
+    }
+    if write.send(Message::Binary(Vec::new())).await.is_err() {
+        return sent;
+    }
+
+    let deadline = tokio::time::sleep(FINAL_TOKENS_GRACE);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            msg = read.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let Ok(response) = serde_json::from_str::<SonioxResponse>(&text) else {
+                        continue;
+                    };
+                    if response.error_code.is_some() || response.error_message.is_some() {
+                        eprintln!("SONIOX error while finishing: {:?}", response.error_message);
+                        break;
                     }
-                }
-                Ok(Message::Close(frame)) => {
-                    if let Some(frame) = frame {
-                        println!(
-                            "SONIOX connection closed (code={}, reason={})",
-                            frame.code, frame.reason
-                        );
-                    } else {
-                        println!("SONIOX connection closed");
+                    if !response.tokens.is_empty() {
+                        apply_tokens(app, &response.tokens, transcript, latest_transcription).await;
+                    }
+                    if response.finished {
+                        println!("SONIOX transcription finished");
+                        break;
                     }
-                    break;
-                }
-                Err(e) => {
-                    eprintln!("WebSocket error: {}", e);
-                    break;
                 }
-                _ => {}
+                Some(Ok(_)) => {}
+                _ => break,
+            },
+            _ = &mut deadline => {
+                eprintln!("Timed out waiting for final SONIOX tokens");
+                break;
             }
         }
+    }
+    sent
+}
 
-        println!("SONIOX stream ended");
-        full_text
-    });
+/// Start transcription with SONIOX, returning the final transcript
+pub async fn start_transcription(
+    session: SessionConfig,
+    ctx: SessionContext,
+) -> Result<String, TranscriptionError> {
+    let SessionContext {
+        app,
+        is_recording,
+        latest_transcription,
+        cancelled,
+        segments,
+        generation,
+        source,
+        usage,
+        live_text,
+    } = ctx;
+
+    emit_connection_state(&app, ConnectionState::Connecting);
+    let (mut write, mut read) = match open_session(&session).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            emit_connection_state(&app, ConnectionState::Error);
+            return Err(e);
+        }
+    };
+    emit_connection_state(&app, ConnectionState::Connected);
+
+    // Start audio capture; without a microphone there's nothing to stream
+    let (capture, mut audio_rx) =
+        match start_capture(app.clone(), session.capture.clone(), &source).await {
+            Ok(capture) => capture,
+            Err(e) => {
+                let _ = write.send(Message::Close(None)).await;
+                emit_connection_state(&app, ConnectionState::Error);
+                return Err(e);
+            }
+        };
 
-    // Send audio data and emit audio levels
-    let is_recording_send = is_recording.clone();
-    let app_for_audio = app.clone();
+    // Optionally keep a copy of exactly what's sent, for debugging accuracy
+    let mut recorder = session.recording_dir.as_deref().and_then(|dir| {
+        SessionRecorder::create(dir, session.capture.sample_rate)
+            .map_err(|e| eprintln!("Not saving this recording: {}", e))
+            .ok()
+    });
 
+    // Stream audio up and transcriptions down until recording stops
+    let mut transcript = Transcript {
+        priming_ms: session.priming_ms,
+        trim_priming_artifacts: session.trim_priming_artifacts,
+        smoothing: (session.display_smoothing_ms > 0)
+            .then(|| Duration::from_millis(session.display_smoothing_ms)),
+        live_text,
+        translating: session.options.translation.is_some(),
+        pause_tracker: session.pause_breaks.enabled.then(|| {
+            PauseTracker::new(
+                session.pause_breaks,
+                session.auto_stop.threshold,
+                session.capture.sample_rate,
+            )
+        }),
+        ..Transcript::default()
+    };
+    let mut server_finished = false;
+    let mut pending_audio: VecDeque<AudioChunk> = VecDeque::new();
     let mut sent_audio_frame = false;
-    let mut level_emit_counter = 0u32;
+    let mut last_level_emit: Option<Instant> = None;
+    // Auto-stop only arms after speech is heard, so initial quiet doesn't count.
+    let mut heard_speech = false;
+    let mut silence_since: Option<Instant> = None;
+    let mut auto_stop_sent = false;
+    let started_at = Instant::now();
+    let mut stall_watch = StallWatch::new(session.stall_notice_ms);
+    let keepalive =
+        (session.keepalive_secs > 0).then(|| Duration::from_secs(session.keepalive_secs));
+    let mut last_sent = Instant::now();
 
-    while *is_recording_send.lock().await {
-        tokio::select! {
-            Some(chunk) = audio_rx.recv() => {
-                let bytes = samples_to_bytes(&chunk.samples);
-                if let Err(e) = write.send(Message::Binary(bytes)).await {
-                    eprintln!("Failed to send audio: {}", e);
-                    break;
-                }
+    let result = 'session: loop {
+        // Reason the connection dropped unexpectedly (None = session over)
+        let disconnect: Option<String> = loop {
+            // A newer session owns the mic now; don't send anything more
+            if !is_recording.load(Ordering::SeqCst) || !generation.is_current() {
+                break None;
+            }
 
-                // Emit audio level every few chunks to avoid flooding
-                level_emit_counter += 1;
-                if level_emit_counter % 2 == 0 {
-                    let _ = app_for_audio.emit("audio-level", AudioLevelEvent { level: chunk.level });
+            if !auto_stop_sent
+                && session
+                    .max_duration
+                    .is_some_and(|max| started_at.elapsed() >= max)
+            {
+                println!("Maximum recording duration reached; requesting auto-stop");
+                auto_stop_sent = true;
+                let _ = app.emit("auto-stop", "max-duration");
+            }
+
+            // Catch up on an update held back by the throttle
+            emit_transcript(&app, &mut transcript);
+            stall_watch.check(&app);
+
+            // Audio normally keeps the connection busy; while none is going
+            // out (a lost device, a file waiting), say the stream is still alive
+            if keepalive.is_some_and(|interval| last_sent.elapsed() >= interval) {
+                if let Err(e) = write.send(Message::Text(KEEPALIVE_MESSAGE.into())).await {
+                    break Some(format!("Failed to send keepalive: {}", e));
                 }
+                last_sent = Instant::now();
+            }
+
+            tokio::select! {
+                Some(chunk) = audio_rx.recv() => {
+                    if !generation.is_current() {
+                        break None;
+                    }
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.write(&chunk.samples);
+                    }
+                    let bytes = chunk.to_bytes();
+                    if let Err(e) = write.send(Message::Binary(bytes)).await {
+                        break Some(format!("Failed to send audio: {}", e));
+                    }
+                    last_sent = Instant::now();
+                    usage.record(chunk.samples.len(), session.capture.sample_rate);
+
+                    // Throttle audio level events to avoid flooding the UI
+                    if last_level_emit.is_none_or(|t| t.elapsed() >= LEVEL_EMIT_INTERVAL) {
+                        last_level_emit = Some(Instant::now());
+                        let _ = app.emit("audio-level", AudioLevelEvent { level: chunk.level });
+                    }
+
+                    if !sent_audio_frame {
+                        sent_audio_frame = true;
+                        emit_connection_state(&app, ConnectionState::Streaming);
+                        println!("Sent first audio frame");
+                    }
 
-                if !sent_audio_frame {
-                    sent_audio_frame = true;
-                    println!("Sent first audio frame");
+                    stall_watch.on_audio(chunk.level);
+                    transcript.on_audio(chunk.samples.len(), chunk.level);
+
+                    let auto_stop = &session.auto_stop;
+                    if auto_stop.enabled && !auto_stop_sent {
+                        if chunk.level >= auto_stop.threshold {
+                            heard_speech = true;
+                            silence_since = None;
+                        } else if heard_speech {
+                            let since = *silence_since.get_or_insert_with(Instant::now);
+                            if since.elapsed() >= Duration::from_millis(auto_stop.silence_ms) {
+                                println!("Trailing silence detected; requesting auto-stop");
+                                auto_stop_sent = true;
+                                let _ = app.emit("auto-stop", "silence");
+                            }
+                        }
+                    }
                 }
+                msg = read.next() => match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        match serde_json::from_str::<SonioxResponse>(&text) {
+                            Ok(response) => {
+                                // Check for errors
+                                if response.error_code.is_some() || response.error_message.is_some() {
+                                    let code = response.error_code.unwrap_or_default();
+                                    let msg = response
+                                        .error_message
+                                        .unwrap_or_else(|| "Unknown SONIOX error".to_string());
+                                    break 'session Err(response_error(code, msg));
+                                }
+
+                                // Check if finished
+                                if response.finished {
+                                    println!("SONIOX transcription finished");
+                                    server_finished = true;
+                                    break None;
+                                }
+
+                                // Process tokens - accumulate full text properly
+                                if !response.tokens.is_empty() {
+                                    stall_watch.reset(&app);
+                                    apply_tokens(
+                                        &app,
+                                        &response.tokens,
+                                        &mut transcript,
+                                        &latest_transcription,
+                                    )
+                                    .await;
+                                }
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to parse SONIOX response: {} - {}", e, text);
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(frame))) => {
+                        // A rejected key can arrive as a close frame instead of an error message
+                        if let Some(frame) = frame.as_ref().filter(|f| mentions_bad_key(&f.reason)) {
+                            break 'session Err(TranscriptionError::Auth(format!(
+                                "connection closed: {}",
+                                frame.reason
+                            )));
+                        }
+                        let reason = match frame {
+                            Some(frame) => format!(
+                                "SONIOX connection closed (code={}, reason={})",
+                                frame.code, frame.reason
+                            ),
+                            None => "SONIOX connection closed".to_string(),
+                        };
+                        println!("{}", reason);
+                        // Only unexpected while still recording; the check below decides.
+                        break Some(reason);
+                    }
+                    Some(Err(e)) => break Some(e.to_string()),
+                    None => break Some("Connection ended unexpectedly".to_string()),
+                    Some(Ok(_)) => {}
+                },
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {}
             }
-            _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
-                if !*is_recording_send.lock().await {
-                    break;
+        };
+
+        let Some(reason) = disconnect else {
+            break Ok(());
+        };
+        if !is_recording.load(Ordering::SeqCst) || !generation.is_current() {
+            break Ok(());
+        }
+
+        eprintln!("SONIOX connection lost: {}", reason);
+        emit_connection_state(&app, ConnectionState::Connecting);
+        match reconnect(
+            &session,
+            &mut audio_rx,
+            &mut pending_audio,
+            &is_recording,
+            &generation,
+        )
+        .await
+        {
+            Ok((new_write, new_read)) => {
+                write = new_write;
+                read = new_read;
+                transcript.start_new_stream();
+                last_sent = Instant::now();
+                stall_watch.reset(&app);
+                emit_connection_state(&app, ConnectionState::Connected);
+                // Report streaming again once live audio flows on the new connection
+                sent_audio_frame = false;
+                println!(
+                    "Reconnected to SONIOX; replaying {} buffered chunks",
+                    pending_audio.len()
+                );
+                while let Some(chunk) = pending_audio.pop_front() {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.write(&chunk.samples);
+                    }
+                    if let Err(e) = write.send(Message::Binary(chunk.to_bytes())).await {
+                        eprintln!("Failed to replay buffered audio: {}", e);
+                        break;
+                    }
+                    transcript.on_audio(chunk.samples.len(), chunk.level);
+                    usage.record(chunk.samples.len(), session.capture.sample_rate);
                 }
             }
+            Err(e) => break Err(e),
         }
-    }
-    println!("Recording flag set to false; stopping audio send");
+    };
+    println!("SONIOX stream ended");
 
     // Stop the audio capture thread
-    audio_recording_flag.store(false, Ordering::Relaxed);
+    capture.stop();
+
+    // The last words are usually still non-final when recording stops; wait
+    // briefly for SONIOX to finalize them instead of dropping them.
+    let cancelled_before_drain = cancelled.load(Ordering::Relaxed) || !generation.is_current();
+    if result.is_ok() && !server_finished && !cancelled_before_drain {
+        let flushed = drain_final_tokens(
+            &app,
+            &mut write,
+            &mut read,
+            &mut audio_rx,
+            &mut recorder,
+            &mut transcript,
+            &latest_transcription,
+        )
+        .await;
+        usage.record(flushed, session.capture.sample_rate);
+    }
 
     // Close WebSocket
     let _ = write.send(Message::Close(None)).await;
+    if let Some(recorder) = recorder {
+        recorder.finish();
+    }
+    emit_connection_state(
+        &app,
+        if result.is_ok() {
+            ConnectionState::Closed
+        } else {
+            ConnectionState::Error
+        },
+    );
+
+    result?;
+    // Checked again, since a cancel can arrive while the last words are flushed
+    if cancelled.load(Ordering::Relaxed) || !generation.is_current() {
+        return Err(TranscriptionError::Cancelled);
+    }
 
-    // Wait for receive task
-    let final_text = receive_task.await.unwrap_or_default();
+    // Speaker labels take precedence over low-confidence marking
+    let mut full_text = session
+        .options
+        .speaker_labels
+        .and_then(|format| transcript.labelled_text(format))
+        .or_else(|| {
+            session
+                .options
+                .low_confidence_threshold
+                .and_then(|threshold| transcript.marked_text(threshold))
+        })
+        .unwrap_or_else(|| transcript.full_text.clone());
+    // Whatever never got finalized is still better than losing it
+    full_text.push_str(&transcript.non_final_text);
 
     // Emit final transcription
+    let final_text = full_text.trim().to_string();
     let _ = app.emit(
         "transcription-complete",
-        TranscriptionEvent {
-            text: final_text.trim().to_string(),
-            is_final: true,
-        },
+        TranscriptionEvent::complete(&final_text, &transcript.segments),
     );
+    *segments.lock().await = transcript.segments;
 
-    Ok(())
+    Ok(final_text)
+}
+
+/// SONIOX real-time streaming backend
+pub struct SonioxBackend {
+    session: SessionConfig,
+}
+
+impl SonioxBackend {
+    pub fn new(session: SessionConfig) -> Self {
+        SonioxBackend { session }
+    }
+}
+
+impl TranscriptionBackend for SonioxBackend {
+    fn transcribe_stream(
+        self: Box<Self>,
+        ctx: SessionContext,
+    ) -> BoxFuture<'static, Result<String, TranscriptionError>> {
+        Box::pin(start_transcription(self.session, ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentence_break_takes_back_a_sent_comma() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut transcript = Transcript {
+            live_text: Some(tx),
+            ..Transcript::default()
+        };
+        transcript.full_text.push_str("hello,");
+        transcript.send_live_text();
+        transcript.push_sentence_break();
+        transcript.send_live_text();
+
+        let mut sent = Vec::new();
+        while let Ok(change) = rx.try_recv() {
+            sent.push(change);
+        }
+        assert_eq!(
+            sent,
+            vec![
+                LiveText::Append("hello,".to_string()),
+                LiveText::Rewind(1),
+                LiveText::Append(".\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reconnect_keeps_tentative_words() {
+        let mut transcript = Transcript::default();
+        transcript.full_text.push_str("Hello");
+        transcript.non_final_text.push_str(" there");
+        transcript.start_new_stream();
+        assert_eq!(transcript.full_text, "Hello there");
+        assert!(transcript.non_final_text.is_empty());
+    }
 }
@@ -0,0 +1,203 @@
+use crate::audio::{start_capture, CaptureConfig, DEFAULT_SAMPLE_RATE};
+use crate::backend::{SessionContext, TranscriptionBackend, TranscriptionError};
+use crate::recordings::SessionRecorder;
+use crate::soniox::{SessionConfig, TranscriptionEvent};
+use futures_util::future::BoxFuture;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::task::JoinHandle;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+// Audio is transcribed in windows of this many samples (8s at 16kHz)
+const CHUNK_SAMPLES: usize = 16000 * 8;
+
+/// Offline transcription with whisper.cpp.
+///
+/// Whisper isn't a streaming model, so audio is buffered and transcribed in
+/// fixed windows while recording, with the remainder handled on stop.
+pub struct WhisperLocal {
+    session: SessionConfig,
+    model_path: String,
+}
+
+impl WhisperLocal {
+    pub fn new(session: SessionConfig, model_path: String) -> Self {
+        WhisperLocal {
+            session,
+            model_path,
+        }
+    }
+
+    async fn run(self, ctx: SessionContext) -> Result<String, TranscriptionError> {
+        // Loading a ggml model takes a while; keep it off the async runtime.
+        let model_path = self.model_path.clone();
+        let context = tokio::task::spawn_blocking(move || {
+            WhisperContext::new_with_params(&model_path, WhisperContextParameters::default())
+        })
+        .await
+        .map_err(|e| TranscriptionError::Backend(e.to_string()))?
+        .map_err(|e| TranscriptionError::Backend(format!("Failed to load Whisper model: {}", e)))?;
+        let context = Arc::new(context);
+        println!("Loaded Whisper model: {}", self.model_path);
+
+        // Whisper takes a single language; use the first hint or auto-detect.
+        let language = self.session.options.language_hints.first().cloned();
+
+        // Whisper models only take 16kHz input, whatever rate is configured
+        let capture_config = CaptureConfig {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            ..self.session.capture.clone()
+        };
+        let (capture, mut audio_rx) =
+            start_capture(ctx.app.clone(), capture_config, &ctx.source).await?;
+
+        let mut recorder = self.session.recording_dir.as_deref().and_then(|dir| {
+            SessionRecorder::create(dir, DEFAULT_SAMPLE_RATE)
+                .map_err(|e| eprintln!("Not saving this recording: {}", e))
+                .ok()
+        });
+
+        let mut pending: Vec<f32> = Vec::new();
+        let mut full_text = String::new();
+        // The window being transcribed. It runs as its own task so audio keeps
+        // being drained meanwhile; the capture queue would drop it otherwise.
+        let mut transcribing: Option<JoinHandle<Result<String, TranscriptionError>>> = None;
+
+        let result = loop {
+            let superseded = !ctx.generation.is_current();
+            let recording = ctx.is_recording.load(Ordering::SeqCst) && !superseded;
+            let mut finished = None;
+            if recording {
+                tokio::select! {
+                    Some(chunk) = audio_rx.recv() => {
+                        if let Some(recorder) = recorder.as_mut() {
+                            recorder.write(&chunk.samples);
+                        }
+                        pending.extend(chunk.samples.iter().map(|&s| s as f32 / 32768.0));
+                    }
+                    result = async { transcribing.as_mut().unwrap().await }, if transcribing.is_some() => {
+                        finished = Some(result);
+                    }
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                }
+            } else if ctx.cancelled.load(Ordering::Relaxed) || superseded {
+                break Err(TranscriptionError::Cancelled);
+            } else {
+                // Stop capturing and pick up whatever is still queued.
+                capture.stop();
+                while let Ok(chunk) = audio_rx.try_recv() {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.write(&chunk.samples);
+                    }
+                    pending.extend(chunk.samples.iter().map(|&s| s as f32 / 32768.0));
+                }
+                // The window in progress comes before the remainder
+                if let Some(task) = transcribing.as_mut() {
+                    finished = Some(task.await);
+                }
+            }
+
+            if let Some(result) = finished {
+                transcribing = None;
+                let result = result.map_err(|e| TranscriptionError::Backend(e.to_string()));
+                match result.and_then(|text| text) {
+                    Ok(text) if !text.is_empty() => {
+                        if !full_text.is_empty() {
+                            full_text.push(' ');
+                        }
+                        full_text.push_str(&text);
+
+                        *ctx.latest_transcription.lock().await = full_text.clone();
+                        let _ = ctx.app.emit(
+                            "transcription",
+                            TranscriptionEvent::partial(&full_text, "", &[]),
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => break Err(e),
+                }
+            }
+
+            // Audio that arrived while the model was busy joins the next window
+            if transcribing.is_none()
+                && (pending.len() >= CHUNK_SAMPLES || (!recording && !pending.is_empty()))
+            {
+                let samples = std::mem::take(&mut pending);
+                transcribing = Some(tokio::spawn(transcribe_chunk(
+                    context.clone(),
+                    samples,
+                    language.clone(),
+                )));
+            }
+
+            if !recording && transcribing.is_none() {
+                break Ok(());
+            }
+        };
+        // Whatever the model is still working on is no longer wanted
+        if let Some(task) = transcribing {
+            task.abort();
+        }
+        capture.stop();
+        if let Some(recorder) = recorder {
+            recorder.finish();
+        }
+        result?;
+        if ctx.cancelled.load(Ordering::Relaxed) || !ctx.generation.is_current() {
+            return Err(TranscriptionError::Cancelled);
+        }
+
+        let final_text = full_text.trim().to_string();
+        let _ = ctx.app.emit(
+            "transcription-complete",
+            TranscriptionEvent::complete(&final_text, &[]),
+        );
+
+        Ok(final_text)
+    }
+}
+
+impl TranscriptionBackend for WhisperLocal {
+    fn transcribe_stream(
+        self: Box<Self>,
+        ctx: SessionContext,
+    ) -> BoxFuture<'static, Result<String, TranscriptionError>> {
+        Box::pin(self.run(ctx))
+    }
+}
+
+/// Run the model over one window of 16kHz mono audio
+async fn transcribe_chunk(
+    context: Arc<WhisperContext>,
+    samples: Vec<f32>,
+    language: Option<String>,
+) -> Result<String, TranscriptionError> {
+    tokio::task::spawn_blocking(move || {
+        let mut state = context
+            .create_state()
+            .map_err(|e| format!("Failed to create Whisper state: {}", e))?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(language.as_deref().unwrap_or("auto")));
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+
+        state
+            .full(params, &samples)
+            .map_err(|e| format!("Whisper transcription failed: {}", e))?;
+
+        let segments = state.full_n_segments().map_err(|e| e.to_string())?;
+        let mut text = String::new();
+        for i in 0..segments {
+            let segment = state.full_get_segment_text(i).map_err(|e| e.to_string())?;
+            text.push_str(&segment);
+        }
+        Ok(text.trim().to_string())
+    })
+    .await
+    .map_err(|e| TranscriptionError::Backend(e.to_string()))?
+    .map_err(TranscriptionError::Backend)
+}
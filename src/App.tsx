@@ -2,18 +2,57 @@ import { useEffect, useState, useCallback, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow, LogicalSize } from "@tauri-apps/api/window";
-import RecordingPopup from "./components/RecordingPopup";
+import RecordingPopup, { PermissionKind, TranscriptWord } from "./components/RecordingPopup";
 import ApiKeySetup from "./components/ApiKeySetup";
 
 interface TranscriptionEvent {
   text: string;
+  final_text: string;
+  partial_text: string;
+  words: TranscriptWord[];
   is_final: boolean;
 }
 
+// Changes since the last transcript update; see `transcription-delta`
+interface TranscriptionDeltaEvent {
+  final_text: string;
+  partial_text: string;
+  words_from: number;
+  words: TranscriptWord[];
+}
+
 interface RecordingStateEvent {
   is_recording: boolean;
 }
 
+interface TranscriptionErrorEvent {
+  kind:
+    | "connect"
+    | "auth"
+    | "no_input_device"
+    | "audio"
+    | "protocol"
+    | "websocket"
+    | "backend";
+  message: string;
+}
+
+type ConnectionState = "connecting" | "connected" | "streaming" | "closed" | "error";
+
+interface RecordingElapsedEvent {
+  seconds: number;
+}
+
+interface DeviceChangedEvent {
+  device: string;
+}
+
+interface HistoryCandidateEvent {
+  text: string;
+  position: number;
+  count: number;
+}
+
 interface AudioLevelEvent {
   level: number;
 }
@@ -22,11 +61,33 @@ function App() {
   const appWindow = getCurrentWindow();
   const [isRecording, setIsRecording] = useState(false);
   const [transcription, setTranscription] = useState("");
+  // Tentative tail of `transcription` that may still be revised
+  const [partialText, setPartialText] = useState("");
+  // Confirmed words with confidence scores, for underlining doubtful ones
+  const [words, setWords] = useState<TranscriptWord[]>([]);
   const [error, setError] = useState<string | null>(null);
   const [apiKeySet, setApiKeySet] = useState(false);
+  // The stored key was rejected by SONIOX; the setup screen explains why
+  const [apiKeyRejected, setApiKeyRejected] = useState(false);
   const [audioLevel, setAudioLevel] = useState(0);
+  const [elapsedSeconds, setElapsedSeconds] = useState(0);
+  // Finished text held for review before typing (confirm-before-type mode)
+  const [reviewText, setReviewText] = useState<string | null>(null);
+  // Set while a non-streaming backend transcribes the finished recording
+  const [isTranscribing, setIsTranscribing] = useState(false);
+  // Speech is coming in but SONIOX hasn't sent new text for a while
+  const [stillListening, setStillListening] = useState(false);
+  // Milliseconds until the dictation is typed, while the countdown runs
+  const [typingCountdownMs, setTypingCountdownMs] = useState<number | null>(null);
+  // A macOS permission (Accessibility for typing, or the microphone) is missing
+  const [permissionRequired, setPermissionRequired] = useState<PermissionKind | null>(null);
+  // Microphone the device shortcut just switched to, shown briefly
+  const [deviceNotice, setDeviceNotice] = useState<string | null>(null);
+  const [connectionState, setConnectionState] = useState<ConnectionState>("closed");
   const lastRecordingStartRef = useRef<number>(0);
   const transcriptionRef = useRef<string>("");
+  // Confirmed part of the live transcript, which deltas append to
+  const finalTextRef = useRef<string>("");
   const lastTypedTextRef = useRef<string>(""); // Track what we've already typed
   const lastWindowSizeRef = useRef<{ width: number; height: number } | null>(null);
 
@@ -35,6 +96,8 @@ function App() {
     try {
       setError(null);
       setTranscription("");
+      setPartialText("");
+      setWords([]);
       transcriptionRef.current = "";
       lastTypedTextRef.current = "";
       await invoke("start_recording");
@@ -44,14 +107,33 @@ function App() {
   }, []);
 
   // Handle stopping recording
+  // Resolves to true when the backend holds the text for review instead
   const stopRecording = useCallback(async (reason = "ui") => {
     try {
-      await invoke("stop_recording", { reason });
+      return await invoke<boolean>("stop_recording", { reason });
     } catch (e) {
       setError(String(e));
+      return false;
     }
   }, []);
 
+  // Type the reviewed text, or drop it
+  const confirmReview = useCallback(async (text: string) => {
+    setReviewText(null);
+    try {
+      await invoke("confirm_and_type", { text });
+    } catch (e) {
+      setError(String(e));
+    }
+  }, []);
+
+  const discardReview = useCallback(() => {
+    setReviewText(null);
+    void invoke("cancel_and_hide", { reason: "ui:discard-review" }).catch((e) =>
+      setError(String(e))
+    );
+  }, []);
+
   // Handle completing transcription (type text and hide window)
   const completeTranscription = useCallback(async () => {
     const finalText = transcriptionRef.current.trim();
@@ -67,6 +149,8 @@ function App() {
       await invoke("hide_window");
     }
     setTranscription("");
+    setPartialText("");
+    setWords([]);
     transcriptionRef.current = "";
     lastTypedTextRef.current = "";
   }, []);
@@ -76,9 +160,7 @@ function App() {
     try {
       await invoke("set_api_key", { apiKey });
       setApiKeySet(true);
-      // Store in localStorage for persistence
-      localStorage.setItem("soniox_api_key_set", "true");
-      localStorage.setItem("soniox_api_key", apiKey);
+      setApiKeyRejected(false);
       // Auto-start recording after setting API key
       setTimeout(() => {
         startRecording();
@@ -88,15 +170,46 @@ function App() {
     }
   }, [startRecording]);
 
-  // Check for stored API key on mount
+  // Check typing and microphone permissions up front so a first-run Mac user
+  // is told how to grant them before the first dictation fails. The
+  // microphone check also brings up the OS prompt if it was never shown.
   useEffect(() => {
-    const storedKey = localStorage.getItem("soniox_api_key");
-    if (storedKey) {
-      invoke("set_api_key", { apiKey: storedKey }).then(() => {
+    invoke<boolean>("check_accessibility_permission").then((granted) => {
+      if (!granted) {
+        setPermissionRequired("accessibility");
+        void invoke("show_window");
+      }
+    });
+    invoke<string>("check_microphone_permission").then((status) => {
+      if (status === "denied" || status === "restricted") {
+        setPermissionRequired("microphone");
+        void invoke("show_window");
+      }
+    });
+  }, []);
+
+  const openPermissionSettings = useCallback(() => {
+    const command =
+      permissionRequired === "microphone" ? "open_microphone_settings" : "open_accessibility_settings";
+    void invoke(command).catch((e) => setError(String(e)));
+  }, [permissionRequired]);
+
+  // Check for a persisted API key on mount (the backend stores it on disk)
+  useEffect(() => {
+    invoke<boolean>("has_api_key").then(async (hasKey) => {
+      if (hasKey) {
         setApiKeySet(true);
-        // Don't auto-start - let the shortcut handle it
-      });
-    }
+        return;
+      }
+      // Migrate keys saved by older versions in localStorage
+      const storedKey = localStorage.getItem("soniox_api_key");
+      if (storedKey && storedKey.trim()) {
+        await invoke("set_api_key", { apiKey: storedKey });
+        setApiKeySet(true);
+      }
+      localStorage.removeItem("soniox_api_key");
+      localStorage.removeItem("soniox_api_key_set");
+    });
   }, []);
 
   // Set up event listeners
@@ -105,7 +218,23 @@ function App() {
       "transcription",
       (event) => {
         setTranscription(event.payload.text);
+        setPartialText(event.payload.partial_text);
+        setWords(event.payload.words);
         transcriptionRef.current = event.payload.text;
+        finalTextRef.current = event.payload.final_text;
+      }
+    );
+
+    // Long dictations send only what changed, with a full `transcription` now and then
+    const unlistenTranscriptionDelta = listen<TranscriptionDeltaEvent>(
+      "transcription-delta",
+      (event) => {
+        finalTextRef.current += event.payload.final_text;
+        const text = finalTextRef.current + event.payload.partial_text;
+        setTranscription(text);
+        setPartialText(event.payload.partial_text);
+        setWords((words) => words.slice(0, event.payload.words_from).concat(event.payload.words));
+        transcriptionRef.current = text;
       }
     );
 
@@ -113,31 +242,50 @@ function App() {
       "transcription-complete",
       (event) => {
         setTranscription(event.payload.text);
+        setPartialText("");
+        setWords(event.payload.words);
+        setIsTranscribing(false);
         transcriptionRef.current = event.payload.text;
       }
     );
 
+    const unlistenTranscribing = listen("transcribing", () => {
+      setIsTranscribing(true);
+    });
+
     const unlistenState = listen<RecordingStateEvent>(
       "recording-state",
       (event) => {
         setIsRecording(event.payload.is_recording);
+        setIsTranscribing(false);
+        setStillListening(false);
         if (event.payload.is_recording) {
           lastRecordingStartRef.current = Date.now();
+          setElapsedSeconds(0);
         }
       }
     );
 
-    const unlistenError = listen<string>("transcription-error", (event) => {
-      setError(event.payload);
+    const unlistenAuthError = listen<string>("auth-error", () => {
+      setApiKeyRejected(true);
+      setApiKeySet(false);
       setIsRecording(false);
-      // If 403 error, clear the API key and show setup
-      if (event.payload.includes("403") || event.payload.includes("Forbidden")) {
-        localStorage.removeItem("soniox_api_key");
-        localStorage.removeItem("soniox_api_key_set");
-        setApiKeySet(false);
-      }
+      setIsTranscribing(false);
     });
 
+    const unlistenError = listen<TranscriptionErrorEvent>(
+      "transcription-error",
+      (event) => {
+        setError(event.payload.message);
+        setIsRecording(false);
+        setIsTranscribing(false);
+        // Rejected key: show setup so the user can enter a new one
+        if (event.payload.kind === "auth") {
+          setApiKeySet(false);
+        }
+      }
+    );
+
     // LIVE TYPING: Type directly to cursor as you speak!
     const unlistenLiveType = listen<TranscriptionEvent>(
       "live-type",
@@ -163,9 +311,87 @@ function App() {
     });
 
     const unlistenStopRequest = listen("stop-recording-request", () => {
-      stopRecording("event:stop-recording-request").then(() =>
-        completeTranscription()
-      );
+      stopRecording("event:stop-recording-request").then((review) => {
+        if (!review) {
+          completeTranscription();
+        }
+      });
+    });
+
+    const unlistenReview = listen<string>("review-transcription", (event) => {
+      setIsRecording(false);
+      setReviewText(event.payload);
+    });
+
+    // Typing failed after the popup closed (e.g. blocked on Wayland)
+    const unlistenTypingError = listen<string>("typing-error", (event) => {
+      setError(event.payload);
+    });
+
+    // Nothing was typed because no text field had focus; the text is on the clipboard
+    const unlistenTypingSkipped = listen<string>("typing-skipped", (event) => {
+      setError(event.payload);
+    });
+
+    // The microphone disconnected and recording moved to another one
+    const unlistenDeviceChanged = listen<DeviceChangedEvent>("device-changed", (event) => {
+      setError(`تم التبديل إلى الميكروفون: ${event.payload.device}`);
+    });
+
+    // The connection can't keep up, so some audio never reached SONIOX
+    const unlistenNetworkSlow = listen("network-slow", () => {
+      setError("الاتصال بطيء، وقد يفوت جزء من الكلام.");
+    });
+
+    // The dictation came back empty; usually a muted or too-quiet microphone
+    const unlistenNoSpeech = listen("no-speech-detected", () => {
+      setError("لم يتم التعرف على أي كلام. تحقق من الميكروفون.");
+    });
+
+    // The history shortcut is showing a past transcription it will type again
+    const unlistenHistoryCandidate = listen<HistoryCandidateEvent>("history-candidate", (event) => {
+      setError(null);
+      setTranscription(event.payload.text);
+      setPartialText("");
+      setWords([]);
+    });
+
+    // Settings went back to defaults; the API key may have been cleared too
+    const unlistenSettingsReset = listen("settings-reset", () => {
+      void invoke<boolean>("has_api_key").then(setApiKeySet);
+    });
+
+    // Nothing but digital silence since recording started: likely a muted mic
+    const unlistenPossiblyMuted = listen("possibly-muted", () => {
+      setError("يبدو أن الميكروفون مكتوم. تحقق من إعدادات الصوت.");
+    });
+
+    // The device shortcut switched microphones; show the new one for a moment
+    let deviceNoticeTimer: ReturnType<typeof setTimeout> | undefined;
+    const unlistenDeviceSwitched = listen<string>("device-switched", (event) => {
+      setDeviceNotice(event.payload);
+      clearTimeout(deviceNoticeTimer);
+      deviceNoticeTimer = setTimeout(() => setDeviceNotice(null), 1500);
+    });
+
+    const unlistenTypingCountdown = listen<number>("typing-countdown", (event) => {
+      setTypingCountdownMs(event.payload > 0 ? event.payload : null);
+    });
+
+    const unlistenStillListening = listen<boolean>("still-listening", (event) => {
+      setStillListening(event.payload);
+    });
+
+    const unlistenPermission = listen<PermissionKind>("permission-required", (event) => {
+      setPermissionRequired(event.payload);
+    });
+
+    const unlistenConnection = listen<ConnectionState>("connection-state", (event) => {
+      setConnectionState(event.payload);
+    });
+
+    const unlistenElapsed = listen<RecordingElapsedEvent>("recording-elapsed", (event) => {
+      setElapsedSeconds(event.payload.seconds);
     });
 
     // Listen for audio level updates
@@ -176,7 +402,11 @@ function App() {
     // Shortcut stop now types text in Rust directly.
     // Frontend should only clear UI state to avoid duplicate typing and delays.
     const unlistenFinishAndType = listen("finish-and-type", () => {
+      setReviewText(null);
+      setIsTranscribing(false);
       setTranscription("");
+      setPartialText("");
+      setWords([]);
       transcriptionRef.current = "";
       lastTypedTextRef.current = "";
       setIsRecording(false);
@@ -184,12 +414,31 @@ function App() {
 
     return () => {
       unlistenTranscription.then((f) => f());
+      unlistenTranscriptionDelta.then((f) => f());
       unlistenComplete.then((f) => f());
+      unlistenTranscribing.then((f) => f());
       unlistenState.then((f) => f());
       unlistenError.then((f) => f());
+      unlistenAuthError.then((f) => f());
       unlistenLiveType.then((f) => f());
       unlistenStartRequest.then((f) => f());
       unlistenStopRequest.then((f) => f());
+      unlistenReview.then((f) => f());
+      unlistenTypingError.then((f) => f());
+      unlistenTypingSkipped.then((f) => f());
+      unlistenDeviceChanged.then((f) => f());
+      unlistenNoSpeech.then((f) => f());
+      unlistenNetworkSlow.then((f) => f());
+      unlistenHistoryCandidate.then((f) => f());
+      unlistenSettingsReset.then((f) => f());
+      unlistenPossiblyMuted.then((f) => f());
+      unlistenDeviceSwitched.then((f) => f());
+      clearTimeout(deviceNoticeTimer);
+      unlistenTypingCountdown.then((f) => f());
+      unlistenStillListening.then((f) => f());
+      unlistenPermission.then((f) => f());
+      unlistenConnection.then((f) => f());
+      unlistenElapsed.then((f) => f());
       unlistenAudioLevel.then((f) => f());
       unlistenFinishAndType.then((f) => f());
     };
@@ -235,16 +484,19 @@ function App() {
     };
 
     void fitWindowToPopup();
-  }, [appWindow, apiKeySet, isRecording, transcription, error]);
+  }, [appWindow, apiKeySet, isRecording, transcription, error, reviewText, permissionRequired]);
 
   // Keyboard shortcuts
   useEffect(() => {
     const handleKeyDown = (e: KeyboardEvent) => {
       if (e.key === "Escape") {
+        setReviewText(null);
         void invoke("cancel_and_hide", { reason: "ui:escape" }).catch((e) =>
           setError(String(e))
         );
         setTranscription("");
+        setPartialText("");
+        setWords([]);
         transcriptionRef.current = "";
         lastTypedTextRef.current = "";
       } else if (e.key === "Enter" && e.ctrlKey && !e.shiftKey) {
@@ -259,7 +511,11 @@ function App() {
           return;
         }
         e.preventDefault();
-        stopRecording("ui:ctrl-enter").then(() => completeTranscription());
+        stopRecording("ui:ctrl-enter").then((review) => {
+          if (!review) {
+            completeTranscription();
+          }
+        });
       }
     };
 
@@ -268,20 +524,36 @@ function App() {
   }, [stopRecording, completeTranscription, isRecording, transcription]);
 
   if (!apiKeySet) {
-    return <ApiKeySetup onSubmit={handleApiKeySubmit} />;
+    return <ApiKeySetup onSubmit={handleApiKeySubmit} rejected={apiKeyRejected} />;
   }
 
   return (
     <RecordingPopup
       isRecording={isRecording}
+      isTranscribing={isTranscribing}
+      stillListening={stillListening}
+      typingCountdownMs={typingCountdownMs}
+      deviceNotice={deviceNotice}
       transcription={transcription}
+      partialText={partialText}
+      words={words}
       error={error}
       audioLevel={audioLevel}
+      elapsedSeconds={elapsedSeconds}
+      connectionState={connectionState}
+      reviewText={reviewText}
+      permissionRequired={permissionRequired}
+      onOpenPermissionSettings={openPermissionSettings}
+      onReviewChange={setReviewText}
+      onConfirm={confirmReview}
+      onDiscard={discardReview}
       onCancel={() => {
         void invoke("cancel_and_hide", { reason: "ui:cancel" }).catch((e) =>
           setError(String(e))
         );
         setTranscription("");
+        setPartialText("");
+        setWords([]);
         transcriptionRef.current = "";
         lastTypedTextRef.current = "";
       }}
@@ -3,9 +3,11 @@ import "../styles/popup.css";
 
 interface ApiKeySetupProps {
   onSubmit: (apiKey: string) => void;
+  // Set when SONIOX refused the previously saved key
+  rejected?: boolean;
 }
 
-function ApiKeySetup({ onSubmit }: ApiKeySetupProps) {
+function ApiKeySetup({ onSubmit, rejected = false }: ApiKeySetupProps) {
   const [apiKey, setApiKey] = useState("");
   const [error, setError] = useState("");
 
@@ -38,6 +40,12 @@ function ApiKeySetup({ onSubmit }: ApiKeySetupProps) {
           </a>
         </p>
 
+        {rejected && !error && (
+          <div className="error-message small">
+            رفض SONIOX المفتاح المحفوظ. تأكد منه وأدخله مرة أخرى.
+          </div>
+        )}
+
         <input
           type="password"
           className="api-input"
@@ -0,0 +1,151 @@
+import { useEffect, useRef, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import "../styles/popup.css";
+import "../styles/editor.css";
+
+// Wait this long after the last keystroke before sending edits to the backend
+const SAVE_DELAY_MS = 300;
+
+interface TranscriptionEvent {
+  text: string;
+  final_text: string;
+}
+
+interface TranscriptionDeltaEvent {
+  final_text: string;
+  partial_text: string;
+}
+
+// Larger window for long-form dictation: finished dictations are added to an
+// editable text, which can then be typed or exported in one go
+function EditorWindow() {
+  const [text, setText] = useState("");
+  // What's being said right now, shown under the text until it's added
+  const [liveText, setLiveText] = useState("");
+  const [exportPath, setExportPath] = useState("");
+  const [message, setMessage] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const finalTextRef = useRef("");
+  const saveTimerRef = useRef<ReturnType<typeof setTimeout> | undefined>(undefined);
+
+  useEffect(() => {
+    void invoke<string>("get_editor_text").then(setText);
+
+    // A dictation was added; the backend holds the whole text
+    const unlistenEditorText = listen<string>("editor-text", (event) => {
+      clearTimeout(saveTimerRef.current);
+      setText(event.payload);
+      setLiveText("");
+    });
+
+    const unlistenTranscription = listen<TranscriptionEvent>("transcription", (event) => {
+      finalTextRef.current = event.payload.final_text;
+      setLiveText(event.payload.text);
+    });
+
+    const unlistenTranscriptionDelta = listen<TranscriptionDeltaEvent>(
+      "transcription-delta",
+      (event) => {
+        finalTextRef.current += event.payload.final_text;
+        setLiveText(finalTextRef.current + event.payload.partial_text);
+      }
+    );
+
+    const unlistenFinish = listen("finish-and-type", () => {
+      setLiveText("");
+    });
+
+    return () => {
+      unlistenEditorText.then((f) => f());
+      unlistenTranscription.then((f) => f());
+      unlistenTranscriptionDelta.then((f) => f());
+      unlistenFinish.then((f) => f());
+      clearTimeout(saveTimerRef.current);
+    };
+  }, []);
+
+  const handleChange = (value: string) => {
+    setText(value);
+    clearTimeout(saveTimerRef.current);
+    saveTimerRef.current = setTimeout(() => {
+      void invoke("set_editor_text", { text: value }).catch((e) => setError(String(e)));
+    }, SAVE_DELAY_MS);
+  };
+
+  // Make sure the backend has the latest edits before acting on them
+  const flushEdits = async () => {
+    clearTimeout(saveTimerRef.current);
+    await invoke("set_editor_text", { text });
+  };
+
+  const typeText = async () => {
+    setError(null);
+    setMessage(null);
+    try {
+      await flushEdits();
+      await invoke("type_editor_text");
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const exportText = async () => {
+    setError(null);
+    setMessage(null);
+    if (!exportPath.trim()) {
+      setError("أدخل مسار الملف أولًا");
+      return;
+    }
+    try {
+      await flushEdits();
+      await invoke("export_editor_text", { path: exportPath.trim() });
+      setMessage("تم حفظ الملف");
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const clearText = () => {
+    setError(null);
+    setMessage(null);
+    handleChange("");
+  };
+
+  return (
+    <div className="editor-container">
+      <textarea
+        className="editor-text"
+        value={text}
+        onChange={(e) => handleChange(e.target.value)}
+        placeholder="ابدأ الإملاء وسيظهر النص هنا"
+        autoFocus
+      />
+
+      {liveText && <div className="editor-live">{liveText}</div>}
+
+      {error && <div className="error-message small">{error}</div>}
+      {message && !error && <div className="editor-message">{message}</div>}
+
+      <div className="editor-actions">
+        <button className="action-btn done" onClick={() => void typeText()}>
+          اكتب النص
+        </button>
+        <button className="action-btn btn-secondary" onClick={clearText}>
+          مسح
+        </button>
+        <input
+          className="api-input editor-path"
+          placeholder="مسار الملف للتصدير"
+          value={exportPath}
+          onChange={(e) => setExportPath(e.target.value)}
+        />
+        <button className="action-btn btn-secondary" onClick={() => void exportText()}>
+          تصدير
+        </button>
+      </div>
+    </div>
+  );
+}
+
+export default EditorWindow;
@@ -1,15 +1,52 @@
 import { useEffect, useRef, useState } from "react";
 import "../styles/popup.css";
 
+// OS permission the app is missing, shown with a button to the right settings pane
+export type PermissionKind = "accessibility" | "microphone";
+
+export interface TranscriptWord {
+  text: string;
+  confidence: number;
+}
+
 interface RecordingPopupProps {
   isRecording: boolean;
+  // Recording stopped and the audio is being transcribed (no live results)
+  isTranscribing: boolean;
+  // Speech is being captured but no new text has arrived for a while
+  stillListening: boolean;
+  // Time left before the dictation is typed, or null when not counting down
+  typingCountdownMs: number | null;
+  // Microphone just switched to with the device shortcut, or null
+  deviceNotice: string | null;
   transcription: string;
+  partialText: string;
+  words: TranscriptWord[];
   error: string | null;
   audioLevel: number;
+  elapsedSeconds: number;
+  connectionState: string;
+  // Text awaiting confirmation, or null when not reviewing
+  reviewText: string | null;
+  // Typing is blocked until macOS Accessibility access is granted
+  permissionRequired: PermissionKind | null;
+  onOpenPermissionSettings: () => void;
+  onReviewChange: (text: string) => void;
+  onConfirm: (text: string) => void;
+  onDiscard: () => void;
   onCancel: () => void;
 }
 
 const BAR_COUNT = 20;
+// Confirmed words below this confidence are underlined as likely mistakes
+const LOW_CONFIDENCE = 0.6;
+
+// Format seconds as m:ss for the recording timer
+function formatElapsed(seconds: number): string {
+  const minutes = Math.floor(seconds / 60);
+  const rest = seconds % 60;
+  return `${minutes}:${rest.toString().padStart(2, "0")}`;
+}
 
 // Generate smooth reactive heights based on mic level with subtle idle motion.
 function generateBarHeights(
@@ -41,9 +78,23 @@ function generateBarHeights(
 
 function RecordingPopup({
   isRecording,
+  isTranscribing,
+  stillListening,
+  typingCountdownMs,
+  deviceNotice,
   transcription,
+  partialText,
+  words,
   error,
   audioLevel,
+  elapsedSeconds,
+  connectionState,
+  reviewText,
+  permissionRequired,
+  onOpenPermissionSettings,
+  onReviewChange,
+  onConfirm,
+  onDiscard,
   onCancel,
 }: RecordingPopupProps) {
   const textRef = useRef<HTMLDivElement>(null);
@@ -68,12 +119,12 @@ function RecordingPopup({
 
   // Animate status text dots
   useEffect(() => {
-    if (!isRecording) return;
+    if (!isRecording && !isTranscribing) return;
     const interval = setInterval(() => {
       setStatusPhase((p) => (p + 1) % 4);
     }, 400);
     return () => clearInterval(interval);
-  }, [isRecording]);
+  }, [isRecording, isTranscribing]);
 
   // Run one continuous visualizer loop while recording.
   useEffect(() => {
@@ -107,10 +158,28 @@ function RecordingPopup({
     return () => clearInterval(interval);
   }, [isRecording]);
 
+  // Confirmed words render solid, the tentative tail is dimmed
+  const hasPartial = partialText.length > 0 && transcription.endsWith(partialText);
+  const confirmedText = hasPartial
+    ? transcription.slice(0, transcription.length - partialText.length)
+    : transcription;
+
   // Get dynamic status text
   const getStatusText = () => {
-    if (!isRecording) return "جاهز للتسجيل";
+    if (reviewText !== null) return "راجع النص قبل الكتابة";
+    if (typingCountdownMs !== null) {
+      return `الكتابة بعد ${Math.ceil(typingCountdownMs / 1000)} ث - ارفع يديك عن لوحة المفاتيح`;
+    }
+    if (deviceNotice !== null) return `الميكروفون: ${deviceNotice}`;
     const dots = ".".repeat(statusPhase);
+    if (isTranscribing) return `جاري النسخ${dots}`;
+    if (!isRecording) return "جاهز للتسجيل";
+    if (connectionState === "connecting") {
+      return `جاري الاتصال${dots}`;
+    }
+    if (stillListening) {
+      return `لا يزال يستمع${dots}`;
+    }
     if (transcription) {
       return `جاري التفريغ${dots}`;
     }
@@ -133,6 +202,9 @@ function RecordingPopup({
             {isRecording && <div className="status-pulse" />}
           </div>
           <span className="status-text">{getStatusText()}</span>
+          {isRecording && (
+            <span className="elapsed-time">{formatElapsed(elapsedSeconds)}</span>
+          )}
         </div>
         <button className="close-btn" onClick={onCancel} title="إلغاء (Esc)">
           <svg width="14" height="14" viewBox="0 0 14 14" fill="none">
@@ -189,15 +261,46 @@ function RecordingPopup({
         <div className="transcription-area" ref={textRef}>
           {error ? (
             <div className="error-message">{error}</div>
+          ) : reviewText !== null ? (
+            <textarea
+              className="review-text"
+              value={reviewText}
+              onChange={(e) => onReviewChange(e.target.value)}
+              onKeyDown={(e) => {
+                if (e.key === "Enter" && e.ctrlKey) {
+                  e.preventDefault();
+                  e.stopPropagation();
+                  onConfirm(reviewText);
+                }
+              }}
+              autoFocus
+            />
           ) : transcription ? (
             <div className="transcription-text">
-              {transcription}
+              {isRecording && words.length > 0
+                ? words.map((word, i) => (
+                    <span key={i}>
+                      {i > 0 && " "}
+                      <span
+                        className={
+                          word.confidence < LOW_CONFIDENCE ? "low-confidence" : undefined
+                        }
+                      >
+                        {word.text}
+                      </span>
+                    </span>
+                  ))
+                : confirmedText}
+              {isRecording && words.length > 0 && hasPartial && " "}
+              {hasPartial && <span className="partial-text">{partialText}</span>}
               <span className="cursor" />
             </div>
           ) : (
             <div className="placeholder">
               {isRecording ? (
                 <span className="listening-text">تحدث الآن...</span>
+              ) : isTranscribing ? (
+                <span className="listening-text">جاري النسخ...</span>
               ) : (
                 <>
                   <div className="shortcut-hint">
@@ -209,6 +312,34 @@ function RecordingPopup({
           )}
         </div>
       </div>
+
+      {permissionRequired && (
+        <div className="permission-notice">
+          <span>
+            {permissionRequired === "microphone"
+              ? "الوصول إلى الميكروفون مرفوض، لذلك لن يُسجَّل أي صوت. اسمح به من إعدادات النظام ثم أعد المحاولة."
+              : "يحتاج التطبيق إلى إذن تسهيلات الاستخدام لكتابة النص. فعّله من إعدادات النظام ثم أعد المحاولة."}
+          </span>
+          <button className="btn btn-primary" onClick={onOpenPermissionSettings}>
+            فتح الإعدادات
+          </button>
+        </div>
+      )}
+
+      {reviewText !== null && (
+        <div className="popup-footer">
+          <button
+            className="btn btn-primary"
+            onClick={() => onConfirm(reviewText)}
+            disabled={!reviewText.trim()}
+          >
+            كتابة
+          </button>
+          <button className="btn btn-secondary" onClick={onDiscard}>
+            تجاهل
+          </button>
+        </div>
+      )}
     </div>
   );
 }
@@ -1,10 +1,12 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import EditorWindow from "./components/EditorWindow";
 import "./styles/globals.css";
 
+// The editor window loads the same page with `?window=editor`
+const isEditor = new URLSearchParams(window.location.search).get("window") === "editor";
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
-  <React.StrictMode>
-    <App />
-  </React.StrictMode>
+  <React.StrictMode>{isEditor ? <EditorWindow /> : <App />}</React.StrictMode>
 );